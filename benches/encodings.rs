@@ -0,0 +1,33 @@
+use criterion::{criterion_group, BatchSize, Criterion};
+use texter::{change::GridIndex, core::text::Text};
+
+fn utf16(c: &mut Criterion) {
+    // a long line of mixed BMP and astral characters
+    let line = "let ゲート = \"😀𐍈\"; // コメント 🦀\t".repeat(64);
+    // char boundaries in UTF-16 code units, from the end so earlier inserts do not shift them
+    let cols: Vec<usize> = line
+        .chars()
+        .scan(0, |col, c| {
+            *col += c.len_utf16();
+            Some(*col)
+        })
+        .step_by(37)
+        .collect::<Vec<_>>()
+        .into_iter()
+        .rev()
+        .collect();
+    let text = Text::new_utf16(format!("{line}\n{line}"));
+    c.bench_function("utf16_edit_mixed_line", |b| {
+        b.iter_batched(
+            || text.clone(),
+            |mut t| {
+                for &col in &cols {
+                    t.insert("x", GridIndex { row: 1, col }, &mut ()).unwrap();
+                }
+            },
+            BatchSize::SmallInput,
+        );
+    });
+}
+
+criterion_group!(benches, utf16);
@@ -0,0 +1,36 @@
+use criterion::{black_box, criterion_group, BatchSize, Criterion};
+use texter::core::text::Text;
+
+fn eol(c: &mut Criterion) {
+    // the sample file only contains "\n" line breaks, repeated to get a large file
+    let large = include_str!("sample_file.txt").repeat(32);
+    let mut group = c.benchmark_group("eol_scan");
+    group.bench_function("default", |b| {
+        b.iter_batched(
+            || large.clone(),
+            |s| black_box(Text::new(s)),
+            BatchSize::LargeInput,
+        );
+    });
+    group.bench_function("lf_only", |b| {
+        b.iter_batched(
+            || large.clone(),
+            |s| black_box(Text::new_lf_only(s)),
+            BatchSize::LargeInput,
+        );
+    });
+    group.finish();
+
+    // large enough to be searched in parallel when the "rayon" feature is enabled, run with and
+    // without the feature to compare
+    let very_large = include_str!("sample_file.txt").repeat(256);
+    c.bench_function("eol_scan_large_file", |b| {
+        b.iter_batched(
+            || very_large.clone(),
+            |s| black_box(Text::new(s)),
+            BatchSize::LargeInput,
+        );
+    });
+}
+
+criterion_group!(benches, eol);
@@ -1,6 +1,13 @@
+mod encodings;
+mod eol;
 mod text;
 mod text_lines;
 
 use criterion::criterion_main;
 
-criterion_main!(text::benches, text_lines::benches);
+criterion_main!(
+    encodings::benches,
+    eol::benches,
+    text::benches,
+    text_lines::benches
+);
@@ -2,6 +2,7 @@ use criterion::{criterion_group, BatchSize, Criterion};
 use texter::{
     change::{Change, GridIndex},
     core::text::Text,
+    updateables::UpdateContext,
 };
 
 fn text(c: &mut Criterion) {
@@ -114,6 +115,36 @@ fn text(c: &mut Criterion) {
             BatchSize::SmallInput,
         );
     });
+
+    // `()` skips copying the old EOL positions, the closure reads them and does not
+    let mut group = c.benchmark_group("insert_char");
+    group.bench_function("no_updateable", |b| {
+        b.iter_batched_ref(
+            || text.clone(),
+            |text| {
+                for col in 0..100 {
+                    text.insert("c", GridIndex { row: 200, col }, &mut ())
+                        .unwrap();
+                }
+            },
+            BatchSize::SmallInput,
+        );
+    });
+    group.bench_function("needs_old_state", |b| {
+        b.iter_batched_ref(
+            || text.clone(),
+            |text| {
+                for col in 0..100 {
+                    text.insert("c", GridIndex { row: 200, col }, &mut |_: UpdateContext| {
+                        Ok(())
+                    })
+                    .unwrap();
+                }
+            },
+            BatchSize::SmallInput,
+        );
+    });
+    group.finish();
 }
 
 criterion_group!(benches, text);
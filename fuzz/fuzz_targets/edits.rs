@@ -0,0 +1,17 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use texter::{change::Change, core::text::Text};
+
+// Applies random changes to a text and checks that its fields stay in sync with the string.
+//
+// Run with `cargo fuzz run edits` from the repository root.
+fuzz_target!(|input: (String, Vec<Change<'static>>)| {
+    let (s, changes) = input;
+    let mut t = Text::new(s);
+    for change in changes {
+        // out of bounds positions are expected to return an error rather than panic
+        let _ = t.update(change, &mut ());
+        assert!(t.verify_invariants());
+    }
+});
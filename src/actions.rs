@@ -0,0 +1,1036 @@
+//! Common editing actions that can be performed on a [`Text`].
+//!
+//! An action computes the [`Change`]s needed to perform an edit, which can then be applied with
+//! [`Text::apply`]. Since the changes are applied through the usual update methods, any
+//! [`Updateable`][`crate::updateables::Updateable`] is kept in sync as well.
+use std::ops::Range;
+
+use crate::{
+    change::{Change, GridIndex},
+    core::text::Text,
+    utils::eol_start,
+};
+
+/// The [`Change`]s produced by an [`Actionable`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum ActionKind<'a> {
+    /// A single change.
+    Single(Change<'a>),
+    /// Multiple changes that are applied in order.
+    ///
+    /// The positions of each change are relative to the content after the previous changes are
+    /// applied. An empty list performs no change.
+    Multi(Vec<Change<'a>>),
+}
+
+/// An action that can be performed on a [`Text`].
+pub trait Actionable {
+    /// Computes the [`Change`]s needed to perform the action on the provided [`Text`].
+    ///
+    /// The positions of the changes are in the [`Text`]'s expected encoding.
+    fn action<'a>(&'a self, text: &Text) -> ActionKind<'a>;
+}
+
+/// Inserts a line break at the end of the [`Text`] if it does not already end with one.
+///
+/// The inserted line break is the same as the first line break of the [`Text`], or `\n` if there
+/// is none. An empty [`Text`] is left as is.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct EnsureTrailingNewline;
+
+impl Actionable for EnsureTrailingNewline {
+    fn action<'a>(&'a self, text: &Text) -> ActionKind<'a> {
+        if text.text.is_empty() || text.ends_with_newline() {
+            return ActionKind::Multi(vec![]);
+        }
+
+        let eol = match text.br_indexes.0.get(1) {
+            Some(&i) => &text.text[eol_start(&text.text, i)..=i],
+            None => "\n",
+        };
+
+        ActionKind::Single(Change::Insert {
+            at: text.end_position(),
+            text: eol.to_string().into(),
+        })
+    }
+}
+
+/// Removes the spaces and tabs at the end of every line.
+///
+/// A [`Change::Delete`] is produced for each line that has trailing whitespace. The EOL bytes
+/// are not modified.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TrimTrailingWhitespace;
+
+impl Actionable for TrimTrailingWhitespace {
+    fn action<'a>(&'a self, text: &Text) -> ActionKind<'a> {
+        // the changes are in reverse order so that each change does not modify the positions of
+        // the following changes
+        let changes = (0..text.line_count())
+            .rev()
+            .map(|row| (row, text.get_row(row).expect("row should exist")))
+            .filter_map(|(row, line)| {
+                let trimmed = line.trim_end_matches([' ', '\t']);
+                (trimmed.len() != line.len()).then(|| Change::Delete {
+                    start: GridIndex {
+                        row,
+                        col: text.encoded_len(trimmed),
+                    },
+                    end: GridIndex {
+                        row,
+                        col: text.encoded_len(line),
+                    },
+                })
+            })
+            .collect();
+
+        ActionKind::Multi(changes)
+    }
+}
+
+/// Converts the tabs in the leading indentation of every line to spaces.
+///
+/// The value is the width of a tab. A tab is expanded to the next column that is a multiple of
+/// the width, so any spaces before a tab are taken in to account. Whitespace after the first
+/// non-whitespace character is not modified.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TabsToSpaces(pub usize);
+
+impl Actionable for TabsToSpaces {
+    fn action<'a>(&'a self, text: &Text) -> ActionKind<'a> {
+        map_indentation(text, |indent| {
+            if !indent.contains('\t') {
+                return None;
+            }
+
+            Some(" ".repeat(indent_width(indent, self.0)))
+        })
+    }
+}
+
+/// Converts the spaces in the leading indentation of every line to tabs.
+///
+/// The value is the width of a tab. Any remaining spaces that do not fill a full tab are kept
+/// after the tabs. Whitespace after the first non-whitespace character is not modified. A width
+/// of zero performs no change.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SpacesToTabs(pub usize);
+
+impl Actionable for SpacesToTabs {
+    fn action<'a>(&'a self, text: &Text) -> ActionKind<'a> {
+        if self.0 == 0 {
+            return ActionKind::Multi(vec![]);
+        }
+
+        map_indentation(text, |indent| {
+            let width = indent_width(indent, self.0);
+            let new = "\t".repeat(width / self.0) + &" ".repeat(width % self.0);
+            (new != indent).then_some(new)
+        })
+    }
+}
+
+/// Moves the line at the provided row above the previous line.
+///
+/// The EOL bytes between the two lines are kept in place. Moving the first line, or a row that
+/// does not exist, performs no change.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MoveLineUp(pub usize);
+
+impl Actionable for MoveLineUp {
+    fn action<'a>(&'a self, text: &Text) -> ActionKind<'a> {
+        match self.0.checked_sub(1) {
+            Some(upper) => swap_lines(text, upper),
+            None => ActionKind::Multi(vec![]),
+        }
+    }
+}
+
+/// Moves the line at the provided row below the next line.
+///
+/// The EOL bytes between the two lines are kept in place. Moving the last line, or a row that
+/// does not exist, performs no change.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MoveLineDown(pub usize);
+
+impl Actionable for MoveLineDown {
+    fn action<'a>(&'a self, text: &Text) -> ActionKind<'a> {
+        swap_lines(text, self.0)
+    }
+}
+
+/// Swaps the content of the provided row with the content of the row after it.
+fn swap_lines<'a>(text: &Text, upper: usize) -> ActionKind<'a> {
+    let (Some(upper_line), Some(lower_line)) = (text.get_row(upper), text.get_row(upper + 1))
+    else {
+        return ActionKind::Multi(vec![]);
+    };
+
+    let eol_start = text.br_indexes.row_start(upper).expect("row should exist") + upper_line.len();
+    let eol_end = text
+        .br_indexes
+        .row_start(upper + 1)
+        .expect("row should exist");
+    let swapped = [lower_line, &text.text[eol_start..eol_end], upper_line].concat();
+
+    ActionKind::Single(Change::Replace {
+        start: GridIndex { row: upper, col: 0 },
+        end: GridIndex {
+            row: upper + 1,
+            col: text.encoded_len(lower_line),
+        },
+        text: swapped.into(),
+    })
+}
+
+/// Adds a level of indentation to the start of every line in the provided rows.
+///
+/// When `use_tabs` is `true` a single tab is inserted, otherwise `width` spaces are inserted.
+/// Empty lines and rows past the end of the [`Text`] are left as is.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Indent {
+    pub rows: Range<usize>,
+    pub width: usize,
+    pub use_tabs: bool,
+}
+
+impl Actionable for Indent {
+    fn action<'a>(&'a self, text: &Text) -> ActionKind<'a> {
+        let indent = if self.use_tabs {
+            "\t".to_string()
+        } else {
+            " ".repeat(self.width)
+        };
+
+        let changes = clamp_rows(text, &self.rows)
+            .rev()
+            .filter(|&row| !text.get_row(row).expect("row should exist").is_empty())
+            .map(|row| Change::Insert {
+                at: GridIndex { row, col: 0 },
+                text: indent.clone().into(),
+            })
+            .collect();
+
+        ActionKind::Multi(changes)
+    }
+}
+
+/// Removes a level of indentation from the start of every line in the provided rows.
+///
+/// The removed indentation is the shortest leading run of spaces and tabs that is at least
+/// `width` columns wide, with a tab expanding to the next multiple of `width`. A line with less
+/// indentation than `width` has all of its indentation removed. Empty lines and rows past the
+/// end of the [`Text`] are left as is. A width of zero performs no change.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Dedent {
+    pub rows: Range<usize>,
+    pub width: usize,
+}
+
+impl Actionable for Dedent {
+    fn action<'a>(&'a self, text: &Text) -> ActionKind<'a> {
+        if self.width == 0 {
+            return ActionKind::Multi(vec![]);
+        }
+
+        let changes = clamp_rows(text, &self.rows)
+            .rev()
+            .filter_map(|row| {
+                let line = text.get_row(row).expect("row should exist");
+                let mut width = 0;
+                let removed = line
+                    .bytes()
+                    .take_while(|&b| {
+                        if width >= self.width {
+                            return false;
+                        }
+
+                        width = match b {
+                            b' ' => width + 1,
+                            b'\t' => (width / self.width + 1) * self.width,
+                            _ => return false,
+                        };
+                        true
+                    })
+                    .count();
+
+                (removed > 0).then(|| Change::Delete {
+                    start: GridIndex { row, col: 0 },
+                    end: GridIndex {
+                        row,
+                        col: text.encoded_len(&line[..removed]),
+                    },
+                })
+            })
+            .collect();
+
+        ActionKind::Multi(changes)
+    }
+}
+
+/// Comments out the lines in the provided rows, or uncomments them if all of them are already
+/// commented out.
+///
+/// A line is commented out if `prefix` follows its leading indentation. If any line lacks the
+/// prefix, it is inserted after the indentation of every line that lacks it. Otherwise the prefix
+/// is removed from every line. Lines that only contain whitespace are left as is, and rows past
+/// the end of the [`Text`] are ignored. An empty prefix performs no change.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ToggleLineComment<'p> {
+    pub rows: Range<usize>,
+    pub prefix: &'p str,
+}
+
+impl Actionable for ToggleLineComment<'_> {
+    fn action<'a>(&'a self, text: &Text) -> ActionKind<'a> {
+        if self.prefix.is_empty() {
+            return ActionKind::Multi(vec![]);
+        }
+
+        // the row, the UTF-8 length of its indentation, and whether it is commented out
+        let lines: Vec<(usize, usize, bool)> = clamp_rows(text, &self.rows)
+            .filter_map(|row| {
+                let line = text.get_row(row).expect("row should exist");
+                let content = line.trim_start();
+                (!content.is_empty()).then(|| {
+                    let indent = line.len() - content.len();
+                    (row, indent, content.starts_with(self.prefix))
+                })
+            })
+            .collect();
+        let uncomment = lines.iter().all(|&(_, _, commented)| commented);
+
+        let changes = lines
+            .into_iter()
+            .rev()
+            .filter(|&(_, _, commented)| uncomment || !commented)
+            .map(|(row, indent, _)| {
+                let line = text.get_row(row).expect("row should exist");
+                let start = GridIndex {
+                    row,
+                    col: text.encoded_len(&line[..indent]),
+                };
+                if uncomment {
+                    Change::Delete {
+                        start,
+                        end: GridIndex {
+                            row,
+                            col: text.encoded_len(&line[..indent + self.prefix.len()]),
+                        },
+                    }
+                } else {
+                    Change::Insert {
+                        at: start,
+                        text: self.prefix.into(),
+                    }
+                }
+            })
+            .collect();
+
+        ActionKind::Multi(changes)
+    }
+}
+
+/// Sorts the lines in the provided rows.
+///
+/// The lines are compared with [`str::cmp`], so the sort is case-sensitive and uppercase letters
+/// are placed before lowercase letters. The sort is stable and the EOL bytes between the lines
+/// are kept in place. Rows past the end of the [`Text`] are ignored.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SortLines {
+    pub rows: Range<usize>,
+    pub descending: bool,
+}
+
+impl Actionable for SortLines {
+    fn action<'a>(&'a self, text: &Text) -> ActionKind<'a> {
+        reorder_lines(text, &self.rows, |lines| {
+            if self.descending {
+                lines.sort_by(|a, b| b.cmp(a));
+            } else {
+                lines.sort();
+            }
+        })
+    }
+}
+
+/// Reverses the order of the lines in the provided rows.
+///
+/// The EOL bytes between the lines are kept in place. Rows past the end of the [`Text`] are
+/// ignored.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReverseLines(pub Range<usize>);
+
+impl Actionable for ReverseLines {
+    fn action<'a>(&'a self, text: &Text) -> ActionKind<'a> {
+        reorder_lines(text, &self.0, |lines| lines.reverse())
+    }
+}
+
+/// Removes the consecutive duplicate lines in the provided rows.
+///
+/// Only a line that is the same as the line before it is removed, similar to the `uniq` command,
+/// so duplicates that are not next to each other are kept. A line is removed along with the EOL
+/// bytes before it, so the line that is kept is followed by the EOL bytes of the last line of
+/// its run. Rows past the end of the [`Text`] are ignored.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UniqueLines(pub Range<usize>);
+
+impl Actionable for UniqueLines {
+    fn action<'a>(&'a self, text: &Text) -> ActionKind<'a> {
+        let rows = clamp_rows(text, &self.0);
+        // reverse order so that each change does not modify the positions of the following changes
+        let changes = (rows.start + 1..rows.end)
+            .rev()
+            .filter_map(|row| {
+                let prev = text.get_row(row - 1).expect("row should exist");
+                let line = text.get_row(row).expect("row should exist");
+                (prev == line).then(|| Change::Delete {
+                    start: GridIndex {
+                        row: row - 1,
+                        col: text.encoded_len(prev),
+                    },
+                    end: GridIndex {
+                        row,
+                        col: text.encoded_len(line),
+                    },
+                })
+            })
+            .collect();
+
+        ActionKind::Multi(changes)
+    }
+}
+
+/// Swaps the [`char`] before the provided position with the [`char`] after it.
+///
+/// Only the [`char`]s of the position's row are swapped, so a position at the start or the end
+/// of a row, such as the start of the [`Text`], performs no change. A position that does not
+/// exist also performs no change.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TransposeChars(pub GridIndex);
+
+impl Actionable for TransposeChars {
+    fn action<'a>(&'a self, text: &Text) -> ActionKind<'a> {
+        let GridIndex { row, col } = self.0;
+        let Some(line) = text.get_row(row) else {
+            return ActionKind::Multi(vec![]);
+        };
+        let Ok(col) = text.col_to_utf8(row, line, col) else {
+            return ActionKind::Multi(vec![]);
+        };
+        let col = col.min(line.len());
+        let (Some(before), Some(after)) =
+            (line[..col].chars().next_back(), line[col..].chars().next())
+        else {
+            return ActionKind::Multi(vec![]);
+        };
+
+        let start = col - before.len_utf8();
+        let end = col + after.len_utf8();
+        ActionKind::Single(Change::Replace {
+            start: GridIndex {
+                row,
+                col: text.encoded_len(&line[..start]),
+            },
+            end: GridIndex {
+                row,
+                col: text.encoded_len(&line[..end]),
+            },
+            text: [after, before].iter().collect::<String>().into(),
+        })
+    }
+}
+
+/// A cursor movement that can be computed on a [`Text`].
+///
+/// Unlike an [`Actionable`], a navigation does not change the [`Text`] and only computes the
+/// position the cursor should move to.
+pub trait Navigable {
+    /// Computes the position the cursor should move to in the provided [`Text`].
+    ///
+    /// The positions are in the [`Text`]'s expected encoding.
+    fn navigate(&self, text: &Text) -> GridIndex;
+}
+
+/// Moves the cursor to the first non-whitespace character of its row, or to the start of the row
+/// if it is already at or before it.
+///
+/// Pressing home twice on an indented line first moves to the indentation, and then to column
+/// zero. See [`Text::smart_home`] for the details.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SmartHome(pub GridIndex);
+
+impl Navigable for SmartHome {
+    fn navigate(&self, text: &Text) -> GridIndex {
+        text.smart_home(self.0)
+    }
+}
+
+/// Moves the cursor to the start of its row.
+///
+/// If the row does not exist, the position is returned as is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LineStart(pub GridIndex);
+
+impl Navigable for LineStart {
+    fn navigate(&self, text: &Text) -> GridIndex {
+        match text.get_row(self.0.row) {
+            Some(_) => self.0.with_col(0),
+            None => self.0,
+        }
+    }
+}
+
+/// Moves the cursor to the end of its row, before the EOL bytes.
+///
+/// If the row does not exist, the position is returned as is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LineEnd(pub GridIndex);
+
+impl Navigable for LineEnd {
+    fn navigate(&self, text: &Text) -> GridIndex {
+        match text.row_len_in_encoding(self.0.row) {
+            Some(len) => self.0.with_col(len),
+            None => self.0,
+        }
+    }
+}
+
+/// Reorders the lines in the provided rows with `f`.
+///
+/// The EOL bytes between the lines are kept in place, so the last line of the rows is never
+/// followed by new EOL bytes even if it is the last line of the [`Text`]. A single
+/// [`Change::Replace`] is produced over the rows if the order of the lines is changed.
+fn reorder_lines<'a>(
+    text: &Text,
+    rows: &Range<usize>,
+    f: impl FnOnce(&mut [&str]),
+) -> ActionKind<'a> {
+    let rows = clamp_rows(text, rows);
+    let original: Vec<&str> = rows
+        .clone()
+        .map(|row| text.get_row(row).expect("row should exist"))
+        .collect();
+    let mut lines = original.clone();
+    f(&mut lines);
+    if lines == original {
+        return ActionKind::Multi(vec![]);
+    }
+
+    let mut reordered = String::new();
+    for (i, (row, line)) in rows.clone().zip(&lines).enumerate() {
+        reordered.push_str(line);
+        if i + 1 < lines.len() {
+            let eol_start =
+                text.br_indexes.row_start(row).expect("row should exist") + original[i].len();
+            let eol_end = text
+                .br_indexes
+                .row_start(row + 1)
+                .expect("row should exist");
+            reordered.push_str(&text.text[eol_start..eol_end]);
+        }
+    }
+
+    ActionKind::Single(Change::Replace {
+        start: GridIndex {
+            row: rows.start,
+            col: 0,
+        },
+        end: GridIndex {
+            row: rows.end - 1,
+            col: text.encoded_len(original[original.len() - 1]),
+        },
+        text: reordered.into(),
+    })
+}
+
+/// Clamps the provided rows to the rows that exist in the [`Text`].
+fn clamp_rows(text: &Text, rows: &Range<usize>) -> Range<usize> {
+    let end = rows.end.min(text.line_count());
+    rows.start.min(end)..end
+}
+
+/// Replaces the leading indentation of every line with the value returned by `f`.
+///
+/// If `f` returns [`None`] the line is left as is.
+fn map_indentation<'a>(text: &Text, mut f: impl FnMut(&str) -> Option<String>) -> ActionKind<'a> {
+    // reverse order so that each change does not modify the positions of the following changes
+    let changes = (0..text.line_count())
+        .rev()
+        .filter_map(|row| {
+            let line = text.get_row(row).expect("row should exist");
+            let indent = &line[..line.len() - line.trim_start_matches([' ', '\t']).len()];
+            let new = f(indent)?;
+            Some(Change::Replace {
+                start: GridIndex { row, col: 0 },
+                end: GridIndex {
+                    row,
+                    col: text.encoded_len(indent),
+                },
+                text: new.into(),
+            })
+        })
+        .collect();
+
+    ActionKind::Multi(changes)
+}
+
+/// The visual width of an indentation consisting of spaces and tabs.
+fn indent_width(indent: &str, tab_width: usize) -> usize {
+    indent.bytes().fold(0, |width, b| match b {
+        b'\t' if tab_width > 0 => (width / tab_width + 1) * tab_width,
+        b'\t' => width,
+        _ => width + 1,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod ensure_trailing_newline {
+        use super::*;
+
+        fn ensured(s: &str) -> Text {
+            let mut t = Text::new_utf16(s.into());
+            t.apply(&EnsureTrailingNewline, &mut ()).unwrap();
+            t
+        }
+
+        #[test]
+        fn missing() {
+            let t = ensured("Hello\nゲート");
+            assert_eq!(t.text, "Hello\nゲート\n");
+            assert_eq!(t.br_indexes, [0, 5, 15]);
+        }
+
+        #[test]
+        fn crlf() {
+            let t = ensured("Hello\r\nWorld");
+            assert_eq!(t.text, "Hello\r\nWorld\r\n");
+            assert_eq!(t.br_indexes, [0, 6, 13]);
+        }
+
+        #[test]
+        fn present() {
+            assert_eq!(
+                EnsureTrailingNewline.action(&Text::new("Hello\n".into())),
+                ActionKind::Multi(vec![])
+            );
+            assert_eq!(ensured("Hello\r\n").text, "Hello\r\n");
+            assert_eq!(ensured("\n").text, "\n");
+            assert_eq!(ensured("").text, "");
+        }
+
+        #[test]
+        fn multiple() {
+            let t = ensured("Hello\n\n\n");
+            assert_eq!(t.text, "Hello\n\n\n");
+            assert_eq!(t.br_indexes, [0, 5, 6, 7]);
+        }
+    }
+
+    mod trim_trailing_whitespace {
+        use super::*;
+        use crate::core::eol_indexes::EolIndexes;
+
+        #[test]
+        fn trim() {
+            let mut t = Text::new_utf16("a ゲート \t\r\n \t \n\tb\n\nc  ".into());
+            let ActionKind::Multi(changes) = TrimTrailingWhitespace.action(&t) else {
+                panic!("expected multiple changes");
+            };
+            assert_eq!(changes.len(), 3);
+
+            t.apply(&TrimTrailingWhitespace, &mut ()).unwrap();
+            assert_eq!(t.text, "a ゲート\r\n\n\tb\n\nc");
+            assert_eq!(t.br_indexes, [0, 12, 13, 16, 17]);
+            assert_eq!(t.br_indexes, EolIndexes::new(&t.text));
+        }
+
+        #[test]
+        fn nothing_to_trim() {
+            let t = Text::new("a\n b\r\n".into());
+            assert_eq!(TrimTrailingWhitespace.action(&t), ActionKind::Multi(vec![]));
+        }
+    }
+
+    mod indentation {
+        use super::*;
+        use crate::core::eol_indexes::EolIndexes;
+
+        const S: &str = "\tfn a() {\r\n\t\tlet x =\t1;\n  \tb\n    c\n\n      d";
+
+        #[test]
+        fn tabs_to_spaces() {
+            let mut t = Text::new_utf16(S.into());
+            t.apply(&TabsToSpaces(4), &mut ()).unwrap();
+            assert_eq!(
+                t.text,
+                "    fn a() {\r\n        let x =\t1;\n    b\n    c\n\n      d"
+            );
+            assert_eq!(t.br_indexes, EolIndexes::new(&t.text));
+
+            let mut t = Text::new(S.into());
+            t.apply(&TabsToSpaces(2), &mut ()).unwrap();
+            assert_eq!(
+                t.text,
+                "  fn a() {\r\n    let x =\t1;\n    b\n    c\n\n      d"
+            );
+            assert_eq!(t.br_indexes, EolIndexes::new(&t.text));
+        }
+
+        #[test]
+        fn spaces_to_tabs() {
+            let mut t = Text::new_utf16(S.into());
+            t.apply(&SpacesToTabs(4), &mut ()).unwrap();
+            assert_eq!(t.text, "\tfn a() {\r\n\t\tlet x =\t1;\n\tb\n\tc\n\n\t  d");
+            assert_eq!(t.br_indexes, EolIndexes::new(&t.text));
+
+            let mut t = Text::new(S.into());
+            t.apply(&SpacesToTabs(2), &mut ()).unwrap();
+            assert_eq!(
+                t.text,
+                "\tfn a() {\r\n\t\tlet x =\t1;\n\t\tb\n\t\tc\n\n\t\t\td"
+            );
+            assert_eq!(t.br_indexes, EolIndexes::new(&t.text));
+        }
+
+        #[test]
+        fn zero_width() {
+            let t = Text::new(S.into());
+            assert_eq!(SpacesToTabs(0).action(&t), ActionKind::Multi(vec![]));
+        }
+    }
+
+    mod move_line {
+        use super::*;
+        use crate::core::eol_indexes::EolIndexes;
+
+        const S: &str = "first\r\nゲート\nthird\n\nlast";
+
+        #[test]
+        fn up() {
+            let mut t = Text::new_utf16(S.into());
+            t.apply(&MoveLineUp(2), &mut ()).unwrap();
+            assert_eq!(t.text, "first\r\nthird\nゲート\n\nlast");
+            assert_eq!(t.br_indexes, [0, 6, 12, 22, 23]);
+
+            t.apply(&MoveLineUp(4), &mut ()).unwrap();
+            assert_eq!(t.text, "first\r\nthird\nゲート\nlast\n");
+            assert_eq!(t.br_indexes, EolIndexes::new(&t.text));
+        }
+
+        #[test]
+        fn down() {
+            let mut t = Text::new_utf16(S.into());
+            t.apply(&MoveLineDown(1), &mut ()).unwrap();
+            assert_eq!(t.text, "first\r\nthird\nゲート\n\nlast");
+            assert_eq!(t.br_indexes, [0, 6, 12, 22, 23]);
+
+            t.apply(&MoveLineDown(0), &mut ()).unwrap();
+            assert_eq!(t.text, "third\r\nfirst\nゲート\n\nlast");
+            assert_eq!(t.br_indexes, EolIndexes::new(&t.text));
+        }
+
+        #[test]
+        fn no_op() {
+            let t = Text::new(S.into());
+            assert_eq!(MoveLineUp(0).action(&t), ActionKind::Multi(vec![]));
+            assert_eq!(MoveLineDown(4).action(&t), ActionKind::Multi(vec![]));
+            assert_eq!(MoveLineUp(10).action(&t), ActionKind::Multi(vec![]));
+            assert_eq!(MoveLineDown(10).action(&t), ActionKind::Multi(vec![]));
+        }
+    }
+
+    mod indent {
+        use super::*;
+        use crate::core::eol_indexes::EolIndexes;
+
+        const S: &str = "fn a() {\r\n\tlet x = 1;\n  \tlet y = 2;\n\n      ゲート();\n   z\n}";
+
+        #[test]
+        fn indent() {
+            let mut t = Text::new_utf16(S.into());
+            t.apply(
+                &Indent {
+                    rows: 1..6,
+                    width: 4,
+                    use_tabs: false,
+                },
+                &mut (),
+            )
+            .unwrap();
+            assert_eq!(
+                t.text,
+                "fn a() {\r\n    \tlet x = 1;\n      \tlet y = 2;\n\n          ゲート();\n       z\n}"
+            );
+            assert_eq!(t.br_indexes, EolIndexes::new(&t.text));
+
+            let mut t = Text::new(S.into());
+            t.apply(
+                &Indent {
+                    rows: 5..10,
+                    width: 4,
+                    use_tabs: true,
+                },
+                &mut (),
+            )
+            .unwrap();
+            assert_eq!(
+                t.text,
+                "fn a() {\r\n\tlet x = 1;\n  \tlet y = 2;\n\n      ゲート();\n\t   z\n\t}"
+            );
+            assert_eq!(t.br_indexes, EolIndexes::new(&t.text));
+        }
+
+        #[test]
+        fn dedent() {
+            let mut t = Text::new_utf16(S.into());
+            t.apply(
+                &Dedent {
+                    rows: 0..7,
+                    width: 4,
+                },
+                &mut (),
+            )
+            .unwrap();
+            assert_eq!(
+                t.text,
+                "fn a() {\r\nlet x = 1;\nlet y = 2;\n\n  ゲート();\nz\n}"
+            );
+            assert_eq!(t.br_indexes, EolIndexes::new(&t.text));
+
+            let mut t = Text::new(S.into());
+            t.apply(
+                &Dedent {
+                    rows: 2..5,
+                    width: 2,
+                },
+                &mut (),
+            )
+            .unwrap();
+            assert_eq!(
+                t.text,
+                "fn a() {\r\n\tlet x = 1;\n\tlet y = 2;\n\n    ゲート();\n   z\n}"
+            );
+            assert_eq!(t.br_indexes, EolIndexes::new(&t.text));
+        }
+
+        #[test]
+        fn no_op() {
+            let t = Text::new(S.into());
+            let indent = Indent {
+                rows: 7..10,
+                width: 4,
+                use_tabs: false,
+            };
+            assert_eq!(indent.action(&t), ActionKind::Multi(vec![]));
+            let dedent = Dedent {
+                rows: 0..7,
+                width: 0,
+            };
+            assert_eq!(dedent.action(&t), ActionKind::Multi(vec![]));
+        }
+    }
+
+    mod sort_lines {
+        use super::*;
+
+        const S: &str = "header\ncherry\r\nApple\nゲート\nbanana\nfooter";
+
+        #[test]
+        fn ascending() {
+            let mut t = Text::new_utf16(S.into());
+            let sort = SortLines {
+                rows: 1..5,
+                descending: false,
+            };
+            t.apply(&sort, &mut ()).unwrap();
+            assert_eq!(t.text, "header\nApple\r\nbanana\ncherry\nゲート\nfooter");
+            assert_eq!(t.br_indexes, [0, 6, 13, 20, 27, 37]);
+            assert_eq!(sort.action(&t), ActionKind::Multi(vec![]));
+        }
+
+        #[test]
+        fn descending() {
+            let mut t = Text::new(S.into());
+            let sort = SortLines {
+                rows: 3..10,
+                descending: true,
+            };
+            t.apply(&sort, &mut ()).unwrap();
+            assert_eq!(t.text, "header\ncherry\r\nApple\nゲート\nfooter\nbanana");
+            assert_eq!(t.br_indexes, [0, 6, 14, 20, 30, 37]);
+        }
+    }
+
+    mod reverse_lines {
+        use super::*;
+
+        #[test]
+        fn last_lines() {
+            let mut t = Text::new_utf16("header\none\r\ntwo\nゲート\nfour".into());
+            t.apply(&ReverseLines(1..5), &mut ()).unwrap();
+            assert_eq!(t.text, "header\nfour\r\nゲート\ntwo\none");
+            assert_eq!(t.br_indexes, [0, 6, 12, 22, 26]);
+        }
+
+        #[test]
+        fn middle_lines() {
+            let mut t = Text::new("one\ntwo\nthree\nfour\n".into());
+            t.apply(&ReverseLines(0..4), &mut ()).unwrap();
+            assert_eq!(t.text, "four\nthree\ntwo\none\n");
+            assert_eq!(t.br_indexes, [0, 4, 10, 14, 18]);
+
+            assert_eq!(ReverseLines(2..3).action(&t), ActionKind::Multi(vec![]));
+        }
+    }
+
+    mod unique_lines {
+        use super::*;
+        use crate::core::eol_indexes::EolIndexes;
+
+        #[test]
+        fn adjacent() {
+            let mut t = Text::new_utf16("a\nゲート\r\nゲート\nゲート\nb\nb".into());
+            t.apply(&UniqueLines(0..6), &mut ()).unwrap();
+            assert_eq!(t.text, "a\nゲート\nb");
+            assert_eq!(t.br_indexes, [0, 1, 11]);
+
+            let mut t = Text::new("b\r\nb\nb".into());
+            t.apply(&UniqueLines(0..3), &mut ()).unwrap();
+            assert_eq!(t.text, "b");
+            assert_eq!(t.br_indexes, [0]);
+        }
+
+        #[test]
+        fn non_adjacent() {
+            let mut t = Text::new("a\nb\na\n\n\nb\nb\nb".into());
+            t.apply(&UniqueLines(1..7), &mut ()).unwrap();
+            assert_eq!(t.text, "a\nb\na\n\nb\nb");
+            assert_eq!(t.br_indexes, EolIndexes::new(&t.text));
+
+            assert_eq!(UniqueLines(0..5).action(&t), ActionKind::Multi(vec![]));
+        }
+    }
+
+    mod toggle_line_comment {
+        use super::*;
+        use crate::core::eol_indexes::EolIndexes;
+
+        const COMMENTED: &str = "fn main() {\r\n    // ゲート();\n\n    // let a = 1;\n}";
+
+        #[test]
+        fn mixed() {
+            let mut t =
+                Text::new_utf16("fn main() {\r\n    // ゲート();\n\n    let a = 1;\n}".into());
+            let action = ToggleLineComment {
+                rows: 1..4,
+                prefix: "// ",
+            };
+            t.apply(&action, &mut ()).unwrap();
+            assert_eq!(t.text, COMMENTED);
+            assert_eq!(t.br_indexes, EolIndexes::new(&t.text));
+        }
+
+        #[test]
+        fn commented() {
+            let mut t = Text::new_utf16(COMMENTED.into());
+            let action = ToggleLineComment {
+                rows: 1..4,
+                prefix: "// ",
+            };
+            t.apply(&action, &mut ()).unwrap();
+            assert_eq!(t.text, "fn main() {\r\n    ゲート();\n\n    let a = 1;\n}");
+
+            // toggled back
+            t.apply(&action, &mut ()).unwrap();
+            assert_eq!(t.text, COMMENTED);
+        }
+
+        #[test]
+        fn no_op() {
+            let t = Text::new("a\n  \n".into());
+            let empty = ActionKind::Multi(vec![]);
+            let action = |rows, prefix| ToggleLineComment { rows, prefix };
+            assert_eq!(action(0..1, "").action(&t), empty);
+            assert_eq!(action(1..3, "#").action(&t), empty);
+            assert_eq!(action(5..8, "#").action(&t), empty);
+        }
+    }
+
+    mod transpose_chars {
+        use super::*;
+
+        #[test]
+        fn ascii() {
+            let mut t = Text::new("abc\r\ndef".into());
+            t.apply(&TransposeChars(GridIndex { row: 0, col: 1 }), &mut ())
+                .unwrap();
+            assert_eq!(t.text, "bac\r\ndef");
+            t.apply(&TransposeChars(GridIndex { row: 1, col: 2 }), &mut ())
+                .unwrap();
+            assert_eq!(t.text, "bac\r\ndfe");
+        }
+
+        #[test]
+        fn multibyte() {
+            let mut t = Text::new_utf16("aゲ😀b".into());
+            t.apply(&TransposeChars(GridIndex { row: 0, col: 2 }), &mut ())
+                .unwrap();
+            assert_eq!(t.text, "a😀ゲb");
+            assert_eq!(t.text.as_bytes()[1..8], *"😀ゲ".as_bytes());
+            t.apply(&TransposeChars(GridIndex { row: 0, col: 4 }), &mut ())
+                .unwrap();
+            assert_eq!(t.text, "a😀bゲ");
+        }
+
+        #[test]
+        fn no_op() {
+            let t = Text::new_utf16("ab\ncd😀".into());
+            let empty = ActionKind::Multi(vec![]);
+            // the start of the text, the start and end of a row
+            for (row, col) in [(0, 0), (1, 0), (0, 2), (1, 4), (3, 0)] {
+                let action = TransposeChars(GridIndex { row, col });
+                assert_eq!(action.action(&t), empty, "{row}:{col}");
+            }
+            // in between a surrogate pair
+            let action = TransposeChars(GridIndex { row: 1, col: 3 });
+            assert_eq!(action.action(&t), empty);
+        }
+    }
+
+    mod navigation {
+        use super::*;
+
+        #[test]
+        fn smart_home() {
+            let t = Text::new_utf16("fn main() {\r\n    ゲート();\n}".into());
+            let first = SmartHome(GridIndex { row: 1, col: 7 }).navigate(&t);
+            assert_eq!(first, GridIndex { row: 1, col: 4 });
+            let second = SmartHome(first).navigate(&t);
+            assert_eq!(second, GridIndex { row: 1, col: 0 });
+            assert_eq!(SmartHome(second).navigate(&t), second);
+        }
+
+        #[test]
+        fn line_boundaries() {
+            let t = Text::new_utf16("fn main() {\r\n    ゲート();\n}".into());
+            let pos = GridIndex { row: 1, col: 5 };
+            assert_eq!(LineStart(pos).navigate(&t), GridIndex { row: 1, col: 0 });
+            assert_eq!(LineEnd(pos).navigate(&t), GridIndex { row: 1, col: 10 });
+            assert_eq!(
+                LineEnd(GridIndex { row: 0, col: 0 }).navigate(&t),
+                GridIndex { row: 0, col: 11 }
+            );
+
+            // rows that do not exist
+            let pos = GridIndex { row: 3, col: 2 };
+            assert_eq!(LineStart(pos).navigate(&t), pos);
+            assert_eq!(LineEnd(pos).navigate(&t), pos);
+        }
+    }
+}
@@ -4,9 +4,8 @@
 use std::borrow::Cow;
 
 use crate::{
-    core::text::Text,
-    error::{Error, Result},
-    utils::trim_eol_from_end,
+    core::{lines::EolMode, text::Text},
+    error::{Encoding, Error, Result},
 };
 
 /// A [`Change`] to be performed on a [`Text`].
@@ -39,10 +38,81 @@ pub enum Change<'a> {
 /// A structure denoting text positions for any encoding.
 ///
 /// Both fields are used as an index, which means the first row is always zero.
-#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
-pub struct GridIndex {
+///
+/// By default the column is in the [`Text`]'s expected encoding. To denote a column that is
+/// always counted in bytes or [`char`]s regardless of the encoding, [`ByteCol`] or [`CharCol`]
+/// can be used as the column type.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct GridIndex<C = usize> {
     pub row: usize,
-    pub col: usize,
+    pub col: C,
+}
+
+/// A column counted in UTF-8 bytes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ByteCol(pub usize);
+
+/// A column counted in [`char`]s.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CharCol(pub usize);
+
+mod sealed {
+    pub trait Sealed {}
+    impl Sealed for usize {}
+    impl Sealed for super::ByteCol {}
+    impl Sealed for super::CharCol {}
+}
+
+/// A column type of a [`GridIndex`].
+///
+/// This trait is sealed, and is implemented for [`usize`] which is a column in the [`Text`]'s
+/// expected encoding, [`ByteCol`], and [`CharCol`].
+pub trait Column: sealed::Sealed + Copy {
+    /// Converts the column of `line`, which is the content of `row`, to a UTF-8 column.
+    #[doc(hidden)]
+    fn to_utf8(self, text: &Text, row: usize, line: &str) -> Result<usize>;
+}
+
+impl Column for usize {
+    #[inline]
+    fn to_utf8(self, text: &Text, row: usize, line: &str) -> Result<usize> {
+        text.check_col(row, line, self)?;
+        text.col_to_utf8(row, line, self)
+    }
+}
+
+impl Column for ByteCol {
+    #[inline]
+    fn to_utf8(self, _: &Text, row: usize, line: &str) -> Result<usize> {
+        if self.0 > line.len() {
+            return Err(Error::OutOfBoundsColumn {
+                row,
+                max: line.len(),
+                current: self.0,
+            });
+        }
+        if !line.is_char_boundary(self.0) {
+            return Err(Error::InBetweenCharBoundries {
+                encoding: Encoding::UTF8,
+                row,
+                col: self.0,
+                line_len: line.len(),
+            });
+        }
+
+        Ok(self.0)
+    }
+}
+
+impl Column for CharCol {
+    fn to_utf8(self, _: &Text, row: usize, line: &str) -> Result<usize> {
+        let mut chars = line.char_indices().map(|(i, _)| i).chain([line.len()]);
+        chars.nth(self.0).ok_or_else(|| Error::OutOfBoundsColumn {
+            row,
+            max: line.chars().count(),
+            current: self.0,
+        })
+    }
 }
 
 #[cfg_attr(docsrs, doc(cfg(feature = "tree-sitter")))]
@@ -167,56 +237,156 @@ mod lspt {
     }
 }
 
+#[cfg_attr(docsrs, doc(cfg(feature = "arbitrary")))]
+#[cfg(feature = "arbitrary")]
+mod arb {
+    use arbitrary::{Arbitrary, Result, Unstructured};
+
+    use super::{Change, GridIndex};
+
+    /// The maximum row generated, kept small so that most positions exist in a fuzzed text.
+    const MAX_ROW: usize = 16;
+    /// The maximum column generated, kept small so that most positions exist in a fuzzed text.
+    const MAX_COL: usize = 64;
+
+    impl<'a> Arbitrary<'a> for GridIndex {
+        fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+            Ok(GridIndex {
+                row: u.int_in_range(0..=MAX_ROW)?,
+                col: u.int_in_range(0..=MAX_COL)?,
+            })
+        }
+
+        fn size_hint(depth: usize) -> (usize, Option<usize>) {
+            arbitrary::size_hint::and(usize::size_hint(depth), usize::size_hint(depth))
+        }
+    }
+
+    impl<'a> Arbitrary<'a> for Change<'static> {
+        fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+            Ok(match u.choose_index(4)? {
+                0 => Change::Delete {
+                    start: u.arbitrary()?,
+                    end: u.arbitrary()?,
+                },
+                1 => Change::Insert {
+                    at: u.arbitrary()?,
+                    text: String::arbitrary(u)?.into(),
+                },
+                2 => Change::Replace {
+                    start: u.arbitrary()?,
+                    end: u.arbitrary()?,
+                    text: String::arbitrary(u)?.into(),
+                },
+                _ => Change::ReplaceFull(String::arbitrary(u)?.into()),
+            })
+        }
+    }
+}
+
+impl Change<'_> {
+    /// Converts the [`Change`] to one that owns its text, cloning it if it is borrowed.
+    pub fn into_owned(self) -> Change<'static> {
+        match self {
+            Change::Delete { start, end } => Change::Delete { start, end },
+            Change::Insert { at, text } => Change::Insert {
+                at,
+                text: Cow::Owned(text.into_owned()),
+            },
+            Change::Replace { start, end, text } => Change::Replace {
+                start,
+                end,
+                text: Cow::Owned(text.into_owned()),
+            },
+            Change::ReplaceFull(text) => Change::ReplaceFull(Cow::Owned(text.into_owned())),
+        }
+    }
+}
+
 impl GridIndex {
+    /// Returns a copy of the [`GridIndex`] with the provided column.
+    #[inline]
+    pub fn with_col(self, col: usize) -> Self {
+        Self { col, ..self }
+    }
+
+    /// Returns a copy of the [`GridIndex`] with the column incremented by one.
+    ///
+    /// This does not check if the new column exists, see [`GridIndex::clamped_to`].
+    #[inline]
+    pub fn next_col(self) -> Self {
+        self.with_col(self.col + 1)
+    }
+
+    /// Returns a copy of the [`GridIndex`] that is clamped to the positions that exist in the
+    /// provided [`Text`].
+    ///
+    /// The row is clamped to the last row, and the column is clamped to the length of the row in
+    /// the [`Text`]'s expected encoding.
+    pub fn clamped_to(&self, text: &Text) -> GridIndex {
+        let row = self.row.min(text.line_count() - 1);
+        let line = text
+            .get_row(row)
+            .expect("the row is clamped to the last row");
+        let line_len = text.encoded_len(line);
+        GridIndex {
+            row,
+            col: self.col.min(line_len),
+        }
+    }
+
     /// Transform the positions from the [`Text`]'s expected encoding, to UTF-8 positions.
     ///
     /// If the row value of the [`GridIndex`] is same as the number of rows, this will insert a
-    /// line break.
-    pub fn normalize(&mut self, text: &mut Text) -> Result<()> {
+    /// line break. Returns `true` if a line break was inserted. The inserted line break is a `\r`
+    /// if the string ends with a lone `\r` line break, as a `\n` would be merged in to a single
+    /// `\r\n` line break instead of starting a new row.
+    ///
+    /// If [`Text::set_checked_columns`] is enabled, a column past the end of the row returns
+    /// [`Error::OutOfBoundsColumn`]. If an error is returned, the inserted line break is removed.
+    pub fn normalize(&mut self, text: &mut Text) -> Result<bool> {
         let br_indexes = &mut text.br_indexes;
         let mut row_count = br_indexes.row_count();
-        if self.row == row_count.get() {
-            br_indexes.insert_index(self.row, br_indexes.last_row_start());
-            text.text.push('\n');
+        let inserted_br = self.row == row_count.get();
+        if inserted_br {
+            br_indexes.insert_index(self.row, text.text.len());
+            let eol = if text.eol_mode() != EolMode::LfOnly && text.text.ends_with('\r') {
+                '\r'
+            } else {
+                '\n'
+            };
+            text.text.push(eol);
             row_count = row_count.saturating_add(1);
         }
 
-        let row_start = br_indexes
-            .row_start(self.row)
-            .ok_or(Error::oob_row(row_count, self.row))?;
-        let pure_line = if !br_indexes.is_last_row(self.row) && row_count.get() > 1 {
-            let row_end = br_indexes
-                .row_start(self.row + 1)
-                .ok_or(Error::oob_row(row_count, self.row))?;
-            let base_line = &text.text[row_start..row_end];
-            trim_eol_from_end(base_line)
-        } else {
-            &text.text[row_start..]
-        };
-
-        self.col = (text.encoding[0])(pure_line, self.col)?;
+        let col = text
+            .get_row(self.row)
+            .ok_or(Error::oob_row(row_count, self.row))
+            .and_then(|pure_line| {
+                text.check_col(self.row, pure_line, self.col)?;
+                text.col_to_utf8(self.row, pure_line, self.col)
+            });
+        match col {
+            Ok(col) => self.col = col,
+            Err(err) => {
+                if inserted_br {
+                    text.text.pop();
+                    text.br_indexes.0.remove(self.row);
+                }
+                return Err(err);
+            }
+        }
 
-        Ok(())
+        Ok(inserted_br)
     }
 
     /// Transform the positions to the [`Text`]'s expected encoding, from UTF-8 positions.
     pub fn denormalize(&mut self, text: &Text) -> Result<()> {
-        let br_indexes = &text.br_indexes;
-        let row_count = br_indexes.row_count();
-        let row_start = br_indexes
-            .row_start(self.row)
-            .ok_or(Error::oob_row(row_count, self.row))?;
-        let pure_line = if !br_indexes.is_last_row(self.row) && row_count.get() > 1 {
-            let row_end = br_indexes
-                .row_start(self.row + 1)
-                .ok_or(Error::oob_row(row_count, self.row))?;
-            let base_line = &text.text[row_start..row_end];
-            trim_eol_from_end(base_line)
-        } else {
-            &text.text[row_start..]
-        };
+        let pure_line = text
+            .get_row(self.row)
+            .ok_or(Error::oob_row(text.br_indexes.row_count(), self.row))?;
 
-        self.col = (text.encoding[1])(pure_line, self.col)?;
+        self.col = text.col_from_utf8(self.row, pure_line, self.col)?;
 
         Ok(())
     }
@@ -229,3 +399,199 @@ pub(crate) fn correct_positions(start: &mut GridIndex, end: &mut GridIndex) {
         std::mem::swap(start, end);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::GridIndex;
+    #[cfg(feature = "arbitrary")]
+    use crate::core::eol_indexes::EolIndexes;
+    use crate::core::text::Text;
+
+    #[test]
+    fn with_col() {
+        let pos = GridIndex { row: 2, col: 3 };
+        assert_eq!(pos.with_col(7), GridIndex { row: 2, col: 7 });
+        assert_eq!(pos.next_col(), GridIndex { row: 2, col: 4 });
+    }
+
+    mod column {
+        use super::*;
+        use crate::{
+            change::{ByteCol, CharCol},
+            error::{Encoding, Error},
+        };
+
+        const S: &str = "ab\r\nゲ😀c";
+
+        #[test]
+        fn byte_col() {
+            let t = Text::new_utf16(S.into());
+            // the column is used as is, even though the text expects UTF-16 columns
+            let pos = GridIndex {
+                row: 1,
+                col: ByteCol(7),
+            };
+            assert_eq!(t.byte_index(pos), Ok(11));
+            assert_eq!(&t.text[t.byte_index(pos).unwrap()..], "c");
+            assert_eq!(
+                t.byte_index(GridIndex {
+                    row: 1,
+                    col: ByteCol(4)
+                }),
+                Err(Error::InBetweenCharBoundries {
+                    encoding: Encoding::UTF8,
+                    row: 1,
+                    col: 4,
+                    line_len: 8
+                })
+            );
+            assert_eq!(
+                t.byte_index(GridIndex {
+                    row: 0,
+                    col: ByteCol(3)
+                }),
+                Err(Error::OutOfBoundsColumn {
+                    row: 0,
+                    max: 2,
+                    current: 3
+                })
+            );
+
+            let mut t = t;
+            t.insert_at_byte("!", t.byte_index(pos).unwrap(), &mut ())
+                .unwrap();
+            assert_eq!(t.text, "ab\r\nゲ😀!c");
+        }
+
+        #[test]
+        fn char_col() {
+            let t = Text::new_utf16(S.into());
+            let at = |row, col| {
+                t.byte_index(GridIndex {
+                    row,
+                    col: CharCol(col),
+                })
+            };
+            assert_eq!(at(1, 0), Ok(4));
+            assert_eq!(at(1, 2), Ok(11));
+            // the end of the row
+            assert_eq!(at(1, 3), Ok(12));
+            assert_eq!(
+                at(1, 4),
+                Err(Error::OutOfBoundsColumn {
+                    row: 1,
+                    max: 3,
+                    current: 4
+                })
+            );
+            assert!(matches!(at(2, 0), Err(Error::OutOfBoundsRow { .. })));
+        }
+
+        #[test]
+        fn encoded_col() {
+            let t = Text::new_utf16(S.into());
+            assert_eq!(t.byte_index(GridIndex { row: 1, col: 3 }), Ok(11));
+            // clamped to the end of the row
+            assert_eq!(t.byte_index(GridIndex { row: 0, col: 5 }), Ok(2));
+            assert!(t.byte_index(GridIndex { row: 1, col: 2 }).is_err());
+        }
+    }
+
+    #[test]
+    fn hash() {
+        use std::collections::HashSet;
+
+        let positions: HashSet<GridIndex> = [(0, 1), (1, 0), (0, 1), (2, 2)]
+            .into_iter()
+            .map(|(row, col)| GridIndex { row, col })
+            .collect();
+        assert_eq!(positions.len(), 3);
+        assert!(positions.contains(&GridIndex { row: 1, col: 0 }));
+        assert!(!positions.contains(&GridIndex { row: 0, col: 0 }));
+    }
+
+    #[test]
+    fn normalize_inserted_br() {
+        let mut t = Text::new("ab\nc".into());
+        let mut pos = GridIndex { row: 1, col: 1 };
+        assert!(!pos.normalize(&mut t).unwrap());
+        assert_eq!(t.text, "ab\nc");
+
+        let mut pos = GridIndex { row: 2, col: 0 };
+        assert!(pos.normalize(&mut t).unwrap());
+        assert_eq!(t.text, "ab\nc\n");
+        assert_eq!(t.br_indexes, [0, 2, 4]);
+    }
+
+    #[test]
+    fn normalize_inserted_br_after_cr() {
+        let mut t = Text::new("a\r".into());
+        t.insert("x", GridIndex { row: 2, col: 0 }, &mut ())
+            .unwrap();
+        assert_eq!(t.text, "a\r\rx");
+        assert_eq!(t.br_indexes, [0, 1, 2]);
+        assert!(t.verify_invariants());
+
+        // a "\r" is not a line break in this mode
+        let mut t = Text::new_lf_only("a\r".into());
+        t.insert("x", GridIndex { row: 1, col: 0 }, &mut ())
+            .unwrap();
+        assert_eq!(t.text, "a\r\nx");
+        assert!(t.verify_invariants());
+    }
+
+    #[test]
+    fn normalize_utf16_end_of_line_astral() {
+        let mut t = Text::new_utf16("ab😀\nc😀".into());
+        let mut pos = GridIndex { row: 0, col: 4 };
+        assert!(!pos.normalize(&mut t).unwrap());
+        assert_eq!(pos, GridIndex { row: 0, col: 6 });
+        pos.denormalize(&t).unwrap();
+        assert_eq!(pos, GridIndex { row: 0, col: 4 });
+
+        // last line without a trailing EOL
+        let mut pos = GridIndex { row: 1, col: 3 };
+        assert!(!pos.normalize(&mut t).unwrap());
+        assert_eq!(pos, GridIndex { row: 1, col: 5 });
+
+        t.insert("!", GridIndex { row: 0, col: 4 }, &mut ())
+            .unwrap();
+        t.insert("?", GridIndex { row: 1, col: 3 }, &mut ())
+            .unwrap();
+        assert_eq!(t.text, "ab😀!\nc😀?");
+    }
+
+    #[test]
+    fn clamped_to() {
+        let t = Text::new_utf16("ゲート\r\nab\n".into());
+        let clamp = |row, col| GridIndex { row, col }.clamped_to(&t);
+        assert_eq!(clamp(0, 2), GridIndex { row: 0, col: 2 });
+        // short line
+        assert_eq!(clamp(0, 10), GridIndex { row: 0, col: 3 });
+        assert_eq!(clamp(1, 3), GridIndex { row: 1, col: 2 });
+        // out of range row
+        assert_eq!(clamp(2, 1), GridIndex { row: 2, col: 0 });
+        assert_eq!(clamp(9, 1), GridIndex { row: 2, col: 0 });
+    }
+
+    #[cfg(feature = "arbitrary")]
+    #[test]
+    fn arbitrary() {
+        use arbitrary::{Arbitrary, Unstructured};
+
+        use super::Change;
+
+        let bytes: Vec<u8> = (0..=255).cycle().take(4096).collect();
+        let mut u = Unstructured::new(&bytes);
+        let mut t = Text::new("Hello\nWorld".into());
+        for _ in 0..32 {
+            let change = Change::arbitrary(&mut u).unwrap();
+            if let Change::Delete { start, end } | Change::Replace { start, end, .. } = change {
+                assert!(start.row <= 16 && start.col <= 64);
+                assert!(end.row <= 16 && end.col <= 64);
+            }
+            let _ = t.update(change, &mut ());
+            assert_eq!(t.br_indexes, EolIndexes::new(&t.text));
+        }
+    }
+}
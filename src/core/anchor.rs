@@ -0,0 +1,249 @@
+//! Positions that move along with the edits performed on a [`Text`].
+//!
+//! [`Text`]: crate::core::text::Text
+use crate::{
+    change::GridIndex,
+    error::Result,
+    updateables::{UpdateContext, Updateable},
+};
+
+/// The side an [`Anchor`] sticks to when text is inserted at its position.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Bias {
+    /// The [`Anchor`] stays before the inserted text.
+    #[default]
+    Left,
+    /// The [`Anchor`] moves after the inserted text.
+    Right,
+}
+
+/// A position that is moved as the content around it changes.
+///
+/// The position is a UTF-8 position, the same as the positions provided in an [`UpdateContext`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Anchor {
+    pub position: GridIndex,
+    pub bias: Bias,
+}
+
+impl Anchor {
+    /// Creates a new [`Anchor`] at the provided UTF-8 position.
+    pub fn new(position: GridIndex, bias: Bias) -> Self {
+        Self { position, bias }
+    }
+}
+
+/// An [`Updateable`] that keeps a set of [`Anchor`]s in sync with a [`Text`].
+///
+/// Text inserted or deleted before an [`Anchor`] shifts it, and text inserted at its position
+/// shifts it depending on its [`Bias`]. An [`Anchor`] inside of a deleted or replaced range
+/// collapses to the start of the edit, or to the end of the replacement with [`Bias::Right`].
+/// When the full content is replaced, every [`Anchor`] is treated as being inside of the replaced
+/// range.
+///
+/// [`Text`]: crate::core::text::Text
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AnchorSet {
+    anchors: Vec<Anchor>,
+}
+
+impl AnchorSet {
+    /// Creates a new [`AnchorSet`] with no [`Anchor`]s.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an [`Anchor`] to the set, and returns its index.
+    pub fn push(&mut self, anchor: Anchor) -> usize {
+        self.anchors.push(anchor);
+        self.anchors.len() - 1
+    }
+
+    /// Returns the [`Anchor`] at the provided index.
+    pub fn get(&self, index: usize) -> Option<&Anchor> {
+        self.anchors.get(index)
+    }
+
+    /// Returns the [`Anchor`]s in the order they were added.
+    pub fn anchors(&self) -> &[Anchor] {
+        &self.anchors
+    }
+
+    /// Returns the number of [`Anchor`]s in the set.
+    pub fn len(&self) -> usize {
+        self.anchors.len()
+    }
+
+    /// Returns `true` if the set contains no [`Anchor`]s.
+    pub fn is_empty(&self) -> bool {
+        self.anchors.is_empty()
+    }
+}
+
+impl Updateable for AnchorSet {
+    fn update(&mut self, ctx: UpdateContext) -> Result<()> {
+        let old_br = ctx.old_breaklines;
+        let old_str = ctx.old_str;
+        let (edited, text) = ctx.edited_bytes();
+        let (start, end) = (edited.start, edited.end);
+
+        // the byte at the provided index of the new string
+        let new_byte = |i: usize| {
+            if i < start {
+                old_str.as_bytes()[i]
+            } else if i < start + text.len() {
+                text.as_bytes()[i - start]
+            } else {
+                old_str.as_bytes()[i - text.len() + (end - start)]
+            }
+        };
+
+        let new_br = ctx.breaklines;
+        for anchor in &mut self.anchors {
+            let old = old_br
+                .row_start(anchor.position.row)
+                .map_or(old_str.len(), |rs| rs + anchor.position.col)
+                .min(old_str.len());
+            let new = if anchor.bias == Bias::Right && (start..=end).contains(&old) {
+                start + text.len()
+            } else {
+                ctx.remap_byte(old)
+            };
+
+            let row = new_br.0[1..].partition_point(|&eol| eol < new);
+            let row_start = new_br.row_start(row).expect("row should exist");
+            // the edit may have joined a "\r" and a "\n" around the anchor, in which case it is
+            // moved out of the line break to the end of the row
+            let in_crlf = new_br.0.get(row + 1) == Some(&new)
+                && new > 0
+                && new_byte(new) == b'\n'
+                && new_byte(new - 1) == b'\r';
+            anchor.position = GridIndex {
+                row,
+                col: new - row_start - in_crlf as usize,
+            };
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Anchor, AnchorSet, Bias};
+    use crate::{change::GridIndex, core::text::Text};
+
+    fn anchors(t: &str, positions: &[(usize, usize, Bias)]) -> (Text, AnchorSet) {
+        let mut set = AnchorSet::new();
+        for &(row, col, bias) in positions {
+            set.push(Anchor::new(GridIndex { row, col }, bias));
+        }
+        (Text::new(t.into()), set)
+    }
+
+    fn positions(set: &AnchorSet) -> Vec<(usize, usize)> {
+        set.anchors()
+            .iter()
+            .map(|a| (a.position.row, a.position.col))
+            .collect()
+    }
+
+    #[test]
+    fn insert() {
+        let (mut t, mut set) = anchors(
+            "abc\ndef",
+            &[
+                (0, 1, Bias::Left),
+                (1, 1, Bias::Left),
+                (1, 1, Bias::Right),
+                (1, 2, Bias::Left),
+            ],
+        );
+        // before, at, and after the anchors
+        t.insert("X\nY", GridIndex { row: 1, col: 1 }, &mut set)
+            .unwrap();
+        assert_eq!(t.text, "abc\ndX\nYef");
+        assert_eq!(positions(&set), [(0, 1), (1, 1), (2, 1), (2, 2)]);
+
+        t.insert("\n", GridIndex { row: 0, col: 0 }, &mut set)
+            .unwrap();
+        assert_eq!(positions(&set), [(1, 1), (2, 1), (3, 1), (3, 2)]);
+    }
+
+    #[test]
+    fn delete() {
+        let (mut t, mut set) = anchors(
+            "abc\ndef\nghi",
+            &[
+                (0, 1, Bias::Left),
+                (0, 2, Bias::Left),
+                (1, 1, Bias::Right),
+                (2, 0, Bias::Left),
+                (2, 2, Bias::Left),
+            ],
+        );
+        t.delete(
+            GridIndex { row: 0, col: 2 },
+            GridIndex { row: 2, col: 0 },
+            &mut set,
+        )
+        .unwrap();
+        assert_eq!(t.text, "abghi");
+        // the anchors inside of the range collapse to its start
+        assert_eq!(positions(&set), [(0, 1), (0, 2), (0, 2), (0, 2), (0, 4)]);
+    }
+
+    #[test]
+    fn replace() {
+        let (mut t, mut set) = anchors(
+            "abc\ndef",
+            &[(0, 1, Bias::Left), (0, 2, Bias::Left), (0, 2, Bias::Right)],
+        );
+        t.replace(
+            "XY\nZ",
+            GridIndex { row: 0, col: 1 },
+            GridIndex { row: 0, col: 3 },
+            &mut set,
+        )
+        .unwrap();
+        assert_eq!(t.text, "aXY\nZ\ndef");
+        assert_eq!(positions(&set), [(0, 1), (0, 1), (1, 1)]);
+    }
+
+    #[test]
+    fn joined_crlf() {
+        let (mut t, mut set) = anchors("a\rb", &[(1, 0, Bias::Left), (1, 0, Bias::Right)]);
+        t.insert("\n", GridIndex { row: 1, col: 0 }, &mut set)
+            .unwrap();
+        assert_eq!(t.text, "a\r\nb");
+        assert_eq!(positions(&set), [(0, 1), (1, 0)]);
+    }
+
+    #[test]
+    fn new_row() {
+        let (mut t, mut set) = anchors("a", &[(0, 1, Bias::Left), (0, 1, Bias::Right)]);
+        t.insert("b", GridIndex { row: 1, col: 0 }, &mut set)
+            .unwrap();
+        assert_eq!(t.text, "a\nb");
+        assert_eq!(positions(&set), [(0, 1), (0, 1)]);
+    }
+
+    #[test]
+    fn alongside() {
+        use crate::updateables::ChangeRecorder;
+
+        let (mut t, mut set) = anchors("abc", &[(0, 1, Bias::Left)]);
+        let mut updateables = (ChangeRecorder::new(), set);
+        t.insert("X", GridIndex { row: 0, col: 0 }, &mut updateables)
+            .unwrap();
+        (_, set) = updateables;
+        assert_eq!(positions(&set), [(0, 2)]);
+    }
+
+    #[test]
+    fn replace_full() {
+        let (mut t, mut set) = anchors("abc", &[(0, 1, Bias::Left), (0, 2, Bias::Right)]);
+        t.replace_full("xy\nz".into(), &mut set).unwrap();
+        assert_eq!(positions(&set), [(0, 0), (1, 1)]);
+    }
+}
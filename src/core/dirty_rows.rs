@@ -0,0 +1,107 @@
+use std::ops::Range;
+
+/// Tracks the rows that were modified since the last time it was cleared.
+///
+/// The rows are stored as sorted ranges that never overlap or touch each other. When an edit
+/// inserts or removes rows, the ranges after the edit are shifted so that they keep pointing to
+/// the same content.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct DirtyRows(Vec<Range<usize>>);
+
+impl DirtyRows {
+    /// Mark the rows affected by an edit as dirty.
+    ///
+    /// `start..=old_end` are the rows the edit spanned before it was applied, and
+    /// `start..=new_end` are the rows the edited content spans after it was applied.
+    pub(crate) fn mark(&mut self, start: usize, old_end: usize, new_end: usize) {
+        debug_assert!(start <= old_end && start <= new_end);
+        let mut edited = start..new_end + 1;
+        let first = self.0.partition_point(|r| r.end < start);
+        let mut last = first;
+        for r in &mut self.0[first..] {
+            if r.start > old_end + 1 {
+                // rows after the edit keep pointing to the same content
+                r.start = r.start + new_end - old_end;
+                r.end = r.end + new_end - old_end;
+                continue;
+            }
+
+            edited.start = edited.start.min(r.start);
+            if r.end > old_end + 1 {
+                edited.end = edited.end.max(r.end + new_end - old_end);
+            }
+            last += 1;
+        }
+
+        self.0.splice(first..last, [edited]);
+    }
+
+    /// The number of bytes allocated on the heap to track the dirty rows.
+    pub(crate) fn heap_size(&self) -> usize {
+        self.0.capacity() * size_of::<Range<usize>>()
+    }
+
+    /// Returns an iterator over the dirty rows in ascending order.
+    pub(crate) fn rows(&self) -> impl Iterator<Item = usize> + '_ {
+        self.0.iter().flat_map(Clone::clone)
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.0.clear();
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::single_range_in_vec_init)]
+mod tests {
+    use super::DirtyRows;
+
+    #[test]
+    fn mark_single_row() {
+        let mut d = DirtyRows::default();
+        d.mark(3, 3, 3);
+        assert_eq!(d.0, [3..4]);
+        assert_eq!(d.rows().collect::<Vec<_>>(), [3]);
+    }
+
+    #[test]
+    fn mark_merges_adjacent() {
+        let mut d = DirtyRows::default();
+        d.mark(3, 3, 3);
+        d.mark(4, 4, 4);
+        d.mark(1, 1, 1);
+        assert_eq!(d.0, [1..2, 3..5]);
+        d.mark(2, 2, 2);
+        assert_eq!(d.0, [1..5]);
+    }
+
+    #[test]
+    fn mark_shifts_following_rows() {
+        let mut d = DirtyRows::default();
+        d.mark(10, 10, 10);
+        // two rows were inserted before the dirty row
+        d.mark(2, 2, 4);
+        assert_eq!(d.0, [2..5, 12..13]);
+        // three rows were removed before the dirty row
+        d.mark(0, 3, 0);
+        assert_eq!(d.0, [0..2, 9..10]);
+    }
+
+    #[test]
+    fn mark_overlapping_removal() {
+        let mut d = DirtyRows::default();
+        d.mark(2, 2, 6);
+        assert_eq!(d.0, [2..7]);
+        // rows 4..=8 are joined in to row 4, row 5 and 6 no longer exist
+        d.mark(4, 8, 4);
+        assert_eq!(d.0, [2..5]);
+    }
+
+    #[test]
+    fn clear() {
+        let mut d = DirtyRows::default();
+        d.mark(2, 2, 6);
+        d.clear();
+        assert_eq!(d.rows().count(), 0);
+    }
+}
@@ -1,6 +1,8 @@
-use crate::error::Error;
+use crate::error::Encoding;
 
-pub(crate) type EncodingFn = fn(&str, usize) -> Result<usize, Error>;
+/// Converts a column of a line, returning [`None`] if the column is in between the boundaries of
+/// the encoding.
+pub(crate) type EncodingFn = fn(&str, usize) -> Option<usize>;
 pub(crate) type EncodingFns = [EncodingFn; 2];
 
 pub(crate) const UTF8: EncodingFns = [utf8::to, utf8::from];
@@ -9,78 +11,113 @@ pub(crate) const UTF16: EncodingFns = [utf16::to, utf16::from];
 
 pub(crate) const UTF32: EncodingFns = [utf32::to, utf32::from];
 
-pub mod utf8 {
-
-    use crate::error::{Encoding, Error};
+#[cfg(feature = "unicode-segmentation")]
+pub(crate) const GRAPHEMES: EncodingFns = [graphemes::to, graphemes::from];
+
+/// Get the conversion functions for the provided [`Encoding`].
+pub(crate) fn encoding_fns(encoding: Encoding) -> EncodingFns {
+    match encoding {
+        Encoding::UTF8 => UTF8,
+        Encoding::UTF16 => UTF16,
+        Encoding::UTF32 => UTF32,
+        #[cfg(feature = "unicode-segmentation")]
+        Encoding::Graphemes => GRAPHEMES,
+    }
+}
 
+pub mod utf8 {
     #[inline]
-    pub(super) fn to(s: &str, nth: usize) -> Result<usize, Error> {
+    pub(super) fn to(s: &str, nth: usize) -> Option<usize> {
         if !s.is_char_boundary(nth) {
-            return Err(Error::InBetweenCharBoundries {
-                encoding: Encoding::UTF8,
-            });
+            return None;
         }
 
-        Ok(nth.min(s.len()))
+        Some(nth.min(s.len()))
     }
 
     #[inline]
-    pub(super) fn from(s: &str, nth: usize) -> Result<usize, Error> {
+    pub(super) fn from(s: &str, nth: usize) -> Option<usize> {
         to(s, nth)
     }
 }
 
 pub mod utf16 {
-    use crate::error::{Encoding, Error};
+    use std::cmp::Ordering;
 
-    /// Converts UTF16 indexes to UTF8 indexes but also allows code point + 1 to be used in range operations.
-    pub(super) fn to(s: &str, nth: usize) -> Result<usize, Error> {
-        let mut total_code_points = 0;
-        if nth == 0 {
-            return Ok(0);
+    /// The number of bytes counted at once when searching for a position.
+    const CHUNK_LEN: usize = 32;
+
+    /// Counts the UTF-16 code units of the chars that start in the provided bytes.
+    ///
+    /// Every byte that is not a continuation byte starts a char, and only the chars encoded with
+    /// four bytes need a surrogate pair. This avoids decoding the chars, and is simple enough to
+    /// be vectorized.
+    #[inline]
+    pub(super) fn code_units(bytes: &[u8]) -> usize {
+        bytes
+            .iter()
+            .map(|&b| (b as i8 >= -0x40) as usize + (b >= 0xF0) as usize)
+            .sum()
+    }
+
+    /// Converts UTF16 indexes to UTF8 indexes. The line's UTF16 length maps to `s.len()`, and any
+    /// column past it is clamped to the same exclusive end.
+    pub(super) fn to(s: &str, nth: usize) -> Option<usize> {
+        let bytes = s.as_bytes();
+        // every ASCII byte is a single code unit
+        let prefix = &bytes[..nth.min(bytes.len())];
+        if prefix.is_ascii() {
+            return Some(prefix.len());
         }
-        for (utf8_index, utf8_len, utf16_len) in s
-            .char_indices()
-            .map(|(i, c)| (i, c.len_utf8(), c.len_utf16()))
-        {
-            if total_code_points > nth {
-                return Err(Error::InBetweenCharBoundries {
-                    encoding: Encoding::UTF16,
-                });
-            }
-            total_code_points += utf16_len;
-            if total_code_points == nth {
-                return Ok(utf8_index + utf8_len);
+
+        // skip the chunks that end before the position
+        let mut total_code_points = 0;
+        let mut start = 0;
+        for chunk in bytes.chunks(CHUNK_LEN) {
+            let units = code_units(chunk);
+            if total_code_points + units >= nth {
+                break;
             }
+            total_code_points += units;
+            start += chunk.len();
         }
 
-        Ok(nth.min(s.len()))
-    }
+        // the last skipped chunk can end in the middle of a char that is already counted
+        while !s.is_char_boundary(start) {
+            start += 1;
+        }
 
-    pub(super) fn from(s: &str, col: usize) -> Result<usize, Error> {
-        let mut utf8_len = 0;
-        let mut utf16_len = 0;
-        for c in s.chars() {
-            if utf8_len == col {
-                break;
+        for (utf8_index, c) in s[start..].char_indices() {
+            total_code_points += c.len_utf16();
+            match total_code_points.cmp(&nth) {
+                Ordering::Equal => return Some(start + utf8_index + c.len_utf8()),
+                // the position points in between a surrogate pair
+                Ordering::Greater => return None,
+                Ordering::Less => {}
             }
-            utf8_len += c.len_utf8();
-            utf16_len += c.len_utf16();
         }
 
-        Ok(utf16_len)
+        Some(s.len())
+    }
+
+    pub(super) fn from(s: &str, col: usize) -> Option<usize> {
+        // a column that is not on a char boundary counts every char
+        let end = if s.is_char_boundary(col) {
+            col
+        } else {
+            s.len()
+        };
+        Some(code_units(&s.as_bytes()[..end]))
     }
 }
 
 mod utf32 {
-    use crate::error::Error;
-
     #[inline]
-    pub(super) fn to(s: &str, nth: usize) -> Result<usize, Error> {
-        Ok(s.char_indices().map(|(i, _)| i).nth(nth).unwrap_or(s.len()))
+    pub(super) fn to(s: &str, nth: usize) -> Option<usize> {
+        Some(s.char_indices().map(|(i, _)| i).nth(nth).unwrap_or(s.len()))
     }
 
-    pub(super) fn from(s: &str, nth: usize) -> Result<usize, Error> {
+    pub(super) fn from(s: &str, nth: usize) -> Option<usize> {
         let mut len_utf8 = 0;
         let mut i = 0;
         for c in s.chars() {
@@ -92,6 +129,121 @@ mod utf32 {
             len_utf8 += c.len_utf8();
         }
 
-        Ok(i)
+        Some(i)
+    }
+}
+
+#[cfg(feature = "unicode-segmentation")]
+mod graphemes {
+    use std::cmp::Ordering;
+
+    use unicode_segmentation::UnicodeSegmentation;
+
+    #[inline]
+    pub(super) fn to(s: &str, nth: usize) -> Option<usize> {
+        Some(
+            s.grapheme_indices(true)
+                .map(|(i, _)| i)
+                .nth(nth)
+                .unwrap_or(s.len()),
+        )
+    }
+
+    pub(super) fn from(s: &str, nth: usize) -> Option<usize> {
+        let mut count = 0;
+        for (i, _) in s.grapheme_indices(true) {
+            match i.cmp(&nth) {
+                Ordering::Less => count += 1,
+                Ordering::Equal => return Some(count),
+                Ordering::Greater => break,
+            }
+        }
+
+        if nth < s.len() {
+            return None;
+        }
+
+        Some(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    mod utf16 {
+        use std::cmp::Ordering;
+
+        use crate::core::encodings::{utf16, UTF16};
+
+        /// The char by char implementation the optimized conversions are checked against.
+        fn scalar_to(s: &str, nth: usize) -> Option<usize> {
+            let mut total_code_points = 0;
+            if nth == 0 {
+                return Some(0);
+            }
+            for (utf8_index, c) in s.char_indices() {
+                total_code_points += c.len_utf16();
+                match total_code_points.cmp(&nth) {
+                    Ordering::Equal => return Some(utf8_index + c.len_utf8()),
+                    Ordering::Greater => return None,
+                    Ordering::Less => {}
+                }
+            }
+
+            Some(s.len())
+        }
+
+        fn scalar_from(s: &str, col: usize) -> Option<usize> {
+            let mut utf8_len = 0;
+            let mut utf16_len = 0;
+            for c in s.chars() {
+                if utf8_len == col {
+                    break;
+                }
+                utf8_len += c.len_utf8();
+                utf16_len += c.len_utf16();
+            }
+
+            Some(utf16_len)
+        }
+
+        #[test]
+        fn code_units() {
+            for s in ["", "abc", "ゲート", "😀a😀", "aé€😀"] {
+                assert_eq!(utf16::code_units(s.as_bytes()), s.encode_utf16().count());
+            }
+        }
+
+        #[test]
+        fn matches_scalar() {
+            let lines = [
+                String::new(),
+                "Hello, World!".repeat(8),
+                "aゲ😀bート𐍈c".repeat(12),
+                format!("{}😀{}", "a".repeat(31), "ゲ".repeat(40)),
+                "😀".repeat(40),
+            ];
+            let [to, from] = UTF16;
+            for s in &lines {
+                let len = s.encode_utf16().count();
+                for nth in 0..=len + 2 {
+                    assert_eq!(to(s, nth), scalar_to(s, nth), "to({s:?}, {nth})");
+                }
+                for col in 0..=s.len() + 2 {
+                    assert_eq!(from(s, col), scalar_from(s, col), "from({s:?}, {col})");
+                }
+            }
+        }
+
+        #[test]
+        fn end_of_line_astral() {
+            let [to, from] = UTF16;
+            for s in ["😀", "ab😀", "ゲ😀", &format!("{}😀", "a".repeat(31))] {
+                let len = s.encode_utf16().count();
+                assert_eq!(to(s, len), Some(s.len()), "to({s:?}, {len})");
+                assert_eq!(from(s, s.len()), Some(len), "from({s:?}, {})", s.len());
+                // the column before the end points in between the surrogate pair
+                assert_eq!(to(s, len - 1), None, "to({s:?}, {})", len - 1);
+            }
+        }
     }
 }
@@ -1,6 +1,6 @@
 use std::{iter::FusedIterator, num::NonZeroUsize};
 
-use super::lines::FastEOL;
+use super::lines::{EolMode, LineBreaks};
 
 #[derive(Debug, PartialEq, Eq)]
 pub struct EolIndexes(pub Vec<usize>);
@@ -33,12 +33,33 @@ impl<S: AsRef<[usize]>> PartialEq<S> for EolIndexes {
 impl EolIndexes {
     #[inline]
     pub fn new(s: &str) -> Self {
-        let iter = FastEOL::new(s);
+        #[cfg(feature = "rayon")]
+        if s.len() >= PAR_MIN_LEN {
+            let mut byte_indexes = vec![0];
+            byte_indexes.extend(par_line_breaks(s, PAR_CHUNK_LEN));
+            return Self(byte_indexes);
+        }
+
+        let iter = LineBreaks::new(s);
         let mut byte_indexes = vec![0];
         byte_indexes.extend(iter);
         Self(byte_indexes)
     }
 
+    /// Computes the [`EolIndexes`] of the provided string, with the line breaks of the provided
+    /// [`EolMode`].
+    #[inline]
+    pub fn with_eol_mode(s: &str, mode: EolMode) -> Self {
+        match mode {
+            EolMode::CrLf => Self::new(s),
+            mode => {
+                let mut byte_indexes = vec![0];
+                byte_indexes.extend(mode.line_breaks(s));
+                Self(byte_indexes)
+            }
+        }
+    }
+
     /// The index to the first byte in the row.
     ///
     /// Returns None if the nth row does not exist.
@@ -149,7 +170,7 @@ impl EolIndexes {
             self.0[rotate_start..].rotate_right(insert_count);
         }
 
-        start + 1..start + 1 + insert_count
+        start + 1..start + 1 + i + insert_count
     }
 
     /// Add an offset to all rows after the provided row number excluding itself.
@@ -218,6 +239,52 @@ fn no_row() -> ! {
     panic!("the row count should never be less than one")
 }
 
+/// The minimum length of a string for its line breaks to be searched in parallel.
+///
+/// Below this, the cost of spawning the work outweighs the time saved.
+#[cfg(feature = "rayon")]
+const PAR_MIN_LEN: usize = 1 << 20;
+
+/// The length of the chunks that are searched in parallel.
+#[cfg(feature = "rayon")]
+const PAR_CHUNK_LEN: usize = 1 << 18;
+
+/// Searches for the line breaks of the string in parallel, by splitting it in to chunks of
+/// roughly `chunk_len` bytes.
+///
+/// The chunks are never split in between a "\r\n", so each chunk can be searched on its own and
+/// the results concatenated.
+#[cfg(feature = "rayon")]
+fn par_line_breaks(s: &str, chunk_len: usize) -> Vec<usize> {
+    use rayon::prelude::*;
+
+    let bytes = s.as_bytes();
+    let mut bounds = vec![0];
+    let mut end = chunk_len;
+    while end < s.len() {
+        while end < s.len()
+            && (!s.is_char_boundary(end) || (bytes[end - 1] == b'\r' && bytes[end] == b'\n'))
+        {
+            end += 1;
+        }
+        bounds.push(end);
+        end += chunk_len;
+    }
+    if *bounds.last().unwrap() < s.len() {
+        bounds.push(s.len());
+    }
+
+    bounds
+        .par_windows(2)
+        .map(|w| {
+            LineBreaks::new(&s[w[0]..w[1]])
+                .map(|i| i + w[0])
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>()
+        .concat()
+}
+
 #[cfg(test)]
 mod tests {
     use crate::core::eol_indexes::EolIndexes;
@@ -230,6 +297,30 @@ mod tests {
         assert_eq!(br.0, [0, 3, 9, 10, 11, 17, 18, 25, 29, 31]);
     }
 
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn par_line_breaks() {
+        use super::{par_line_breaks, LineBreaks};
+
+        let s = "ゲート\r\n\r\r\n\nab\r\rc\r\n".repeat(16);
+        let serial: Vec<usize> = LineBreaks::new(&s).collect();
+        // every chunk length places the split at a different position of "\r\n" and multibyte
+        // characters
+        for chunk_len in 1..32 {
+            assert_eq!(
+                par_line_breaks(&s, chunk_len),
+                serial,
+                "chunk_len = {chunk_len}"
+            );
+        }
+        assert!(par_line_breaks("", 4).is_empty());
+
+        // large enough to be searched in parallel
+        let s = "Hello, World!\r\n".repeat(1 << 17);
+        let br = EolIndexes::new(&s);
+        assert_eq!(br.0[1..], LineBreaks::new(&s).collect::<Vec<_>>());
+    }
+
     #[test]
     fn row_start() {
         let br = EolIndexes::new(S);
@@ -294,6 +385,24 @@ mod tests {
         assert_eq!(br, [0]);
     }
 
+    #[test]
+    fn replace_indexes_returns_inserted() {
+        let mut br = EolIndexes::new(S);
+        let r = br.replace_indexes(1, 3, [5, 6, 7].into_iter());
+        assert_eq!(br, [0, 3, 5, 6, 7, 11, 17, 18, 25, 29, 31]);
+        assert_eq!(br.0[r], [5, 6, 7]);
+
+        let mut br = EolIndexes::new(S);
+        let r = br.replace_indexes(1, 3, [5].into_iter());
+        assert_eq!(br, [0, 3, 5, 11, 17, 18, 25, 29, 31]);
+        assert_eq!(br.0[r], [5]);
+
+        let mut br = EolIndexes::new(S);
+        let r = br.replace_indexes(1, 1, [5].into_iter());
+        assert_eq!(br, [0, 3, 5, 9, 10, 11, 17, 18, 25, 29, 31]);
+        assert_eq!(br.0[r], [5]);
+    }
+
     #[test]
     fn add_offsets() {
         let mut br = EolIndexes::new(S);
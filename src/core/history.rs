@@ -0,0 +1,693 @@
+//! An undo/redo history for a [`Text`].
+use std::{
+    borrow::Cow,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    change::{Change, GridIndex},
+    core::text::{Text, Unvetoed},
+    error::Result,
+    updateables::{ChangeContext, UpdateContext, Updateable},
+};
+
+/// A single edit, and the edit that reverts it.
+#[derive(Clone, Debug)]
+struct Step {
+    redo: Change<'static>,
+    undo: Change<'static>,
+}
+
+/// The information captured from an [`UpdateContext`] to build the inverse of a change.
+///
+/// All positions are UTF-8 positions.
+enum Inverse {
+    Span {
+        start: GridIndex,
+        /// The end of the inserted text in the new string.
+        end: GridIndex,
+        removed: String,
+    },
+    Full(String),
+}
+
+impl Inverse {
+    fn new(ctx: &UpdateContext) -> Inverse {
+        if let ChangeContext::ReplaceFull { .. } = ctx.change {
+            return Inverse::Full(ctx.old_str.to_string());
+        }
+
+        let (edited, text) = ctx.edited_bytes();
+        let old = ctx.old_str.as_bytes();
+        // the byte at the provided index of the new string
+        let new_byte = |i: usize| {
+            if i < edited.start {
+                old[i]
+            } else if i < edited.start + text.len() {
+                text.as_bytes()[i - edited.start]
+            } else {
+                old[i - text.len() + edited.len()]
+            }
+        };
+        let new_len = old.len() - edited.len() + text.len();
+        let splits_crlf =
+            |i: usize| i > 0 && i < new_len && new_byte(i - 1) == b'\r' && new_byte(i) == b'\n';
+
+        // a "\r" and a "\n" joined at the edges of the edit form a single line break, and a
+        // position in between them can not be represented, so the span is extended to include
+        // the whole line break
+        let (mut start, mut end) = (edited.start, edited.end);
+        let mut new_end = edited.start + text.len();
+        if splits_crlf(edited.start) {
+            start -= 1;
+        }
+        if splits_crlf(new_end) {
+            new_end += 1;
+            end += 1;
+        }
+
+        let new_br = ctx.breaklines;
+        let position = |byte: usize| {
+            let row = new_br.0[1..].partition_point(|&eol| eol < byte);
+            GridIndex {
+                row,
+                col: byte - new_br.row_start(row).expect("row should exist"),
+            }
+        };
+
+        Inverse::Span {
+            start: position(start),
+            end: position(new_end),
+            removed: ctx.old_str[start..end].to_string(),
+        }
+    }
+
+    /// Build the [`Change`] that reverts the recorded change.
+    ///
+    /// The provided [`Text`] should be the text after the change was applied.
+    fn into_change(self, text: &Text) -> Result<Change<'static>> {
+        let (mut start, mut end, removed) = match self {
+            Inverse::Span {
+                start,
+                end,
+                removed,
+            } => (start, end, removed),
+            Inverse::Full(s) => return Ok(Change::ReplaceFull(s.into())),
+        };
+        start.denormalize(text)?;
+        end.denormalize(text)?;
+
+        let change = if removed.is_empty() {
+            Change::Delete { start, end }
+        } else if start == end {
+            Change::Insert {
+                at: start,
+                text: removed.into(),
+            }
+        } else {
+            Change::Replace {
+                start,
+                end,
+                text: removed.into(),
+            }
+        };
+
+        Ok(change)
+    }
+}
+
+/// Returns the position of an insert if it only inserts a single character that is not an EOL.
+fn single_char_insert(change: &Change) -> Option<GridIndex> {
+    let Change::Insert { at, text } = change else {
+        return None;
+    };
+    let mut chars = text.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if c != '\n' && c != '\r' => Some(*at),
+        _ => None,
+    }
+}
+
+fn borrowed<'a>(change: &'a Change) -> Change<'a> {
+    match change {
+        Change::Delete { start, end } => Change::Delete {
+            start: *start,
+            end: *end,
+        },
+        Change::Insert { at, text } => Change::Insert {
+            at: *at,
+            text: Cow::Borrowed(text),
+        },
+        Change::Replace { start, end, text } => Change::Replace {
+            start: *start,
+            end: *end,
+            text: Cow::Borrowed(text),
+        },
+        Change::ReplaceFull(text) => Change::ReplaceFull(Cow::Borrowed(text)),
+    }
+}
+
+/// Captures the [`Inverse`] of a change while forwarding it to the wrapped [`Updateable`].
+struct Recording<'u, U: ?Sized> {
+    inner: &'u mut U,
+    inverse: Option<Inverse>,
+}
+
+impl<U: Updateable + ?Sized> Updateable for Recording<'_, U> {
+    fn should_apply(&mut self, ctx: &UpdateContext) -> bool {
+        self.inner.should_apply(ctx)
+    }
+
+    fn update(&mut self, ctx: UpdateContext) -> Result<()> {
+        self.inverse = Some(Inverse::new(&ctx));
+        self.inner.update(ctx)
+    }
+}
+
+/// A [`Text`] that records the changes performed on it so that they can be undone and redone.
+///
+/// Every change is applied through [`Text::update`], which means an [`Updateable`] is kept in
+/// sync when undoing or redoing as well.
+///
+/// Consecutive single character inserts (such as typing) are coalesced in to a single undo unit.
+/// A new unit can be started by calling [`History::boundary`], or by configuring a timeout via
+/// [`History::set_coalesce_timeout`].
+///
+/// When an insertion creates a new row at the end of the [`Text`], the inserted line break is not
+/// recorded and as such is not removed when undoing the change.
+#[derive(Clone, Debug)]
+pub struct History {
+    text: Text,
+    undo: Vec<Vec<Step>>,
+    redo: Vec<Vec<Step>>,
+    coalesce_timeout: Option<Duration>,
+    last_edit: Option<Instant>,
+    boundary: bool,
+}
+
+impl History {
+    /// Creates a new [`History`] with no recorded changes.
+    pub fn new(text: Text) -> Self {
+        Self {
+            text,
+            undo: vec![],
+            redo: vec![],
+            coalesce_timeout: None,
+            last_edit: None,
+            boundary: false,
+        }
+    }
+
+    /// Returns a reference to the inner [`Text`].
+    pub fn text(&self) -> &Text {
+        &self.text
+    }
+
+    /// Consumes the [`History`] returning the inner [`Text`].
+    pub fn into_text(self) -> Text {
+        self.text
+    }
+
+    /// Set the maximum amount of time between two single character inserts for them to be
+    /// coalesced in to a single undo unit.
+    ///
+    /// If `None` is provided, which is the default, inserts are coalesced regardless of the time
+    /// between them.
+    pub fn set_coalesce_timeout(&mut self, timeout: Option<Duration>) {
+        self.coalesce_timeout = timeout;
+    }
+
+    /// Mark the end of an undo unit, so that the next change is never coalesced with the previous
+    /// ones.
+    pub fn boundary(&mut self) {
+        self.boundary = true;
+    }
+
+    /// Returns true if there is a change that can be undone.
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    /// Returns true if there is a change that can be redone.
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// Perform a change on the text, recording it so that it can be undone.
+    ///
+    /// Performing a change clears any change that could be redone.
+    pub fn update<'a, U: Updateable + ?Sized, C: Into<Change<'a>>>(
+        &mut self,
+        change: C,
+        updateable: &mut U,
+    ) -> Result<()> {
+        let change: Change = change.into();
+        let redo = change.clone().into_owned();
+
+        let mut recording = Recording {
+            inner: updateable,
+            inverse: None,
+        };
+        self.text.update(change, &mut recording)?;
+        let Some(inverse) = recording.inverse else {
+            return Ok(());
+        };
+        let step = Step {
+            undo: inverse.into_change(&self.text)?,
+            redo,
+        };
+
+        let now = Instant::now();
+        let coalesce = !self.boundary
+            && self
+                .coalesce_timeout
+                .zip(self.last_edit)
+                .is_none_or(|(timeout, last)| now.duration_since(last) <= timeout)
+            && single_char_insert(&step.redo).is_some_and(|at| {
+                self.undo
+                    .last()
+                    .and_then(|group| group.last())
+                    .is_some_and(|prev| {
+                        single_char_insert(&prev.redo).is_some()
+                            && matches!(prev.undo, Change::Delete { end, .. } if end == at)
+                    })
+            });
+
+        match self.undo.last_mut() {
+            Some(group) if coalesce => group.push(step),
+            _ => self.undo.push(vec![step]),
+        }
+        self.redo.clear();
+        self.last_edit = Some(now);
+        self.boundary = false;
+
+        Ok(())
+    }
+
+    /// Undo the last undo unit.
+    ///
+    /// Returns false if there was nothing to undo, or if the [`Updateable`] vetoed a part of the
+    /// unit. If undoing the unit fails or is vetoed, the parts of it that were already undone are
+    /// redone and the unit is kept so that it can be undone again.
+    pub fn undo<U: Updateable + ?Sized>(&mut self, updateable: &mut U) -> Result<bool> {
+        let Some(group) = self.undo.last() else {
+            return Ok(false);
+        };
+        let changes: Vec<_> = group.iter().rev().map(|s| (&s.undo, &s.redo)).collect();
+        if !apply_all(&mut self.text, &changes, updateable)? {
+            return Ok(false);
+        }
+        let group = self.undo.pop().expect("group should exist");
+        self.redo.push(group);
+        self.boundary = true;
+
+        Ok(true)
+    }
+
+    /// Redo the last undone unit.
+    ///
+    /// Returns false if there was nothing to redo, or if the [`Updateable`] vetoed a part of the
+    /// unit. If redoing the unit fails or is vetoed, the parts of it that were already redone are
+    /// undone and the unit is kept so that it can be redone again.
+    pub fn redo<U: Updateable + ?Sized>(&mut self, updateable: &mut U) -> Result<bool> {
+        let Some(group) = self.redo.last() else {
+            return Ok(false);
+        };
+        let changes: Vec<_> = group.iter().map(|s| (&s.redo, &s.undo)).collect();
+        if !apply_all(&mut self.text, &changes, updateable)? {
+            return Ok(false);
+        }
+        let group = self.redo.pop().expect("group should exist");
+        self.undo.push(group);
+        self.boundary = true;
+
+        Ok(true)
+    }
+}
+
+/// Applies each change in order, paired with the change that reverts it.
+///
+/// If applying a change fails or is vetoed, the changes that were already applied are reverted
+/// without allowing the [`Updateable`] to veto them. Returns `false` if a change was vetoed.
+fn apply_all<U: Updateable + ?Sized>(
+    text: &mut Text,
+    changes: &[(&Change<'static>, &Change<'static>)],
+    updateable: &mut U,
+) -> Result<bool> {
+    let mut guard = VetoGuard {
+        inner: updateable,
+        vetoed: false,
+    };
+    for (i, (change, _)) in changes.iter().enumerate() {
+        let res = text.update(borrowed(change), &mut guard);
+        if res.is_err() || guard.vetoed {
+            for (_, revert) in changes[..i].iter().rev() {
+                text.update(borrowed(revert), &mut Unvetoed(&mut *guard.inner))?;
+            }
+            return res.map(|_| false);
+        }
+    }
+
+    Ok(true)
+}
+
+/// Forwards the edits to the wrapped [`Updateable`], recording whether it vetoed any of them.
+struct VetoGuard<'u, U: ?Sized> {
+    inner: &'u mut U,
+    vetoed: bool,
+}
+
+impl<U: Updateable + ?Sized> Updateable for VetoGuard<'_, U> {
+    fn needs_old_state(&self) -> bool {
+        self.inner.needs_old_state()
+    }
+
+    fn should_apply(&mut self, ctx: &UpdateContext) -> bool {
+        let apply = self.inner.should_apply(ctx);
+        self.vetoed |= !apply;
+        apply
+    }
+
+    fn update(&mut self, ctx: UpdateContext) -> Result<()> {
+        self.inner.update(ctx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::History;
+    use crate::{
+        change::{Change, GridIndex},
+        core::text::Text,
+        updateables::{UpdateContext, Updateable},
+    };
+
+    fn edits() -> Vec<Change<'static>> {
+        vec![
+            Change::Insert {
+                at: GridIndex { row: 0, col: 5 },
+                text: "\nシュタインズ".into(),
+            },
+            Change::Delete {
+                start: GridIndex { row: 0, col: 2 },
+                end: GridIndex { row: 1, col: 2 },
+            },
+            Change::Replace {
+                start: GridIndex { row: 1, col: 1 },
+                end: GridIndex { row: 2, col: 3 },
+                text: "ゲート\nWorld".into(),
+            },
+            Change::ReplaceFull("Full\nReplace".into()),
+            Change::Insert {
+                at: GridIndex { row: 1, col: 7 },
+                text: "!".into(),
+            },
+        ]
+    }
+
+    #[test]
+    fn undo_redo_sequence() {
+        let original = Text::new_utf16("Hello, World!\nApples\n Oranges\nPears".into());
+        let mut h = History::new(original.clone());
+        let mut states = vec![original.clone()];
+        for change in edits() {
+            h.update(change, &mut ()).unwrap();
+            h.boundary();
+            states.push(h.text().clone());
+        }
+        assert_eq!(h.text().text, "Full\nReplace!");
+
+        for state in states.iter().rev().skip(1) {
+            assert!(h.undo(&mut ()).unwrap());
+            assert_eq!(h.text(), state);
+        }
+        assert!(!h.can_undo());
+        assert!(!h.undo(&mut ()).unwrap());
+
+        for state in states.iter().skip(1) {
+            assert!(h.redo(&mut ()).unwrap());
+            assert_eq!(h.text(), state);
+        }
+        assert!(!h.can_redo());
+        assert!(!h.redo(&mut ()).unwrap());
+    }
+
+    #[test]
+    fn coalesce_char_inserts() {
+        let mut h = History::new(Text::new("Hello".into()));
+        for (col, c) in " World".chars().enumerate() {
+            h.update(
+                Change::Insert {
+                    at: GridIndex {
+                        row: 0,
+                        col: 5 + col,
+                    },
+                    text: c.to_string().into(),
+                },
+                &mut (),
+            )
+            .unwrap();
+        }
+        h.boundary();
+        h.update(
+            Change::Insert {
+                at: GridIndex { row: 0, col: 11 },
+                text: "!".into(),
+            },
+            &mut (),
+        )
+        .unwrap();
+        assert_eq!(h.text().text, "Hello World!");
+
+        h.undo(&mut ()).unwrap();
+        assert_eq!(h.text().text, "Hello World");
+        h.undo(&mut ()).unwrap();
+        assert_eq!(h.text().text, "Hello");
+        assert!(!h.can_undo());
+    }
+
+    #[test]
+    fn coalesce_timeout() {
+        let mut h = History::new(Text::new("".into()));
+        h.set_coalesce_timeout(Some(Duration::ZERO));
+        h.update(
+            Change::Insert {
+                at: GridIndex { row: 0, col: 0 },
+                text: "a".into(),
+            },
+            &mut (),
+        )
+        .unwrap();
+        std::thread::sleep(Duration::from_millis(1));
+        h.update(
+            Change::Insert {
+                at: GridIndex { row: 0, col: 1 },
+                text: "b".into(),
+            },
+            &mut (),
+        )
+        .unwrap();
+
+        h.undo(&mut ()).unwrap();
+        assert_eq!(h.text().text, "a");
+    }
+
+    #[test]
+    fn new_change_clears_redo() {
+        let mut h = History::new(Text::new("Hello".into()));
+        h.update(
+            Change::Delete {
+                start: GridIndex { row: 0, col: 0 },
+                end: GridIndex { row: 0, col: 1 },
+            },
+            &mut (),
+        )
+        .unwrap();
+        h.undo(&mut ()).unwrap();
+        assert!(h.can_redo());
+        h.update(
+            Change::Insert {
+                at: GridIndex { row: 0, col: 0 },
+                text: "Why ".into(),
+            },
+            &mut (),
+        )
+        .unwrap();
+        assert!(!h.can_redo());
+        assert_eq!(h.text().text, "Why Hello");
+    }
+
+    #[test]
+    fn joined_crlf() {
+        let changes = [
+            Change::Replace {
+                start: GridIndex { row: 1, col: 0 },
+                end: GridIndex { row: 1, col: 1 },
+                text: "x\r".into(),
+            },
+            Change::Delete {
+                start: GridIndex { row: 1, col: 0 },
+                end: GridIndex { row: 1, col: 1 },
+            },
+            Change::Insert {
+                at: GridIndex { row: 1, col: 0 },
+                text: "\n".into(),
+            },
+        ];
+        for change in changes {
+            let original = Text::new("ab\rc\nd".into());
+            let mut h = History::new(original.clone());
+            h.update(change, &mut ()).unwrap();
+            assert!(h.text().text.contains("\r\n"));
+            h.undo(&mut ()).unwrap();
+            assert_eq!(h.text(), &original);
+        }
+    }
+
+    #[test]
+    fn failed_undo_keeps_unit() {
+        let mut h = History::new(Text::new("Hello".into()));
+        for (col, c) in "ab".chars().enumerate() {
+            h.update(
+                Change::Insert {
+                    at: GridIndex {
+                        row: 0,
+                        col: 5 + col,
+                    },
+                    text: c.to_string().into(),
+                },
+                &mut (),
+            )
+            .unwrap();
+        }
+
+        // fails on the second step of the unit
+        fn failing(
+            calls: &mut usize,
+        ) -> impl FnMut(UpdateContext) -> crate::error::Result<()> + '_ {
+            move |_| {
+                *calls += 1;
+                if *calls == 2 {
+                    return Err(crate::error::Error::OutOfBoundsRow { max: 0, current: 0 });
+                }
+                Ok(())
+            }
+        }
+        let mut calls = 0;
+        assert!(h.undo(&mut failing(&mut calls)).is_err());
+        assert_eq!(h.text().text, "Helloab");
+        assert!(h.can_undo());
+        assert!(!h.can_redo());
+
+        h.undo(&mut ()).unwrap();
+        assert_eq!(h.text().text, "Hello");
+
+        let mut calls = 0;
+        assert!(h.redo(&mut failing(&mut calls)).is_err());
+        assert_eq!(h.text().text, "Hello");
+        assert!(h.can_redo());
+        h.redo(&mut ()).unwrap();
+        assert_eq!(h.text().text, "Helloab");
+    }
+
+    /// Vetoes every change starting from the nth one.
+    struct VetoFrom {
+        nth: usize,
+        calls: usize,
+    }
+
+    impl VetoFrom {
+        fn new(nth: usize) -> Self {
+            Self { nth, calls: 0 }
+        }
+    }
+
+    impl Updateable for VetoFrom {
+        fn should_apply(&mut self, _: &UpdateContext) -> bool {
+            self.calls += 1;
+            self.calls < self.nth
+        }
+
+        fn update(&mut self, _: UpdateContext) -> crate::error::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn vetoed_change() {
+        let mut h = History::new(Text::new("locked".into()));
+        h.update(
+            Change::Insert {
+                at: GridIndex { row: 0, col: 0 },
+                text: "X".into(),
+            },
+            &mut VetoFrom::new(1),
+        )
+        .unwrap();
+        assert_eq!(h.text().text, "locked");
+        assert!(!h.can_undo());
+    }
+
+    #[test]
+    fn vetoed_undo_redo() {
+        let mut h = History::new(Text::new("ab".into()));
+        for (col, c) in ["c", "d"].into_iter().enumerate() {
+            h.update(
+                Change::Insert {
+                    at: GridIndex {
+                        row: 0,
+                        col: 2 + col,
+                    },
+                    text: c.into(),
+                },
+                &mut (),
+            )
+            .unwrap();
+        }
+        assert_eq!(h.text().text, "abcd");
+
+        // vetoing the first or the second step of the unit keeps it on the undo stack
+        for nth in [1, 2] {
+            assert!(!h.undo(&mut VetoFrom::new(nth)).unwrap());
+            assert_eq!(h.text().text, "abcd");
+            assert!(h.can_undo());
+            assert!(!h.can_redo());
+        }
+        assert!(h.undo(&mut ()).unwrap());
+        assert_eq!(h.text().text, "ab");
+
+        for nth in [1, 2] {
+            assert!(!h.redo(&mut VetoFrom::new(nth)).unwrap());
+            assert_eq!(h.text().text, "ab");
+            assert!(!h.can_undo());
+            assert!(h.can_redo());
+        }
+        assert!(h.redo(&mut ()).unwrap());
+        assert_eq!(h.text().text, "abcd");
+        assert!(h.can_undo());
+        assert!(!h.can_redo());
+    }
+
+    #[test]
+    fn updateable_in_sync() {
+        let mut h = History::new(Text::new("Hello".into()));
+        let mut count = 0;
+        let mut counter = |_: UpdateContext| {
+            count += 1;
+            Ok(())
+        };
+        h.update(
+            Change::Insert {
+                at: GridIndex { row: 0, col: 0 },
+                text: "Why ".into(),
+            },
+            &mut counter,
+        )
+        .unwrap();
+        h.undo(&mut counter).unwrap();
+        h.redo(&mut counter).unwrap();
+        assert_eq!(count, 3);
+    }
+}
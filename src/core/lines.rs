@@ -1,15 +1,40 @@
 use std::iter::FusedIterator;
 
-use memchr::{memchr2_iter, Memchr2};
+use memchr::{memchr2_iter, memchr_iter, Memchr, Memchr2};
 
-use crate::utils::trim_eol_from_end;
+use crate::utils::eol_start;
 
 /// A fast iterator that searchs for end of lines.
 ///
 /// The actual search operation relies on [`memchr::memchr2_iter`], but with a wrapper around it to
 /// account for the "\r\n" case.
+///
+/// The byte index of each line break is yielded. `\r`, `\n` and `\r\n` are all treated as a
+/// single line break, and for `\r\n` the index of the `\n` byte is yielded. This is the same
+/// logic used to compute the [`EolIndexes`] of a [`Text`], so it can be used to find the line
+/// breaks of a string without constructing a [`Text`].
+///
+/// ```
+/// use texter::core::lines::LineBreaks;
+///
+/// let breaks: Vec<usize> = LineBreaks::new("123\n45678\r910").collect();
+/// assert_eq!(breaks, [3, 9]);
+///
+/// let breaks: Vec<usize> = LineBreaks::new("123\r\n45678\r\n910").collect();
+/// assert_eq!(breaks, [4, 11]);
+///
+/// let breaks: Vec<usize> = LineBreaks::new("\r\r\r\n123\n\r").collect();
+/// assert_eq!(breaks, [0, 1, 3, 7, 8]);
+///
+/// // the line breaks can also be searched from the end
+/// let breaks: Vec<usize> = LineBreaks::new("\r\r\r\n123\n\r").rev().collect();
+/// assert_eq!(breaks, [8, 7, 3, 1, 0]);
+/// ```
+///
+/// [`EolIndexes`]: crate::core::eol_indexes::EolIndexes
+/// [`Text`]: crate::core::text::Text
 #[derive(Clone, Debug)]
-pub(crate) struct FastEOL<'a> {
+pub struct LineBreaks<'a> {
     haystack: &'a [u8],
     iter: Memchr2<'a>,
     /// The position of the last found b'\r'.
@@ -21,8 +46,9 @@ pub(crate) struct FastEOL<'a> {
 const RC: u8 = b'\r';
 const BR: u8 = b'\n';
 
-impl<'a> FastEOL<'a> {
-    pub(crate) fn new(haystack: &'a str) -> Self {
+impl<'a> LineBreaks<'a> {
+    /// Creates a new [`LineBreaks`] that searches the provided string.
+    pub fn new(haystack: &'a str) -> Self {
         let iter = memchr2_iter(RC, BR, haystack.as_bytes());
         Self {
             iter,
@@ -33,7 +59,7 @@ impl<'a> FastEOL<'a> {
     }
 }
 
-impl Iterator for FastEOL<'_> {
+impl Iterator for LineBreaks<'_> {
     type Item = usize;
     fn next(&mut self) -> Option<Self::Item> {
         let next = self.iter.next();
@@ -76,7 +102,178 @@ impl Iterator for FastEOL<'_> {
     }
 }
 
-impl FusedIterator for FastEOL<'_> {}
+impl DoubleEndedIterator for LineBreaks<'_> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let n = self.iter.next_back()?;
+        // "\r\n" is a single line break, so also consume the "\r" that is before the "\n"
+        if self.haystack[n] == BR && n > 0 && self.haystack[n - 1] == RC {
+            self.iter.next_back();
+        }
+
+        Some(n)
+    }
+}
+
+impl FusedIterator for LineBreaks<'_> {}
+
+/// The line breaks recognized when computing the rows of a [`Text`][`crate::core::text::Text`].
+///
+/// Most text only uses `\r` and `\n`, so the default mode only searches for those bytes as it
+/// is considerably faster.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum EolMode {
+    /// `\r`, `\n` and `\r\n` are treated as line breaks.
+    #[default]
+    CrLf,
+    /// Only `\n` is treated as a line break, which allows skipping the `\r` handling entirely.
+    ///
+    /// A `\r\n` line break is still found through its `\n`, but a lone `\r` is not treated as a
+    /// line break. Text that contains lone `\r` line breaks will have fewer rows than in the
+    /// other modes, so this should only be used when the content is known to use `\n` or `\r\n`.
+    LfOnly,
+    /// In addition to the [`EolMode::CrLf`] line breaks, NEL (U+0085), LINE SEPARATOR (U+2028)
+    /// and PARAGRAPH SEPARATOR (U+2029) are treated as line breaks.
+    Unicode,
+}
+
+impl EolMode {
+    /// Returns an iterator over the line breaks of the provided string for the mode.
+    #[inline]
+    pub(crate) fn line_breaks(self, haystack: &str) -> Breaks<'_> {
+        match self {
+            EolMode::CrLf => Breaks::CrLf(LineBreaks::new(haystack)),
+            EolMode::LfOnly => Breaks::LfOnly(memchr_iter(BR, haystack.as_bytes())),
+            EolMode::Unicode => Breaks::Unicode(UnicodeLineBreaks::new(haystack)),
+        }
+    }
+
+    /// Returns `true` if the provided [`char`] is a line break on its own for the mode.
+    #[inline]
+    pub(crate) fn is_eol(self, c: char) -> bool {
+        match self {
+            EolMode::CrLf => matches!(c, '\r' | '\n'),
+            EolMode::LfOnly => c == '\n',
+            EolMode::Unicode => matches!(c, '\r' | '\n' | '\u{85}' | '\u{2028}' | '\u{2029}'),
+        }
+    }
+}
+
+/// A line break sequence that can be written in to a string.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum EolKind {
+    /// `\n`
+    #[default]
+    Lf,
+    /// `\r\n`
+    CrLf,
+    /// `\r`
+    Cr,
+}
+
+impl EolKind {
+    /// Returns the line break sequence as a string slice.
+    #[inline]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            EolKind::Lf => "\n",
+            EolKind::CrLf => "\r\n",
+            EolKind::Cr => "\r",
+        }
+    }
+}
+
+/// An iterator that searches for line breaks, including the Unicode line breaks.
+///
+/// Same as [`LineBreaks`], the index of the last byte of each line break is yielded. In addition
+/// to `\r`, `\n` and `\r\n`, NEL (U+0085), LINE SEPARATOR (U+2028) and PARAGRAPH SEPARATOR
+/// (U+2029) are also treated as line breaks.
+///
+/// ```
+/// use texter::core::lines::UnicodeLineBreaks;
+///
+/// let breaks: Vec<usize> = UnicodeLineBreaks::new("12\u{2028}3\r\n4\u{85}").collect();
+/// assert_eq!(breaks, [4, 7, 10]);
+/// ```
+#[derive(Clone, Debug)]
+pub struct UnicodeLineBreaks<'a> {
+    haystack: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> UnicodeLineBreaks<'a> {
+    /// Creates a new [`UnicodeLineBreaks`] that searches the provided string.
+    pub fn new(haystack: &'a str) -> Self {
+        Self {
+            haystack: haystack.as_bytes(),
+            pos: 0,
+        }
+    }
+}
+
+impl Iterator for UnicodeLineBreaks<'_> {
+    type Item = usize;
+    fn next(&mut self) -> Option<Self::Item> {
+        let hs = self.haystack;
+        while let Some(&b) = hs.get(self.pos) {
+            let i = self.pos;
+            let len = match b {
+                RC if hs.get(i + 1) == Some(&BR) => 2,
+                RC | BR => 1,
+                // NEL
+                0xC2 if hs.get(i + 1) == Some(&0x85) => 2,
+                // LINE SEPARATOR and PARAGRAPH SEPARATOR
+                0xE2 if matches!(hs.get(i + 1..i + 3), Some([0x80, 0xA8 | 0xA9])) => 3,
+                _ => {
+                    self.pos += 1;
+                    continue;
+                }
+            };
+
+            self.pos += len;
+            return Some(i + len - 1);
+        }
+
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.haystack.len() - self.pos))
+    }
+}
+
+impl FusedIterator for UnicodeLineBreaks<'_> {}
+
+/// A line break iterator for an [`EolMode`].
+#[derive(Clone, Debug)]
+pub(crate) enum Breaks<'a> {
+    CrLf(LineBreaks<'a>),
+    LfOnly(Memchr<'a>),
+    Unicode(UnicodeLineBreaks<'a>),
+}
+
+impl Iterator for Breaks<'_> {
+    type Item = usize;
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            Breaks::CrLf(iter) => iter.next(),
+            Breaks::LfOnly(iter) => iter.next(),
+            Breaks::Unicode(iter) => iter.next(),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self {
+            Breaks::CrLf(iter) => iter.size_hint(),
+            Breaks::LfOnly(iter) => iter.size_hint(),
+            Breaks::Unicode(iter) => iter.size_hint(),
+        }
+    }
+}
+
+impl FusedIterator for Breaks<'_> {}
 
 /// An efficient iterator that provides each line found in a [`Text`][`crate::core::text::Text`].
 ///
@@ -118,17 +315,21 @@ impl<'a> Iterator for TextLines<'a> {
     }
 
     fn nth(&mut self, n: usize) -> Option<Self::Item> {
-        let mut start = *self.eol_indexes.get(self.cursor + n)?;
+        let Some(mut start) = self.eol_indexes.get(self.cursor + n).copied() else {
+            // skipping past the end consumes the iterator, same as calling next n times
+            self.cursor = self.eol_indexes.len();
+            return None;
+        };
 
         start += (self.cursor + n != 0) as usize;
         let end = self
             .eol_indexes
             .get(self.cursor + n + 1)
-            .copied()
+            .map(|&eol| eol_start(self.s, eol))
             .unwrap_or(self.s.len());
 
         self.cursor += n + 1;
-        Some(trim_eol_from_end(&self.s[start..end]))
+        Some(&self.s[start..end])
     }
 
     fn count(self) -> usize {
@@ -146,36 +347,91 @@ impl ExactSizeIterator for TextLines<'_> {}
 
 #[cfg(test)]
 mod tests {
-    use super::{FastEOL, TextLines};
+    use super::{EolMode, LineBreaks, TextLines, UnicodeLineBreaks};
 
     #[test]
     fn br() {
         let hs = "123\n45678\n910";
-        let lines: Vec<_> = FastEOL::new(hs).collect();
+        let lines: Vec<_> = LineBreaks::new(hs).collect();
         assert_eq!(lines, [3, 9]);
     }
 
     #[test]
     fn r() {
         let hs = "123\r45678\r910";
-        let lines: Vec<_> = FastEOL::new(hs).collect();
+        let lines: Vec<_> = LineBreaks::new(hs).collect();
         assert_eq!(lines, [3, 9]);
     }
 
     #[test]
     fn rbr() {
         let hs = "123\r\n45678\r\n910";
-        let lines: Vec<_> = FastEOL::new(hs).collect();
+        let lines: Vec<_> = LineBreaks::new(hs).collect();
         assert_eq!(lines, [4, 11]);
     }
 
     #[test]
     fn rbr_mix() {
         let hs = "\r\r\r\n123\r45678\r\n910\n123\r123\n123123\n\r\r";
-        let lines: Vec<_> = FastEOL::new(hs).collect();
+        let lines: Vec<_> = LineBreaks::new(hs).collect();
         assert_eq!(lines, [0, 1, 3, 7, 14, 18, 22, 26, 33, 34, 35]);
     }
 
+    #[test]
+    fn rbr_mix_rev() {
+        let hs = "\r\r\r\n123\r45678\r\n910\n123\r123\n123123\n\r\r";
+        let mut lines: Vec<_> = LineBreaks::new(hs).rev().collect();
+        lines.reverse();
+        assert_eq!(lines, LineBreaks::new(hs).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn rbr_both_ends() {
+        let hs = "\r\n1\r\n\r\n2\r\n";
+        let mut iter = LineBreaks::new(hs);
+        assert_eq!(iter.next_back(), Some(9));
+        assert_eq!(iter.next(), Some(1));
+        assert_eq!(iter.next_back(), Some(6));
+        assert_eq!(iter.next(), Some(4));
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.next_back(), None);
+
+        let mut iter = LineBreaks::new("\r\n\r\n");
+        assert_eq!(iter.next(), Some(1));
+        assert_eq!(iter.next_back(), Some(3));
+        assert_eq!(iter.next_back(), None);
+    }
+
+    #[test]
+    fn unicode() {
+        let hs = "a\u{85}b\u{2028}c\u{2029}d\r\ne\rf\n";
+        let lines: Vec<_> = UnicodeLineBreaks::new(hs).collect();
+        assert_eq!(lines, [2, 6, 10, 13, 15, 17]);
+    }
+
+    #[test]
+    fn unicode_matches_crlf() {
+        let hs = "\r\r\r\n123\r45678\r\n910\n123\r123\n123123\n\r\r";
+        let lines: Vec<_> = UnicodeLineBreaks::new(hs).collect();
+        assert_eq!(lines, LineBreaks::new(hs).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn unicode_lookalikes() {
+        // chars that share the leading bytes of the unicode line breaks
+        let hs = "\u{80}\u{86}\u{2027}\u{202A}\u{2018}";
+        assert_eq!(UnicodeLineBreaks::new(hs).count(), 0);
+        assert_eq!(EolMode::CrLf.line_breaks("a\u{2028}b").count(), 0);
+        assert_eq!(EolMode::Unicode.line_breaks("a\u{2028}b").count(), 1);
+    }
+
+    #[test]
+    fn lf_only() {
+        let hs = "\r\r\r\n123\r45678\r\n910\n123";
+        let lines: Vec<_> = EolMode::LfOnly.line_breaks(hs).collect();
+        assert_eq!(lines, [3, 14, 18]);
+    }
+
     #[test]
     fn text_lines() {
         let s = "abc\n\r123\n\nbasdasd\n\n\n";
@@ -208,6 +464,27 @@ mod tests {
         assert_eq!(lines.nth(0), Some(""));
     }
 
+    #[test]
+    fn text_lines_nth_matches_next() {
+        let s = "abc\r\n\r123\n\nbasdasd\n\n\n";
+        let indexes = &[0, 4, 5, 9, 10, 18, 19, 20];
+        for k in 0..=indexes.len() {
+            for cursor in 0..3 {
+                let mut lines = TextLines::new(s, indexes);
+                let mut expected = lines.clone();
+                for _ in 0..cursor {
+                    lines.next();
+                    expected.next();
+                }
+                for _ in 0..k {
+                    expected.next();
+                }
+                assert_eq!(lines.nth(k), expected.next(), "k = {k}, cursor = {cursor}");
+                assert_eq!(lines.next(), expected.next());
+            }
+        }
+    }
+
     #[test]
     fn text_lines_skip() {
         let s = "abc\n\r123\n\nbasdasd\n\n\n";
@@ -1,5 +1,8 @@
 //! The core functionality of the crate.
+pub mod anchor;
+mod dirty_rows;
 pub(crate) mod encodings;
 pub mod eol_indexes;
+pub mod history;
 pub mod lines;
 pub mod text;
@@ -3,23 +3,33 @@ use std::{
     borrow::Cow,
     cmp::Ordering,
     fmt::{Debug, Display},
-    ops::Range,
+    hash::{Hash, Hasher},
+    io,
+    ops::{Deref, DerefMut, Range},
+    str::Utf8Error,
 };
 
+use memchr::memmem;
 use tracing::instrument;
 
 use super::{
-    encodings::{EncodingFns, UTF16, UTF32, UTF8},
+    dirty_rows::DirtyRows,
+    encodings::{encoding_fns, EncodingFns},
     eol_indexes::EolIndexes,
-    lines::{FastEOL, TextLines},
+    lines::{EolKind, EolMode, TextLines},
 };
 
 use crate::{
-    change::{correct_positions, Change, GridIndex},
-    error::{Error, Result},
+    actions::{ActionKind, Actionable},
+    change::{correct_positions, Change, Column, GridIndex},
+    error::{Encoding, Error, Result},
     updateables::{ChangeContext, UpdateContext, Updateable},
+    utils::eol_start,
 };
 
+/// The UTF-8 byte order mark.
+const BOM: char = '\u{FEFF}';
+
 /// An efficient way to store and process changes made to a text.
 ///
 /// Any method that performs a change on the text also accepts an [`Updateable`] which will be
@@ -43,7 +53,8 @@ pub struct Text {
     /// will very likely result in a panic.
     ///
     /// This is provided to the [`Updateable`] passed to [`Self::update`] to avoid recalculating
-    /// positions.
+    /// positions. If the [`Updateable`] does not need the old positions (see
+    /// [`Updateable::needs_old_state`]), the value is not updated.
     pub old_br_indexes: EolIndexes,
     /// The text that is stored.
     ///
@@ -58,11 +69,59 @@ pub struct Text {
     /// provided methods, and only reading from the value, this is not an issue as the implemented methods
     /// guarantee that all of the fields are in sync with each other. Before manually modifying the
     /// value, the current `br_indexes` field should be cloned to `old_br_indexes` and the changes
-    /// made on the text should also be reflected to `br_indexes`.
+    /// made on the text should also be reflected to `br_indexes`. If an [`Updateable`] does not
+    /// need to be updated, [`Text::rebuild_indexes`] can be called after the modification instead.
     ///
     /// This is required to correctly update an [`Updateable`] if one is provided.
     pub text: String,
     pub(crate) encoding: EncodingFns,
+    encoding_kind: Encoding,
+    eol_mode: EolMode,
+    bom: bool,
+    checked_columns: bool,
+    dirty_rows: DirtyRows,
+}
+
+/// A saved state of a [`Text`]'s content.
+///
+/// See [`Text::snapshot`] and [`Text::restore`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TextSnapshot {
+    text: String,
+    br_indexes: EolIndexes,
+}
+
+/// A guard that provides mutable access to the string of a [`Text`].
+///
+/// When the guard is dropped, the [`EolIndexes`] are computed again with
+/// [`Text::rebuild_indexes`]. This means the whole string is scanned on drop, so it should not
+/// be used in hot paths. No [`Updateable`] is updated, use [`Text::replace_full`] instead if one
+/// needs to be kept in sync.
+///
+/// See [`Text::edit_raw`].
+#[derive(Debug)]
+pub struct RawEdit<'a> {
+    text: &'a mut Text,
+}
+
+impl Deref for RawEdit<'_> {
+    type Target = String;
+
+    fn deref(&self) -> &Self::Target {
+        &self.text.text
+    }
+}
+
+impl DerefMut for RawEdit<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.text.text
+    }
+}
+
+impl Drop for RawEdit<'_> {
+    fn drop(&mut self) {
+        self.text.rebuild_indexes();
+    }
 }
 
 impl Display for Text {
@@ -71,48 +130,257 @@ impl Display for Text {
     }
 }
 
+impl AsRef<str> for Text {
+    fn as_ref(&self) -> &str {
+        &self.text
+    }
+}
+
 impl PartialEq for Text {
     fn eq(&self, other: &Self) -> bool {
-        self.encoding == other.encoding
+        self.encoding_kind == other.encoding_kind
+            && self.eol_mode == other.eol_mode
+            && self.bom == other.bom
             && self.br_indexes == other.br_indexes
             && self.text == other.text
     }
 }
 
+impl Eq for Text {}
+
+/// The expected encoding, the EOL positions, and the string are hashed. These are all compared by
+/// the [`PartialEq`] implementation, so equal [`Text`]s always have the same hash.
+impl Hash for Text {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(&self.encoding_kind).hash(state);
+        self.br_indexes.0.hash(state);
+        self.text.hash(state);
+    }
+}
+
+#[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+#[cfg(feature = "serde")]
+mod serde_impl {
+    use std::borrow::Cow;
+
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::Text;
+    use crate::{core::lines::EolMode, error::Encoding};
+
+    /// The serialized form of a [`Text`].
+    ///
+    /// The EOL indexes are not stored as they are recomputed when deserializing.
+    #[derive(Serialize, Deserialize)]
+    struct TextRepr<'a> {
+        text: Cow<'a, str>,
+        encoding: Encoding,
+        #[serde(default)]
+        eol_mode: EolMode,
+        #[serde(default)]
+        bom: bool,
+    }
+
+    impl Serialize for Text {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            TextRepr {
+                text: Cow::Borrowed(&self.text),
+                encoding: self.encoding_kind,
+                eol_mode: self.eol_mode,
+                bom: self.bom,
+            }
+            .serialize(serializer)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for Text {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let repr = TextRepr::deserialize(deserializer)?;
+            let mut t = Text::with_eol_mode(repr.text.into_owned(), repr.encoding, repr.eol_mode);
+            t.bom = repr.bom;
+            Ok(t)
+        }
+    }
+}
+
 impl Text {
     /// Creates a new [`Text`] that expects UTF-8 encoded positions.
     ///
     /// You should generally prefer this method instead of [`Text::new_utf16`] or [`Text::new_utf32`]
     /// and then transform the positions manually when using multiple encoding positions.
     pub fn new(text: String) -> Self {
-        let br_indexes = EolIndexes::new(&text);
-        Text {
-            text,
-            br_indexes,
-            old_br_indexes: EolIndexes(vec![]),
-            encoding: UTF8,
+        Self::with_encoding_kind(text, Encoding::UTF8)
+    }
+
+    /// Creates a new [`Text`] that expects UTF-8 encoded positions, by joining the provided
+    /// lines with `\n`.
+    ///
+    /// A line break is not added after the last line, so that the lines returned by
+    /// [`Text::lines`] are the same as the provided lines.
+    pub fn from_lines<I: IntoIterator<Item = S>, S: AsRef<str>>(lines: I) -> Self {
+        let mut text = String::new();
+        for (i, line) in lines.into_iter().enumerate() {
+            if i > 0 {
+                text.push('\n');
+            }
+            text.push_str(line.as_ref());
         }
+
+        Self::new(text)
     }
 
     /// Creates a new [`Text`] that expects UTF-16 encoded positions.
     pub fn new_utf16(text: String) -> Self {
-        let br_indexes = EolIndexes::new(&text);
-        Text {
-            text,
-            br_indexes,
-            old_br_indexes: EolIndexes(vec![]),
-            encoding: UTF16,
-        }
+        Self::with_encoding_kind(text, Encoding::UTF16)
     }
 
     /// Creates a new [`Text`] that expects UTF-32 encoded positions.
     pub fn new_utf32(text: String) -> Self {
-        let br_indexes = EolIndexes::new(&text);
+        Self::with_encoding_kind(text, Encoding::UTF32)
+    }
+
+    /// Creates a new [`Text`] that expects positions counted in extended grapheme clusters.
+    ///
+    /// This is useful for editors that move the cursor by what a user perceives as a single
+    /// character, such as a flag emoji or a letter followed by combining accents.
+    #[cfg_attr(docsrs, doc(cfg(feature = "unicode-segmentation")))]
+    #[cfg(feature = "unicode-segmentation")]
+    pub fn new_graphemes(text: String) -> Self {
+        Self::with_encoding_kind(text, Encoding::Graphemes)
+    }
+
+    /// Creates a new [`Text`] that expects UTF-8 encoded positions, and also treats NEL (U+0085),
+    /// LINE SEPARATOR (U+2028) and PARAGRAPH SEPARATOR (U+2029) as line breaks.
+    ///
+    /// Searching for these line breaks is slower than the default `\r` and `\n` search, so this
+    /// should only be used when the content is expected to contain them. See [`EolMode::Unicode`].
+    pub fn new_unicode_eol(text: String) -> Self {
+        Self::with_eol_mode(text, Encoding::UTF8, EolMode::Unicode)
+    }
+
+    /// Creates a new [`Text`] that expects UTF-8 encoded positions, and only treats `\n` as a line
+    /// break.
+    ///
+    /// Searching only for `\n` is faster than the default line break search, which is mostly
+    /// noticeable when loading or inserting large strings. `\r\n` line breaks are still handled,
+    /// but a lone `\r` is not treated as a line break, so providing text that contains one will
+    /// result in fewer rows than expected. See [`EolMode::LfOnly`].
+    pub fn new_lf_only(text: String) -> Self {
+        Self::with_eol_mode(text, Encoding::UTF8, EolMode::LfOnly)
+    }
+
+    /// Creates a new [`Text`] that expects UTF-8 encoded positions, removing the UTF-8 byte order
+    /// mark from the start of the string if present.
+    ///
+    /// The byte order mark is otherwise treated as a part of the first row, which shifts every
+    /// column of the first row by three bytes. Whether a byte order mark was removed is recorded
+    /// and can be checked with [`Text::has_bom`], and [`Text::write_to`] emits it again.
+    pub fn new_with_bom_handling(mut text: String) -> Self {
+        let bom = text.starts_with(BOM);
+        if bom {
+            text.drain(..BOM.len_utf8());
+        }
+
+        let mut t = Self::new(text);
+        t.bom = bom;
+        t
+    }
+
+    /// Selects the [`Text`] constructor for the position encodings supported by a client.
+    ///
+    /// UTF-8 is preferred, followed by UTF-32. If neither is supported, UTF-16 is used as it is
+    /// the encoding every client must support.
+    #[cfg_attr(docsrs, doc(cfg(feature = "lsp-types")))]
+    #[cfg(feature = "lsp-types")]
+    pub fn constructor_for(encodings: &[lsp_types::PositionEncodingKind]) -> fn(String) -> Text {
+        use lsp_types::PositionEncodingKind;
+
+        if encodings.contains(&PositionEncodingKind::UTF8) {
+            Text::new
+        } else if encodings.contains(&PositionEncodingKind::UTF32) {
+            Text::new_utf32
+        } else {
+            Text::new_utf16
+        }
+    }
+
+    /// Creates a new [`Text`] from UTF-8 bytes, replacing any invalid sequence with
+    /// [`char::REPLACEMENT_CHARACTER`].
+    ///
+    /// The positions are expected to be in the provided [`Encoding`].
+    pub fn from_utf8_lossy(bytes: &[u8], encoding: Encoding) -> Self {
+        Self::with_encoding_kind(String::from_utf8_lossy(bytes).into_owned(), encoding)
+    }
+
+    /// Creates a new [`Text`] from UTF-8 bytes, returning an error if the bytes are not valid
+    /// UTF-8.
+    ///
+    /// The positions are expected to be in the provided [`Encoding`].
+    pub fn from_utf8(bytes: &[u8], encoding: Encoding) -> std::result::Result<Self, Utf8Error> {
+        let s = str::from_utf8(bytes)?;
+        Ok(Self::with_encoding_kind(s.to_string(), encoding))
+    }
+
+    /// Returns the [`Encoding`] the [`Text`] expects positions to be in.
+    #[inline]
+    pub fn encoding(&self) -> Encoding {
+        self.encoding_kind
+    }
+
+    /// Changes the [`Encoding`] the [`Text`] expects positions to be in.
+    ///
+    /// The string is always stored as UTF-8, so the content is left as is and only the way
+    /// positions are interpreted changes. This can be used when the encoding negotiated with a
+    /// client changes after the [`Text`] was constructed.
+    pub fn with_encoding(mut self, encoding: Encoding) -> Text {
+        self.encoding = encoding_fns(encoding);
+        self.encoding_kind = encoding;
+        self
+    }
+
+    /// Returns `true` if a byte order mark was removed when constructing the [`Text`].
+    ///
+    /// See [`Text::new_with_bom_handling`].
+    #[inline]
+    pub fn has_bom(&self) -> bool {
+        self.bom
+    }
+
+    /// Writes the content of the [`Text`] to the provided writer.
+    ///
+    /// If the [`Text`] was constructed from a string that started with a byte order mark, the
+    /// byte order mark is written before the content.
+    pub fn write_to<W: io::Write>(&self, mut w: W) -> io::Result<()> {
+        if self.bom {
+            let mut buf = [0; 3];
+            w.write_all(BOM.encode_utf8(&mut buf).as_bytes())?;
+        }
+
+        w.write_all(self.text.as_bytes())
+    }
+
+    /// Returns the [`EolMode`] used to find the line breaks of the [`Text`].
+    #[inline]
+    pub fn eol_mode(&self) -> EolMode {
+        self.eol_mode
+    }
+
+    fn with_encoding_kind(text: String, encoding: Encoding) -> Self {
+        Self::with_eol_mode(text, encoding, EolMode::default())
+    }
+
+    fn with_eol_mode(text: String, encoding: Encoding, eol_mode: EolMode) -> Self {
+        let br_indexes = EolIndexes::with_eol_mode(&text, eol_mode);
         Text {
             text,
             br_indexes,
             old_br_indexes: EolIndexes(vec![]),
-            encoding: UTF32,
+            encoding: encoding_fns(encoding),
+            encoding_kind: encoding,
+            eol_mode,
+            bom: false,
+            checked_columns: false,
+            dirty_rows: DirtyRows::default(),
         }
     }
 
@@ -121,7 +389,7 @@ impl Text {
     /// The positions in the provided [`Change`] will be transformed to the expected encoding
     /// depending on how the [`Text`] was constructed.
     #[instrument(skip(change, updateable))]
-    pub fn update<'a, U: Updateable, C: Into<Change<'a>>>(
+    pub fn update<'a, U: Updateable + ?Sized, C: Into<Change<'a>>>(
         &mut self,
         change: C,
         updateable: &mut U,
@@ -130,33 +398,123 @@ impl Text {
         let change: Change = change.into();
 
         match change {
-            Change::Delete { start, end } => self.delete(start, end, updateable),
-            Change::Insert { text, at } => self.insert(&text, at, updateable),
-            Change::Replace { text, start, end } => self.replace(&text, start, end, updateable),
+            Change::Delete { start, end } => self.delete(start, end, updateable).map(drop),
+            Change::Insert { text, at } => self.insert(&text, at, updateable).map(drop),
+            Change::Replace { text, start, end } => {
+                self.replace(&text, start, end, updateable).map(drop)
+            }
             Change::ReplaceFull(s) => self.replace_full(s, updateable),
         }
     }
 
+    /// Perform the provided changes on the text in iteration order.
+    ///
+    /// The positions of each change are relative to the content after the previous changes are
+    /// applied. If a change fails to apply, the error is returned immediately and the changes
+    /// before it remain applied.
+    pub fn update_many<'a, I: IntoIterator<Item = Change<'a>>, U: Updateable + ?Sized>(
+        &mut self,
+        changes: I,
+        updateable: &mut U,
+    ) -> Result<()> {
+        for change in changes {
+            self.update(change, updateable)?;
+        }
+
+        Ok(())
+    }
+
+    /// Apply the content changes of a `textDocument/didChange` notification.
+    ///
+    /// The changes are applied in the order they are provided, as required by the LSP
+    /// specification. This means the positions of each change are relative to the content after
+    /// the previous changes are applied. If a change fails to apply, the changes before it remain
+    /// applied.
+    #[cfg_attr(docsrs, doc(cfg(feature = "lsp-types")))]
+    #[cfg(feature = "lsp-types")]
+    pub fn apply_lsp_changes<U: Updateable + ?Sized>(
+        &mut self,
+        changes: &[lsp_types::TextDocumentContentChangeEvent],
+        updateable: &mut U,
+    ) -> Result<()> {
+        for change in changes {
+            self.update(change, updateable)?;
+        }
+
+        Ok(())
+    }
+
+    /// Creates a [`lsp_types::TextDocumentContentChangeEvent`] that replaces the full content of
+    /// a document with the content of the [`Text`].
+    #[cfg_attr(docsrs, doc(cfg(feature = "lsp-types")))]
+    #[cfg(feature = "lsp-types")]
+    pub fn as_full_change(&self) -> lsp_types::TextDocumentContentChangeEvent {
+        lsp_types::TextDocumentContentChangeEvent {
+            range: None,
+            range_length: None,
+            text: self.text.clone(),
+        }
+    }
+
+    /// Perform the provided [`Actionable`] on the text.
+    ///
+    /// The changes computed by the action are applied in order with [`Text::update`]. If a change
+    /// fails to apply, the changes before it remain applied.
+    pub fn apply<A: Actionable + ?Sized, U: Updateable + ?Sized>(
+        &mut self,
+        action: &A,
+        updateable: &mut U,
+    ) -> Result<()> {
+        match action.action(self) {
+            ActionKind::Single(change) => self.update(change, updateable),
+            ActionKind::Multi(changes) => self.update_many(changes, updateable),
+        }
+    }
+
     /// Delete between the start and end [`GridIndex`] with the end being exclusive.
     ///
     /// Updates the current [`EolIndexes`] to align to the string.
     /// The [`GridIndex`] columns value is clamped to the end of the string excluding
-    /// the EOL bytes.
+    /// the EOL bytes, unless [`Text::set_checked_columns`] is enabled.
+    ///
+    /// Returns the position the deleted range collapsed to, in the [`Text`]'s expected encoding.
+    /// If an [`Updateable`] vetoes the edit, the provided start position is returned as is.
     ///
     /// # Panics
     ///
     /// If the [`EolIndexes`] of [`Text`] has a length of zero.
     #[inline]
-    pub fn delete<U: Updateable>(
+    pub fn delete<U: Updateable + ?Sized>(
         &mut self,
-        mut start: GridIndex,
-        mut end: GridIndex,
+        start: impl Into<GridIndex>,
+        end: impl Into<GridIndex>,
         updateable: &mut U,
-    ) -> Result<()> {
-        self.update_prep();
-        start.normalize(self)?;
-        end.normalize(self)?;
+    ) -> Result<GridIndex> {
+        let mut start = start.into();
+        let mut end = end.into();
+        let requested = start;
+        self.update_prep(updateable);
+        let text_len = self.text.len();
+        self.normalize_positions([&mut start, &mut end], updateable, text_len)?;
         correct_positions(&mut start, &mut end);
+        Ok(match self.delete_utf8(start, end, text_len, updateable)? {
+            Some(byte) => self.byte_to_grid_index(byte),
+            None => requested,
+        })
+    }
+
+    /// Deletes between the provided UTF-8 positions, and returns the byte index the deleted range
+    /// collapsed to.
+    ///
+    /// The positions must be normalized and ordered. `text_len` is the length of the string
+    /// before the positions were normalized. Returns [`None`] if an [`Updateable`] vetoed the edit.
+    fn delete_utf8<U: Updateable + ?Sized>(
+        &mut self,
+        start: GridIndex,
+        end: GridIndex,
+        text_len: usize,
+        updateable: &mut U,
+    ) -> Result<Option<usize>> {
         let max_row = self.br_indexes.row_count();
         let row_start_index = self
             .nth_row(start.row)
@@ -171,15 +529,60 @@ impl Text {
 
         self.br_indexes.remove_indexes(start.row, end.row);
         self.br_indexes.sub_offsets(start.row, br_offset);
-
-        updateable.update(UpdateContext {
-            change: ChangeContext::Delete { start, end },
-            breaklines: &self.br_indexes,
-            old_breaklines: &self.old_br_indexes,
-            old_str: self.text.as_str(),
-        })?;
+        let (merged, _) = self.merge_crlf(start_byte, end_byte, "");
+
+        let applied = run_updateable(
+            updateable,
+            UpdateContext {
+                change: ChangeContext::Delete { start, end },
+                breaklines: &self.br_indexes,
+                old_breaklines: &self.old_br_indexes,
+                old_str: self.text.as_str(),
+            },
+        );
+        if !matches!(applied, Ok(true)) {
+            self.abort_update(updateable, text_len);
+            return applied.map(|_| None);
+        }
 
         self.text.drain(byte_range);
+        let start_row = start.row - merged as usize;
+        self.dirty_rows.mark(start_row, end.row, start_row);
+
+        // the position was a part of the merged line break, so it moves to the end of the row
+        Ok(Some(start_byte - merged as usize))
+    }
+
+    /// Delete each of the provided ranges, with the end of each range being exclusive.
+    ///
+    /// All ranges are relative to the content before any deletion is made, as is the case with
+    /// multiple selections. The ranges are sorted and deleted from the last to the first so the
+    /// ranges before each deletion stay valid. If any two ranges overlap,
+    /// [`Error::OverlappingRanges`] is returned before anything is deleted. Ranges that only
+    /// touch each other do not overlap.
+    ///
+    /// If a deletion fails, the error is returned immediately and the deletions made after it in
+    /// the text remain applied.
+    pub fn delete_ranges<U: Updateable + ?Sized>(
+        &mut self,
+        ranges: &[(GridIndex, GridIndex)],
+        updateable: &mut U,
+    ) -> Result<()> {
+        let mut ranges: Vec<_> = ranges
+            .iter()
+            .map(|&(start, end)| (start.min(end), start.max(end)))
+            .collect();
+        ranges.sort_unstable();
+        if let Some(w) = ranges.windows(2).find(|w| w[1].0 < w[0].1) {
+            return Err(Error::OverlappingRanges {
+                first: w[0],
+                second: w[1],
+            });
+        }
+
+        for &(start, end) in ranges.iter().rev() {
+            self.delete(start, end, updateable)?;
+        }
 
         Ok(())
     }
@@ -188,53 +591,297 @@ impl Text {
     ///
     /// Updates the current [`EolIndexes`] to align to the string.
     /// The [`GridIndex`] columns value is clamped to the end of the string excluding
-    /// the EOL bytes.
+    /// the EOL bytes, unless [`Text::set_checked_columns`] is enabled.
+    ///
+    /// Returns the position after the inserted string, in the [`Text`]'s expected encoding. If an
+    /// [`Updateable`] vetoes the edit, the provided position is returned as is.
     ///
     /// # Panics
     ///
     /// If the [`EolIndexes`] of [`Text`] has a length of zero.
     #[inline]
-    pub fn insert<U: Updateable>(
+    pub fn insert<U: Updateable + ?Sized>(
+        &mut self,
+        s: &str,
+        at: impl Into<GridIndex>,
+        updateable: &mut U,
+    ) -> Result<GridIndex> {
+        let mut at = at.into();
+        let requested = at;
+        self.update_prep(updateable);
+        let text_len = self.text.len();
+        at.normalize(self)?;
+        Ok(match self.insert_utf8(s, at, text_len, updateable)? {
+            Some(byte) => self.byte_to_grid_index(byte),
+            None => requested,
+        })
+    }
+
+    /// Inserts the provided string at the provided UTF-8 position, and returns the byte index
+    /// after the inserted string.
+    ///
+    /// The position must be normalized. `text_len` is the length of the string before the
+    /// position was normalized. Returns [`None`] if an [`Updateable`] vetoed the edit.
+    fn insert_utf8<U: Updateable + ?Sized>(
+        &mut self,
+        s: &str,
+        at: GridIndex,
+        text_len: usize,
+        updateable: &mut U,
+    ) -> Result<Option<usize>> {
+        let row_count = self.br_indexes.row_count();
+        let row_end_index = self
+            .nth_row(at.row)
+            .ok_or(Error::oob_row(row_count, at.row))?;
+        let end_byte = row_end_index + at.col;
+        let br_indexes = self.eol_mode.line_breaks(s).map(|i| i + end_byte);
+        self.br_indexes.add_offsets(at.row, s.len());
+        let mut r = self.br_indexes.insert_indexes(at.row + 1, br_indexes);
+        let (left, right) = self.merge_crlf(end_byte, end_byte, s);
+        r.start -= left as usize;
+        r.end -= left as usize + right as usize;
+        let inserted_br_indexes = &self.br_indexes.0[r];
+        let inserted_br_count = inserted_br_indexes.len();
+
+        let applied = run_updateable(
+            updateable,
+            UpdateContext {
+                change: ChangeContext::Insert {
+                    inserted_br_indexes,
+                    position: at,
+                    text: s,
+                },
+                breaklines: &self.br_indexes,
+                old_breaklines: &self.old_br_indexes,
+                old_str: self.text.as_str(),
+            },
+        );
+        if !matches!(applied, Ok(true)) {
+            self.abort_update(updateable, text_len);
+            return applied.map(|_| None);
+        }
+
+        self.text.insert_str(end_byte, s);
+        let row = at.row - left as usize;
+        self.dirty_rows.mark(row, at.row, row + inserted_br_count);
+
+        // a "\r" merged at the end is a part of the line break after the inserted content
+        Ok(Some(end_byte + s.len() - right as usize))
+    }
+
+    /// Insert the provided string at the provided byte index of the string.
+    ///
+    /// This is the same as [`Text::insert`], but avoids converting the position from the
+    /// [`Text`]'s expected encoding. Returns [`Error::InvalidByteOffset`] if the index is past
+    /// the end of the string, is not on a char boundary, or is in between the bytes of a line
+    /// break.
+    pub fn insert_at_byte<U: Updateable + ?Sized>(
+        &mut self,
+        s: &str,
+        byte: usize,
+        updateable: &mut U,
+    ) -> Result<()> {
+        let at = self.utf8_grid_index(byte)?;
+        self.update_prep(updateable);
+        let text_len = self.text.len();
+        self.insert_utf8(s, at, text_len, updateable).map(drop)
+    }
+
+    /// Delete the provided byte range of the string.
+    ///
+    /// This is the same as [`Text::delete`], but avoids converting the positions from the
+    /// [`Text`]'s expected encoding. A reversed range is treated the same as its ordered
+    /// counterpart. Returns [`Error::InvalidByteOffset`] if either end of the range is past the
+    /// end of the string, is not on a char boundary, or is in between the bytes of a line break.
+    pub fn delete_byte_range<U: Updateable + ?Sized>(
+        &mut self,
+        range: Range<usize>,
+        updateable: &mut U,
+    ) -> Result<()> {
+        let start = self.utf8_grid_index(range.start.min(range.end))?;
+        let end = self.utf8_grid_index(range.start.max(range.end))?;
+        self.update_prep(updateable);
+        let text_len = self.text.len();
+        self.delete_utf8(start, end, text_len, updateable).map(drop)
+    }
+
+    /// Insert the provided string at each of the provided [`GridIndex`]s.
+    ///
+    /// All positions are relative to the content before any insertion is made, as is the case
+    /// with multiple cursors. The insertions are made from the last position to the first so the
+    /// positions before each insertion stay valid, and the [`Updateable`] receives a separate
+    /// context for each of them. If an insertion fails, the error is returned immediately and the
+    /// insertions made after it in the text remain applied.
+    pub fn insert_multi<U: Updateable + ?Sized>(
         &mut self,
         s: &str,
-        mut at: GridIndex,
+        positions: &[GridIndex],
+        updateable: &mut U,
+    ) -> Result<()> {
+        let mut positions = positions.to_vec();
+        positions.sort_unstable_by(|a, b| b.cmp(a));
+        for at in positions {
+            self.insert(s, at, updateable)?;
+        }
+
+        Ok(())
+    }
+
+    /// Insert the provided [`char`] at the provided [`GridIndex`].
+    ///
+    /// This is the same as calling [`Text::insert`] with a single character string, but avoids
+    /// scanning the inserted string for EOL bytes.
+    #[inline]
+    pub fn insert_char<U: Updateable + ?Sized>(
+        &mut self,
+        c: char,
+        at: impl Into<GridIndex>,
         updateable: &mut U,
     ) -> Result<()> {
-        self.update_prep();
+        let mut at = at.into();
+        let mut buf = [0; 4];
+        let s = &*c.encode_utf8(&mut buf);
+        self.update_prep(updateable);
+        let text_len = self.text.len();
         at.normalize(self)?;
         let row_count = self.br_indexes.row_count();
         let row_end_index = self
             .nth_row(at.row)
             .ok_or(Error::oob_row(row_count, at.row))?;
         let end_byte = row_end_index + at.col;
-        let br_indexes = FastEOL::new(s).map(|i| i + end_byte);
         self.br_indexes.add_offsets(at.row, s.len());
-        let inserted_br_indexes = {
-            let r = self.br_indexes.insert_indexes(at.row + 1, br_indexes);
-            &self.br_indexes.0[r]
+        let is_eol = self.eol_mode.is_eol(c);
+        if is_eol {
+            self.br_indexes
+                .insert_index(at.row + 1, end_byte + c.len_utf8() - 1);
+        }
+        let (left, right) = if is_eol {
+            self.merge_crlf(end_byte, end_byte, s)
+        } else {
+            (false, false)
         };
+        // a "\r" inserted before a "\n" becomes a part of the existing line break
+        let inserted_br_count = (is_eol && !right) as usize;
+        let inserted_start = at.row + 1 - left as usize;
+
+        let applied = run_updateable(
+            updateable,
+            UpdateContext {
+                change: ChangeContext::Insert {
+                    inserted_br_indexes: &self.br_indexes.0
+                        [inserted_start..inserted_start + inserted_br_count],
+                    position: at,
+                    text: s,
+                },
+                breaklines: &self.br_indexes,
+                old_breaklines: &self.old_br_indexes,
+                old_str: self.text.as_str(),
+            },
+        );
+        if !matches!(applied, Ok(true)) {
+            self.abort_update(updateable, text_len);
+            return applied.map(drop);
+        }
+
+        self.text.insert(end_byte, c);
+        let row = at.row - left as usize;
+        self.dirty_rows.mark(row, at.row, row + inserted_br_count);
+
+        Ok(())
+    }
+
+    /// Splits the [`Text`] at the provided position, returning the content after it as a new
+    /// [`Text`].
+    ///
+    /// The current [`Text`] is truncated to the content before the position, and the
+    /// [`Updateable`] is provided with the deletion. The returned [`Text`] expects the same
+    /// encoding as the current one.
+    pub fn split_off<U: Updateable + ?Sized>(
+        &mut self,
+        at: GridIndex,
+        updateable: &mut U,
+    ) -> Result<Text> {
+        let end = self.end_position();
+        let tail = self.sub_text(at, end)?;
+        self.delete(at, end, updateable)?;
+
+        Ok(tail)
+    }
 
-        updateable.update(UpdateContext {
-            change: ChangeContext::Insert {
-                inserted_br_indexes,
-                position: at,
-                text: s,
+    /// Appends the content of the provided [`Text`] to the end of the current one.
+    ///
+    /// The EOL positions of the provided [`Text`] are reused instead of being recomputed.
+    pub fn append_text<U: Updateable + ?Sized>(
+        &mut self,
+        other: &Text,
+        updateable: &mut U,
+    ) -> Result<()> {
+        self.update_prep(updateable);
+        let text_len = self.text.len();
+        let start_byte = self.text.len();
+        let position = GridIndex {
+            row: self.line_count() - 1,
+            col: start_byte - self.br_indexes.last_row_start(),
+        };
+        // the line breaks can only be reused if they were found with the same mode
+        let other_indexes = if other.eol_mode == self.eol_mode {
+            Cow::Borrowed(&other.br_indexes.0[1..])
+        } else {
+            Cow::Owned(self.eol_mode.line_breaks(&other.text).collect())
+        };
+        let mut r = self.br_indexes.insert_indexes(
+            position.row + 1,
+            other_indexes.iter().map(|i| i + start_byte),
+        );
+        let (left, _) = self.merge_crlf(start_byte, start_byte, &other.text);
+        r.start -= left as usize;
+        r.end -= left as usize;
+        let inserted_br_indexes = &self.br_indexes.0[r];
+        let inserted_br_count = inserted_br_indexes.len();
+
+        let applied = run_updateable(
+            updateable,
+            UpdateContext {
+                change: ChangeContext::Insert {
+                    inserted_br_indexes,
+                    position,
+                    text: &other.text,
+                },
+                breaklines: &self.br_indexes,
+                old_breaklines: &self.old_br_indexes,
+                old_str: self.text.as_str(),
             },
-            breaklines: &self.br_indexes,
-            old_breaklines: &self.old_br_indexes,
-            old_str: self.text.as_str(),
-        })?;
+        );
+        if !matches!(applied, Ok(true)) {
+            self.abort_update(updateable, text_len);
+            return applied.map(drop);
+        }
 
-        self.text.insert_str(end_byte, s);
+        self.text.push_str(&other.text);
+        let row = position.row - left as usize;
+        self.dirty_rows
+            .mark(row, position.row, row + inserted_br_count);
 
         Ok(())
     }
 
+    /// The position after the last character, in the [`Text`]'s expected encoding.
+    pub(crate) fn end_position(&self) -> GridIndex {
+        let row = self.line_count() - 1;
+        GridIndex {
+            row,
+            col: self.encoded_len(&self.text[self.br_indexes.last_row_start()..]),
+        }
+    }
+
     /// Replace start..end with the provided string.
     ///
     /// Updates the current [`EolIndexes`] to align to the string.
     /// The [`GridIndex`] columns value is clamped to the end of the string excluding
-    /// the EOL bytes.
+    /// the EOL bytes, unless [`Text::set_checked_columns`] is enabled.
+    ///
+    /// Returns the position after the replacement string, in the [`Text`]'s expected encoding. If
+    /// an [`Updateable`] vetoes the edit, the provided end position is returned as is.
     ///
     /// This is more optimized than calling [`String::replace_range`] and then updating the
     /// [`EolIndexes`] manually.
@@ -243,17 +890,41 @@ impl Text {
     ///
     /// If the [`EolIndexes`] of [`Text`] has a length of zero.
     #[inline]
-    pub fn replace<U: Updateable>(
+    pub fn replace<U: Updateable + ?Sized>(
         &mut self,
         s: &str,
-        mut start: GridIndex,
-        mut end: GridIndex,
+        start: impl Into<GridIndex>,
+        end: impl Into<GridIndex>,
         updateable: &mut U,
-    ) -> Result<()> {
-        self.update_prep();
-        start.normalize(self)?;
-        end.normalize(self)?;
+    ) -> Result<GridIndex> {
+        let mut start = start.into();
+        let mut end = end.into();
+        let requested = end;
+        self.update_prep(updateable);
+        let text_len = self.text.len();
+        self.normalize_positions([&mut start, &mut end], updateable, text_len)?;
         correct_positions(&mut start, &mut end);
+        Ok(
+            match self.replace_utf8(s, start, end, text_len, updateable)? {
+                Some(byte) => self.byte_to_grid_index(byte),
+                None => requested,
+            },
+        )
+    }
+
+    /// Replaces between the provided UTF-8 positions, and returns the byte index after the
+    /// replacement string.
+    ///
+    /// The positions must be normalized and ordered. `text_len` is the length of the string
+    /// before the positions were normalized. Returns [`None`] if an [`Updateable`] vetoed the edit.
+    fn replace_utf8<U: Updateable + ?Sized>(
+        &mut self,
+        s: &str,
+        start: GridIndex,
+        end: GridIndex,
+        text_len: usize,
+        updateable: &mut U,
+    ) -> Result<Option<usize>> {
         let row_count = self.br_indexes.row_count();
         let row_start_index = self
             .nth_row(start.row)
@@ -273,26 +944,35 @@ impl Text {
             Ordering::Equal => {}
         }
 
-        let inserted = {
-            let r = self.br_indexes.replace_indexes(
-                start.row,
-                end.row,
-                FastEOL::new(s).map(|bri| bri + start_byte),
-            );
-            &self.br_indexes.0[r]
-        };
-
-        updateable.update(UpdateContext {
-            change: ChangeContext::Replace {
-                start,
-                end,
-                text: s,
-                inserted_br_indexes: inserted,
+        let mut r = self.br_indexes.replace_indexes(
+            start.row,
+            end.row,
+            self.eol_mode.line_breaks(s).map(|bri| bri + start_byte),
+        );
+        let (left, right) = self.merge_crlf(start_byte, end_byte, s);
+        r.start -= left as usize;
+        r.end -= left as usize + right as usize;
+        let inserted = &self.br_indexes.0[r];
+        let inserted_br_count = inserted.len();
+
+        let applied = run_updateable(
+            updateable,
+            UpdateContext {
+                change: ChangeContext::Replace {
+                    start,
+                    end,
+                    text: s,
+                    inserted_br_indexes: inserted,
+                },
+                breaklines: &self.br_indexes,
+                old_breaklines: &self.old_br_indexes,
+                old_str: self.text.as_str(),
             },
-            breaklines: &self.br_indexes,
-            old_breaklines: &self.old_br_indexes,
-            old_str: self.text.as_str(),
-        })?;
+        );
+        if !matches!(applied, Ok(true)) {
+            self.abort_update(updateable, text_len);
+            return applied.map(|_| None);
+        }
 
         // String::replace_range contains quite a bit of checks that we do not need.
         // It also internally uses splicing, which (probably) causes the elements to be
@@ -364,49 +1044,530 @@ impl Text {
         }
 
         fast_replace_range(&mut self.text, byte_range, s);
+        self.dirty_rows.mark(
+            start.row - left as usize,
+            end.row,
+            start.row + inserted_br_count - left as usize,
+        );
+
+        // a "\r" merged at the end is a part of the line break after the replacement string
+        Ok(Some(start_byte + s.len() - right as usize))
+    }
 
-        Ok(())
+    /// Merges a lone `\r` and a lone `\n` that are placed next to each other by an edit in to a
+    /// single `\r\n` line break.
+    ///
+    /// `start_byte..end_byte` is the range of the old string that is replaced with `s`. This
+    /// should be called after the [`EolIndexes`] are updated for the edit, but before the string
+    /// is modified. Returns whether a line break was merged at the start and at the end of `s`.
+    fn merge_crlf(&mut self, start_byte: usize, end_byte: usize, s: &str) -> (bool, bool) {
+        // a lone "\r" is not a line break in this mode, so there is nothing to merge
+        if self.eol_mode == EolMode::LfOnly {
+            return (false, false);
+        }
+
+        let old = self.text.as_bytes();
+        let next = s.as_bytes().first().or(old.get(end_byte));
+        let left = start_byte > 0 && old[start_byte - 1] == b'\r' && next == Some(&b'\n');
+        let right = s.ends_with('\r') && old.get(end_byte) == Some(&b'\n');
+
+        // a "\r\n" line break points to the "\n", so the index of the "\r" is removed
+        let mut remove = |byte: usize| {
+            let i = self
+                .br_indexes
+                .0
+                .binary_search(&byte)
+                .expect("a lone \\r should be a line break");
+            self.br_indexes.0.remove(i);
+        };
+        if right {
+            remove(start_byte + s.len() - 1);
+        }
+        if left {
+            remove(start_byte - 1);
+        }
+
+        (left, right)
     }
 
     #[inline]
-    pub fn replace_full<U: Updateable>(
+    pub fn replace_full<U: Updateable + ?Sized>(
         &mut self,
         s: Cow<'_, str>,
         updateable: &mut U,
     ) -> Result<()> {
-        self.br_indexes = EolIndexes::new(&s);
-        updateable.update(UpdateContext {
-            change: ChangeContext::ReplaceFull { text: s.as_ref() },
-            breaklines: &self.br_indexes,
-            old_breaklines: &self.old_br_indexes,
-            old_str: self.text.as_str(),
-        })?;
-        match s {
-            Cow::Borrowed(s) => {
-                self.text.clear();
-                self.text.push_str(s);
-            }
-            Cow::Owned(s) => self.text = s,
-        };
-
-        Ok(())
+        let br_indexes = EolIndexes::with_eol_mode(&s, self.eol_mode);
+        self.replace_full_with(s, br_indexes, updateable)
     }
 
-    /// Returns the start of the nth row.
+    /// Removes every character for which `f` returns `false`.
     ///
-    /// If the nth row does not exist, None is returned.
+    /// Since this can modify the positions across the whole content, the [`Updateable`] is
+    /// provided with a [`ChangeContext::ReplaceFull`]. If no characters are removed, the
+    /// [`Updateable`] is not updated.
+    pub fn retain<U: Updateable + ?Sized, F: FnMut(char) -> bool>(
+        &mut self,
+        mut f: F,
+        updateable: &mut U,
+    ) -> Result<()> {
+        let retained: String = self.text.chars().filter(|&c| f(c)).collect();
+        if retained.len() == self.text.len() {
+            return Ok(());
+        }
+
+        self.replace_full(Cow::Owned(retained), updateable)
+    }
+
+    /// Replace the full contents of the text with precomputed [`EolIndexes`].
+    fn replace_full_with<U: Updateable + ?Sized>(
+        &mut self,
+        s: Cow<'_, str>,
+        br_indexes: EolIndexes,
+        updateable: &mut U,
+    ) -> Result<()> {
+        if !self.replace_full_indexes(&s, br_indexes, updateable)? {
+            return Ok(());
+        }
+
+        match s {
+            Cow::Borrowed(s) => {
+                self.text.clear();
+                self.text.push_str(s);
+            }
+            // keep the larger allocation to avoid reallocating on the next replacement
+            Cow::Owned(s) if s.capacity() < self.text.capacity() => {
+                self.text.clear();
+                self.text.push_str(&s);
+            }
+            Cow::Owned(s) => self.text = s,
+        };
+
+        Ok(())
+    }
+
+    /// Updates all of the state other than the string itself for a full replacement.
+    ///
+    /// The caller is expected to replace the string with `s` if this returns `true`. If the
+    /// [`Updateable`] vetoed the edit, `false` is returned and the state is left as is.
+    fn replace_full_indexes<U: Updateable + ?Sized>(
+        &mut self,
+        s: &str,
+        br_indexes: EolIndexes,
+        updateable: &mut U,
+    ) -> Result<bool> {
+        self.old_br_indexes = std::mem::replace(&mut self.br_indexes, br_indexes);
+        let applied = run_updateable(
+            updateable,
+            UpdateContext {
+                change: ChangeContext::ReplaceFull { text: s },
+                breaklines: &self.br_indexes,
+                old_breaklines: &self.old_br_indexes,
+                old_str: self.text.as_str(),
+            },
+        );
+        if !matches!(applied, Ok(true)) {
+            std::mem::swap(&mut self.br_indexes, &mut self.old_br_indexes);
+            return applied;
+        }
+        self.dirty_rows.clear();
+        self.dirty_rows
+            .mark(0, 0, self.br_indexes.row_count().get() - 1);
+
+        Ok(true)
+    }
+
+    /// Swap the stored string with the provided one, returning the old string.
+    ///
+    /// This is similar to [`Text::replace_full`], but allows reusing the old string's allocation
+    /// as it is returned by value instead of being dropped. If the [`Updateable`] vetoes the edit,
+    /// the provided string is returned instead.
+    pub fn swap_buffer<U: Updateable + ?Sized>(
+        &mut self,
+        new: String,
+        updateable: &mut U,
+    ) -> Result<String> {
+        let br_indexes = EolIndexes::with_eol_mode(&new, self.eol_mode);
+        if !self.replace_full_indexes(&new, br_indexes, updateable)? {
+            return Ok(new);
+        }
+
+        Ok(std::mem::replace(&mut self.text, new))
+    }
+
+    /// Creates a [`TextSnapshot`] of the current content.
+    ///
+    /// The snapshot can later be provided to [`Text::restore`] to roll back any change performed
+    /// after the snapshot was created.
+    pub fn snapshot(&self) -> TextSnapshot {
+        TextSnapshot {
+            text: self.text.clone(),
+            br_indexes: self.br_indexes.clone(),
+        }
+    }
+
+    /// Restore the content from a [`TextSnapshot`].
+    ///
+    /// The [`Updateable`] is provided with a [`ChangeContext::ReplaceFull`] so that any dependent
+    /// state can be synced with the restored content.
+    pub fn restore<U: Updateable + ?Sized>(
+        &mut self,
+        snapshot: TextSnapshot,
+        updateable: &mut U,
+    ) -> Result<()> {
+        self.replace_full_with(Cow::Owned(snapshot.text), snapshot.br_indexes, updateable)
+    }
+
+    /// Run the provided function as a single transaction.
+    ///
+    /// If `f` returns an error, the content is restored to what it was before `f` was called with
+    /// [`Text::restore`], and the error is returned. This means the [`Updateable`] is provided with
+    /// a [`ChangeContext::ReplaceFull`] to sync any state from the reverted edits. The restore
+    /// can not be vetoed with [`Updateable::should_apply`].
+    pub fn transaction<U, F>(&mut self, updateable: &mut U, f: F) -> Result<()>
+    where
+        U: Updateable + ?Sized,
+        F: FnOnce(&mut Text, &mut U) -> Result<()>,
+    {
+        let snapshot = self.snapshot();
+        if let Err(e) = f(self, updateable) {
+            self.restore(snapshot, &mut Unvetoed(updateable))?;
+            return Err(e);
+        }
+
+        Ok(())
+    }
+
+    /// Returns the start of the nth row.
+    ///
+    /// If the nth row does not exist, None is returned.
     #[inline]
     fn nth_row(&self, nth: usize) -> Option<usize> {
         self.br_indexes.row_start(nth)
     }
 
+    /// Computes the [`EolIndexes`] again from the current string.
+    ///
+    /// This should be called after manually modifying `Text.text` to bring the rest of the
+    /// [`Text`] back in sync with the string. No [`Updateable`] is updated, and every row is marked
+    /// as dirty since the modified rows are not known.
+    pub fn rebuild_indexes(&mut self) {
+        self.br_indexes = EolIndexes::with_eol_mode(&self.text, self.eol_mode);
+        self.dirty_rows.clear();
+        self.dirty_rows
+            .mark(0, 0, self.br_indexes.row_count().get() - 1);
+    }
+
+    /// Returns a [`RawEdit`] guard that provides mutable access to the string.
+    ///
+    /// The [`EolIndexes`] are computed again once the guard is dropped.
+    pub fn edit_raw(&mut self) -> RawEdit<'_> {
+        RawEdit { text: self }
+    }
+
+    /// Checks if the [`Text`]'s fields are in sync with its string.
+    ///
+    /// The [`EolIndexes`] are computed again from the string and compared with `br_indexes`, and
+    /// the length of every line in the expected encoding is checked to convert back to the same
+    /// UTF-8 length. This scans the whole string, so it is mostly useful in tests and fuzzers, or
+    /// after manually modifying the string.
+    pub fn verify_invariants(&self) -> bool {
+        if self.br_indexes != EolIndexes::with_eol_mode(&self.text, self.eol_mode) {
+            return false;
+        }
+
+        self.lines().enumerate().all(|(row, line)| {
+            self.col_to_utf8(row, line, self.encoded_len(line))
+                .is_ok_and(|col| col == line.len())
+        })
+    }
+
+    /// Returns the number of lines in the [`Text`].
+    ///
+    /// An empty [`Text`] still contains a single empty line, so the count is never zero.
+    #[inline]
+    pub fn line_count(&self) -> usize {
+        self.br_indexes.row_count().get()
+    }
+
+    /// Returns the content of the [`Text`] as a string slice.
+    #[inline]
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    /// Returns the capacity of the string in bytes.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.text.capacity()
+    }
+
+    /// Returns an estimate of the number of bytes the [`Text`] has allocated on the heap.
+    ///
+    /// This is the capacity of the string, the current and old EOL positions and the tracked
+    /// dirty rows. It is an approximation, as the size of the [`Text`] itself and any allocator
+    /// overhead are not included.
+    pub fn heap_size(&self) -> usize {
+        self.text.capacity()
+            + (self.br_indexes.0.capacity() + self.old_br_indexes.0.capacity()) * size_of::<usize>()
+            + self.dirty_rows.heap_size()
+    }
+
+    /// Reserves capacity for at least `additional` more bytes to be inserted.
+    ///
+    /// This is useful when the size of an incoming edit is known, such as a large paste, to avoid
+    /// reallocating while the edit is performed. Room for the EOL positions is reserved as well,
+    /// estimated from the number of line breaks per byte of the current content. The estimate
+    /// never exceeds one line break per 32 bytes, so that a small document does not reserve a
+    /// position for nearly every byte.
+    pub fn reserve(&mut self, additional: usize) {
+        self.text.reserve(additional);
+        let breaks = self.br_indexes.row_count().get() - 1;
+        let lines =
+            (additional * breaks / self.text.len().max(1)).min(additional / Self::RESERVE_LINE_LEN);
+        self.br_indexes.0.reserve(lines);
+    }
+
+    /// The minimum average line length assumed by [`Text::reserve`].
+    const RESERVE_LINE_LEN: usize = 32;
+
     /// Get the nth row.
     ///
     /// The returned slice is trimmed for any EOL bytes.
     /// Returns None if the nth row does not exist.
     #[inline]
     pub fn get_row(&self, nth: usize) -> Option<&str> {
-        self.lines().nth(nth)
+        self.row_byte_range(nth).map(|range| &self.text[range])
+    }
+
+    /// Returns `true` if the content of the provided row starts with `prefix`.
+    ///
+    /// The EOL bytes of the row are not considered, and the comparison is done byte by byte.
+    /// Returns `false` if the row does not exist.
+    #[inline]
+    pub fn row_starts_with(&self, row: usize, prefix: &str) -> bool {
+        self.get_row(row)
+            .is_some_and(|line| line.starts_with(prefix))
+    }
+
+    /// Returns `true` if the content of the provided row ends with `suffix`.
+    ///
+    /// The EOL bytes of the row are not considered, and the comparison is done byte by byte.
+    /// Returns `false` if the row does not exist.
+    #[inline]
+    pub fn row_ends_with(&self, row: usize, suffix: &str) -> bool {
+        self.get_row(row).is_some_and(|line| line.ends_with(suffix))
+    }
+
+    /// Returns the byte range of the provided row's content in the string.
+    ///
+    /// The range does not include the EOL bytes, see [`Text::row_byte_range_with_eol`] to include
+    /// them. Returns [`None`] if the row does not exist.
+    pub fn row_byte_range(&self, row: usize) -> Option<Range<usize>> {
+        let start = self.br_indexes.row_start(row)?;
+        let end = self
+            .br_indexes
+            .0
+            .get(row + 1)
+            .map(|&eol| eol_start(&self.text, eol))
+            .unwrap_or(self.text.len());
+        Some(start..end)
+    }
+
+    /// Returns the byte range of the provided row in the string, including its EOL bytes.
+    ///
+    /// The last row has no EOL bytes, so its range is the same as [`Text::row_byte_range`].
+    /// Returns [`None`] if the row does not exist.
+    pub fn row_byte_range_with_eol(&self, row: usize) -> Option<Range<usize>> {
+        let start = self.br_indexes.row_start(row)?;
+        let end = self
+            .br_indexes
+            .row_start(row + 1)
+            .unwrap_or(self.text.len());
+        Some(start..end)
+    }
+
+    /// Returns the first line.
+    ///
+    /// The returned slice is trimmed for any EOL bytes.
+    #[inline]
+    pub fn first_line(&self) -> &str {
+        self.get_row(0).expect("a text always has at least one row")
+    }
+
+    /// Returns the last line.
+    ///
+    /// The returned slice is trimmed for any EOL bytes. If the [`Text`] ends with an EOL, the last
+    /// line is empty.
+    #[inline]
+    pub fn last_line(&self) -> &str {
+        &self.text[self.br_indexes.last_row_start()..]
+    }
+
+    /// Returns the row and the content of the line that contains the provided byte offset.
+    ///
+    /// The returned line does not include the EOL bytes, but an offset that points to the EOL
+    /// bytes of a line is considered to be a part of that line. An offset equal to the length of
+    /// the [`Text`] is considered to be a part of the last line. Returns [`None`] if the offset is
+    /// past the end of the [`Text`].
+    pub fn line_at_byte(&self, byte: usize) -> Option<(usize, &str)> {
+        if byte > self.text.len() {
+            return None;
+        }
+
+        let row = self.br_indexes.0[1..].partition_point(|&eol| eol < byte);
+        Some((row, self.get_row(row)?))
+    }
+
+    /// Returns the [`char`] at the provided position.
+    ///
+    /// Returns [`None`] if the position is at or past the end of its row, or if the row does not
+    /// exist.
+    pub fn char_at(&self, pos: GridIndex) -> Option<char> {
+        let line = self.get_row(pos.row)?;
+        let col = self.col_to_utf8(pos.row, line, pos.col).ok()?;
+        line[col..].chars().next()
+    }
+
+    /// Returns the byte at the provided position.
+    ///
+    /// For a multibyte character this is the first byte of the character. Returns [`None`] if the
+    /// position is at or past the end of its row, or if the row does not exist.
+    pub fn byte_at(&self, pos: GridIndex) -> Option<u8> {
+        let line = self.get_row(pos.row)?;
+        let col = self.col_to_utf8(pos.row, line, pos.col).ok()?;
+        line.as_bytes().get(col).copied()
+    }
+
+    /// Returns an [`Iterator`] over the [`char`]s of the [`Text`] and their positions.
+    ///
+    /// The positions are in the [`Text`]'s expected encoding. EOL characters are also yielded,
+    /// positioned after the end of their row's content, which is shared by both characters of a
+    /// `\r\n`. The column is reset to zero on the following row. When counting grapheme clusters, every [`char`] of a cluster shares the
+    /// cluster's column.
+    pub fn char_indices(&self) -> impl Iterator<Item = (GridIndex, char)> + '_ {
+        let mut row = 0;
+        let mut col = 0;
+        self.text.char_indices().map(move |(i, c)| {
+            if self.br_indexes.0.get(row + 1).is_some_and(|&eol| i > eol) {
+                row += 1;
+                col = 0;
+            }
+
+            #[cfg(feature = "unicode-segmentation")]
+            if self.encoding_kind == Encoding::Graphemes {
+                use unicode_segmentation::GraphemeCursor;
+
+                let line = self.get_row(row).expect("row should exist");
+                let offset = i - self.nth_row(row).expect("row should exist");
+                let is_boundary = offset >= line.len()
+                    || GraphemeCursor::new(offset, line.len(), true)
+                        .is_boundary(line, 0)
+                        .unwrap_or(true);
+                if !is_boundary {
+                    return (GridIndex { row, col: col - 1 }, c);
+                }
+            }
+
+            let pos = GridIndex { row, col };
+            // every char of a line break is positioned at the end of the row
+            if self
+                .br_indexes
+                .0
+                .get(row + 1)
+                .is_some_and(|&eol| i >= eol_start(&self.text, eol))
+            {
+                return (pos, c);
+            }
+            col += match self.encoding_kind {
+                Encoding::UTF8 => c.len_utf8(),
+                Encoding::UTF16 => c.len_utf16(),
+                _ => 1,
+            };
+            (pos, c)
+        })
+    }
+
+    /// Creates a new [`Text`] with the content between the provided positions.
+    ///
+    /// The end position is exclusive. The new [`Text`] expects the same encoding as the current
+    /// one.
+    pub fn sub_text(&self, start: GridIndex, end: GridIndex) -> Result<Text> {
+        let (start, end) = (self.normalized(start)?, self.normalized(end)?);
+        let (start, end) = (start.min(end), start.max(end));
+        let start_byte = self.nth_row(start.row).expect("row was normalized") + start.col;
+        let end_byte = self.nth_row(end.row).expect("row was normalized") + end.col;
+        let mut t = Text::with_eol_mode(
+            self.text[start_byte..end_byte].to_string(),
+            self.encoding_kind,
+            self.eol_mode,
+        );
+        t.checked_columns = self.checked_columns;
+
+        Ok(t)
+    }
+
+    /// Returns the content between the provided positions.
+    ///
+    /// The end position is exclusive. If `normalize_eol` is [`None`] the content is borrowed as
+    /// is, otherwise an owned [`String`] is returned where every line break in the range is
+    /// replaced with the provided [`EolKind`].
+    pub fn slice(
+        &self,
+        start: GridIndex,
+        end: GridIndex,
+        normalize_eol: Option<EolKind>,
+    ) -> Result<Cow<'_, str>> {
+        let (start, end) = (self.normalized(start)?, self.normalized(end)?);
+        let (start, end) = (start.min(end), start.max(end));
+        let start_byte = self.nth_row(start.row).expect("row was normalized") + start.col;
+        let end_byte = self.nth_row(end.row).expect("row was normalized") + end.col;
+        let Some(eol) = normalize_eol else {
+            return Ok(Cow::Borrowed(&self.text[start_byte..end_byte]));
+        };
+
+        let mut s = String::with_capacity(end_byte - start_byte);
+        let mut cursor = start_byte;
+        for &br in &self.br_indexes.0[start.row + 1..=end.row] {
+            s.push_str(&self.text[cursor..eol_start(&self.text, br)]);
+            s.push_str(eol.as_str());
+            cursor = br + 1;
+        }
+        s.push_str(&self.text[cursor..end_byte]);
+
+        Ok(Cow::Owned(s))
+    }
+
+    /// Returns a copy of the content where every line break is replaced with the provided
+    /// [`EolKind`].
+    ///
+    /// The [`Text`] itself is left as is.
+    pub fn to_string_with_eol(&self, eol: EolKind) -> String {
+        let mut s = String::with_capacity(self.text.len());
+        let mut cursor = 0;
+        for &br in &self.br_indexes.0[1..] {
+            s.push_str(&self.text[cursor..eol_start(&self.text, br)]);
+            s.push_str(eol.as_str());
+            cursor = br + 1;
+        }
+        s.push_str(&self.text[cursor..]);
+
+        s
+    }
+
+    /// Computes the visual column of the provided position.
+    ///
+    /// A tab expands to the next column that is a multiple of `tab_width`, and every other
+    /// [`char`] has a width of one.
+    pub fn visual_column(&self, pos: GridIndex, tab_width: usize) -> Result<usize> {
+        let line = self
+            .get_row(pos.row)
+            .ok_or(Error::oob_row(self.br_indexes.row_count(), pos.row))?;
+        let col = self.col_to_utf8(pos.row, line, pos.col)?;
+        Ok(line[..col].chars().fold(0, |width, c| match c {
+            '\t' if tab_width > 0 => (width / tab_width + 1) * tab_width,
+            '\t' => width,
+            _ => width + 1,
+        }))
     }
 
     /// Returns an [`Iterator`] over the lines present in the [`Text`].
@@ -418,712 +1579,4516 @@ impl Text {
     ///
     /// If any of the fields of [`Text`] is out of sync, the iterator may panic or return
     /// incorrect results.
-    pub fn lines(&self) -> TextLines {
+    pub fn lines(&self) -> TextLines<'_> {
         TextLines::new(self.text.as_str(), &self.br_indexes.0)
     }
 
-    fn update_prep(&mut self) {
-        self.old_br_indexes.clone_from(&self.br_indexes);
+    /// Returns an [`Iterator`] over the lines in the provided row range.
+    ///
+    /// The yielded slices are trimmed for any EOL bytes, same as [`Text::lines`]. Each line is
+    /// looked up directly through the EOL indexes, so only the rows in the range are visited. The
+    /// range is clamped to the existing rows.
+    pub fn lines_in(
+        &self,
+        rows: Range<usize>,
+    ) -> impl DoubleEndedIterator<Item = &str> + ExactSizeIterator + '_ {
+        let end = rows.end.min(self.line_count());
+        (rows.start.min(end)..end).map(|row| self.get_row(row).expect("row should exist"))
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use crate::change::GridIndex;
+    /// Compares the content of two [`Text`]s, ignoring the EOL sequences used.
+    ///
+    /// This is useful to compare content that was loaded with different line endings, such as
+    /// `\n` and `\r\n`. The expected encodings of the [`Text`]s are not compared.
+    pub fn eq_ignoring_eol(&self, other: &Text) -> bool {
+        self.line_count() == other.line_count() && self.lines().eq(other.lines())
+    }
 
-    use super::Text;
+    /// Returns `true` if the last row of the [`Text`] is terminated with a line break.
+    ///
+    /// A [`Text`] that only contains a line break also ends with a line break, whereas an empty
+    /// [`Text`] does not.
+    pub fn ends_with_newline(&self) -> bool {
+        self.br_indexes.row_count().get() > 1 && self.br_indexes.last_row_start() == self.text.len()
+    }
 
-    // All index modifying tests must check the resulting string, and breakline indexes.
+    /// Returns an [`Iterator`] over the length of each line in the [`Text`]'s expected encoding.
+    ///
+    /// The lengths do not include the EOL bytes.
+    pub fn line_lengths(&self) -> impl Iterator<Item = usize> + '_ {
+        self.lines().map(|line| self.encoded_len(line))
+    }
 
-    #[test]
-    fn nth_row() {
-        let t = Text::new("Apple\nOrange\nBanana\nCoconut\nFruity".into());
-        assert_eq!(t.br_indexes, [0, 5, 12, 19, 27]);
-        assert_eq!(t.nth_row(0), Some(0));
-        assert_eq!(t.nth_row(1), Some(6));
-        assert_eq!(t.nth_row(2), Some(13));
-        assert_eq!(t.nth_row(3), Some(20));
-        assert_eq!(t.nth_row(4), Some(28));
-        assert_eq!(t.nth_row(5), None);
+    /// Returns an [`Iterator`] over the paragraphs of the [`Text`].
+    ///
+    /// A paragraph is a run of consecutive lines that are not empty, and is separated from the
+    /// next paragraph by one or more empty lines. A line containing only whitespace is not
+    /// considered empty. The yielded paragraphs include the EOL bytes between their lines, but not
+    /// the EOL bytes of their last line.
+    pub fn paragraphs(&self) -> impl Iterator<Item = &str> + '_ {
+        let is_empty = |row| self.get_row(row).is_some_and(str::is_empty);
+        let mut rows = 0..self.line_count();
+        std::iter::from_fn(move || {
+            let start = rows.by_ref().find(|&row| !is_empty(row))?;
+            let end = rows.by_ref().take_while(|&row| !is_empty(row)).last();
+            let end = end.unwrap_or(start);
+            let start = self.nth_row(start).expect("row should exist");
+            let end_line = self.get_row(end).expect("row should exist");
+            let end = self.nth_row(end).expect("row should exist") + end_line.len();
+            Some(&self.text[start..end])
+        })
+    }
+
+    /// Returns an [`Iterator`] over the start and end positions of each line.
+    ///
+    /// The end position is the end of the line's content, excluding the EOL bytes. Both positions
+    /// are in the [`Text`]'s expected encoding.
+    pub fn line_spans(&self) -> impl Iterator<Item = (GridIndex, GridIndex)> + '_ {
+        self.line_lengths()
+            .enumerate()
+            .map(|(row, len)| (GridIndex { row, col: 0 }, GridIndex { row, col: len }))
+    }
+
+    /// Returns the length of the provided row in the [`Text`]'s expected encoding.
+    ///
+    /// The length does not include the EOL bytes, so it is also the column of the end of the row.
+    /// Returns [`None`] if the row does not exist.
+    pub fn row_len_in_encoding(&self, row: usize) -> Option<usize> {
+        self.get_row(row).map(|line| self.encoded_len(line))
+    }
+
+    /// The length of a line in the [`Text`]'s expected encoding.
+    pub(crate) fn encoded_len(&self, line: &str) -> usize {
+        (self.encoding[1])(line, line.len()).expect("the end of a str is always a char boundary")
+    }
+
+    /// Converts a column of the provided row from the [`Text`]'s expected encoding to UTF-8.
+    ///
+    /// `line` must be the content of `row`, excluding the EOL bytes.
+    pub(crate) fn col_to_utf8(&self, row: usize, line: &str, col: usize) -> Result<usize> {
+        (self.encoding[0])(line, col).ok_or_else(|| self.in_between(row, line, col))
+    }
+
+    /// Converts a UTF-8 column of the provided row to the [`Text`]'s expected encoding.
+    ///
+    /// `line` must be the content of `row`, excluding the EOL bytes.
+    pub(crate) fn col_from_utf8(&self, row: usize, line: &str, col: usize) -> Result<usize> {
+        (self.encoding[1])(line, col).ok_or_else(|| self.in_between(row, line, col))
+    }
+
+    fn in_between(&self, row: usize, line: &str, col: usize) -> Error {
+        Error::InBetweenCharBoundries {
+            encoding: self.encoding_kind,
+            row,
+            col,
+            line_len: self.encoded_len(line),
+        }
+    }
+
+    /// Computes the [`Change`]s needed to transform the current content in to the provided
+    /// string.
+    ///
+    /// The result is a minimal replacement computed from the common prefix and suffix of the two
+    /// strings, which means at most one change is returned. If the contents are the same an
+    /// empty [`Vec`] is returned. The positions are in the [`Text`]'s expected encoding.
+    ///
+    /// This is useful to perform incremental updates when only the full new content is known,
+    /// such as after formatting a document.
+    pub fn diff(&self, new: &str) -> Vec<Change<'static>> {
+        let old = self.text.as_str();
+        let is_crlf_split = |s: &str, i: usize| {
+            s.as_bytes()[..i].ends_with(b"\r") && s.as_bytes()[i..].starts_with(b"\n")
+        };
+
+        let mut prefix = old
+            .bytes()
+            .zip(new.bytes())
+            .take_while(|(a, b)| a == b)
+            .count();
+        while !old.is_char_boundary(prefix) || is_crlf_split(old, prefix) {
+            prefix -= 1;
+        }
+
+        let max_suffix = old.len().min(new.len()) - prefix;
+        let mut suffix = old
+            .bytes()
+            .rev()
+            .zip(new.bytes().rev())
+            .take(max_suffix)
+            .take_while(|(a, b)| a == b)
+            .count();
+        while !old.is_char_boundary(old.len() - suffix) || is_crlf_split(old, old.len() - suffix) {
+            suffix -= 1;
+        }
+
+        let removed = prefix..old.len() - suffix;
+        let inserted = &new[prefix..new.len() - suffix];
+        if removed.is_empty() && inserted.is_empty() {
+            return vec![];
+        }
+
+        let start = self.byte_to_grid_index(removed.start);
+        let change = if removed.is_empty() {
+            Change::Insert {
+                at: start,
+                text: inserted.to_string().into(),
+            }
+        } else if inserted.is_empty() {
+            Change::Delete {
+                start,
+                end: self.byte_to_grid_index(removed.end),
+            }
+        } else {
+            Change::Replace {
+                start,
+                end: self.byte_to_grid_index(removed.end),
+                text: inserted.to_string().into(),
+            }
+        };
+
+        vec![change]
+    }
+
+    /// Replace every occurrence of `needle` with `replacement`, returning the number of
+    /// replacements performed.
+    ///
+    /// The matches do not overlap, and are replaced starting from the end of the [`Text`]. A match
+    /// that starts or ends in between the bytes of a `\r\n` is widened to replace the whole line
+    /// break. The [`Updateable`] is provided with a context for every replacement, and vetoed
+    /// replacements are not counted. If there is no match or the needle is empty, nothing is done
+    /// and zero is returned.
+    ///
+    /// If a replacement fails, the error is returned immediately and the replacements made after
+    /// it in the text remain applied.
+    pub fn replace_all<U: Updateable + ?Sized>(
+        &mut self,
+        needle: &str,
+        replacement: &str,
+        updateable: &mut U,
+    ) -> Result<usize> {
+        if needle.is_empty() {
+            return Ok(0);
+        }
+        let matches: Vec<usize> =
+            memmem::find_iter(self.text.as_bytes(), needle.as_bytes()).collect();
+        let mut count = 0;
+        for &start in matches.iter().rev() {
+            let end = start + needle.len();
+            let start = self.utf8_grid_index(start - self.splits_crlf(start) as usize)?;
+            let end = self.utf8_grid_index(end + self.splits_crlf(end) as usize)?;
+            self.update_prep(updateable);
+            let text_len = self.text.len();
+            count += self
+                .replace_utf8(replacement, start, end, text_len, updateable)?
+                .is_some() as usize;
+        }
+
+        Ok(count)
+    }
+
+    /// Rebase a [`Change`] performed on the whole [`Text`] to be relative to a sub range.
+    ///
+    /// This is useful for injected languages, where a separate parser is kept in sync with a
+    /// range of the document. The `outer` range is exclusive on the end, and all of the positions
+    /// are expected to be in the [`Text`]'s expected encoding.
+    ///
+    /// Positions in the first row of the range have their column offset by the start column of
+    /// the range, and the rows are offset by the start row. When the change only partially
+    /// overlaps with the range, the changed range is clamped to the sub range. Inserts are
+    /// considered inside if they are performed at the start or end of the range.
+    ///
+    /// Returns `None` if the change is entirely outside of the range, or if it is a
+    /// [`Change::ReplaceFull`], as the range no longer exists in the new content.
+    pub fn sub_range_edit<'a>(
+        &self,
+        outer: (GridIndex, GridIndex),
+        change: &Change<'a>,
+    ) -> Option<Change<'a>> {
+        let (outer_start, outer_end) = (outer.0.min(outer.1), outer.0.max(outer.1));
+        let rebase = |pos: GridIndex| {
+            let pos = pos.clamp(outer_start, outer_end);
+            if pos.row == outer_start.row {
+                GridIndex {
+                    row: 0,
+                    col: pos.col - outer_start.col,
+                }
+            } else {
+                GridIndex {
+                    row: pos.row - outer_start.row,
+                    col: pos.col,
+                }
+            }
+        };
+        let is_inside = |pos: GridIndex| outer_start <= pos && pos <= outer_end;
+        let overlaps = |start: GridIndex, end: GridIndex| start < outer_end && end > outer_start;
+
+        let change = match change {
+            Change::Insert { at, text } => {
+                if !is_inside(*at) {
+                    return None;
+                }
+                Change::Insert {
+                    at: rebase(*at),
+                    text: text.clone(),
+                }
+            }
+            Change::Delete { start, end } => {
+                let (start, end) = (*start.min(end), *start.max(end));
+                if !overlaps(start, end) {
+                    return None;
+                }
+                Change::Delete {
+                    start: rebase(start),
+                    end: rebase(end),
+                }
+            }
+            Change::Replace { start, end, text } => {
+                let (start, end) = (*start.min(end), *start.max(end));
+                if start == end && !is_inside(start) || start != end && !overlaps(start, end) {
+                    return None;
+                }
+                Change::Replace {
+                    start: rebase(start),
+                    end: rebase(end),
+                    text: text.clone(),
+                }
+            }
+            Change::ReplaceFull(_) => return None,
+        };
+
+        Some(change)
+    }
+
+    /// Computes the position a smart home key press should move the cursor to.
+    ///
+    /// If the cursor is past the first non-whitespace character of the row, the column of the
+    /// first non-whitespace character is returned. Otherwise column zero is returned. For a row
+    /// that only contains whitespace, the end of the row is treated as the first non-whitespace
+    /// character.
+    ///
+    /// The columns are in the [`Text`]'s expected encoding. If the row does not exist, the
+    /// position is returned as is.
+    pub fn smart_home(&self, at: GridIndex) -> GridIndex {
+        let Some(line) = self.get_row(at.row) else {
+            return at;
+        };
+        let indent = self.encoded_len(&line[..line.len() - line.trim_start().len()]);
+        let col = if at.col > indent { indent } else { 0 };
+
+        GridIndex { row: at.row, col }
+    }
+
+    /// Returns an [`Iterator`] over the byte ranges of the words in the provided row.
+    ///
+    /// A word is a run of alphanumeric characters or underscores. The ranges are relative to the
+    /// start of the row. If the row does not exist, the iterator is empty.
+    pub fn word_boundaries(&self, row: usize) -> impl Iterator<Item = Range<usize>> + '_ {
+        let mut chars = self
+            .get_row(row)
+            .unwrap_or_default()
+            .char_indices()
+            .peekable();
+        let is_word = |c: char| c.is_alphanumeric() || c == '_';
+        std::iter::from_fn(move || {
+            let (start, c) = chars.find(|&(_, c)| is_word(c))?;
+            let mut end = start + c.len_utf8();
+            while let Some((i, c)) = chars.next_if(|&(_, c)| is_word(c)) {
+                end = i + c.len_utf8();
+            }
+
+            Some(start..end)
+        })
+    }
+
+    /// Returns the position of the first occurrence of the provided string.
+    ///
+    /// The returned [`GridIndex`] is in the [`Text`]'s expected encoding.
+    /// A match that starts in between the bytes of a `\r\n` is moved to the end of its row.
+    /// Returns `None` if there is no match or the needle is empty.
+    pub fn find(&self, needle: &str) -> Option<GridIndex> {
+        if needle.is_empty() {
+            return None;
+        }
+        memmem::find(self.text.as_bytes(), needle.as_bytes()).map(|i| self.byte_to_grid_index(i))
+    }
+
+    /// Returns the position of the last occurrence of the provided string.
+    ///
+    /// The returned [`GridIndex`] is in the [`Text`]'s expected encoding.
+    /// A match that starts in between the bytes of a `\r\n` is moved to the end of its row.
+    /// Returns `None` if there is no match or the needle is empty.
+    pub fn rfind(&self, needle: &str) -> Option<GridIndex> {
+        if needle.is_empty() {
+            return None;
+        }
+        memmem::rfind(self.text.as_bytes(), needle.as_bytes()).map(|i| self.byte_to_grid_index(i))
+    }
+
+    /// Returns an [`Iterator`] over the positions of each occurrence of the provided string.
+    ///
+    /// The matches do not overlap, meaning searching for `"aa"` in `"aaa"` only yields the
+    /// first position. The returned [`GridIndex`]'s are in the [`Text`]'s expected encoding.
+    /// A match that starts in between the bytes of a `\r\n` is moved to the end of its row.
+    /// If the needle is empty, nothing is yielded.
+    pub fn find_all<'a>(&'a self, needle: &'a str) -> impl Iterator<Item = GridIndex> + 'a {
+        memmem::find_iter(self.text.as_bytes(), needle.as_bytes())
+            .take_while(move |_| !needle.is_empty())
+            .map(|i| self.byte_to_grid_index(i))
+    }
+
+    /// Returns an [`Iterator`] over the start and end positions of each match of the provided
+    /// [`regex::Regex`].
+    ///
+    /// The pattern is matched against the whole text, which means a match may span multiple rows.
+    /// The end position is exclusive, and both positions are in the [`Text`]'s expected encoding.
+    /// A match that starts or ends in between the bytes of a `\r\n` is widened to include the
+    /// whole line break.
+    #[cfg_attr(docsrs, doc(cfg(feature = "regex")))]
+    #[cfg(feature = "regex")]
+    pub fn find_regex<'a>(
+        &'a self,
+        re: &'a regex::Regex,
+    ) -> impl Iterator<Item = (GridIndex, GridIndex)> + 'a {
+        re.find_iter(&self.text).map(|m| {
+            // an end in between the bytes of a "\r\n" includes the whole line break
+            let end = m.end() + self.splits_crlf(m.end()) as usize;
+            (
+                self.byte_to_grid_index(m.start()),
+                self.byte_to_grid_index(end),
+            )
+        })
+    }
+
+    /// Returns the [`tree_sitter::Range`] of the content that is modified by the provided
+    /// [`Change`], before the change is applied.
+    ///
+    /// Inserts return an empty range at the insertion position. The columns of the range's points
+    /// are byte offsets as expected by `tree-sitter`.
+    #[cfg_attr(docsrs, doc(cfg(feature = "tree-sitter")))]
+    #[cfg(feature = "tree-sitter")]
+    pub fn ts_range_of(&self, change: &Change) -> Result<tree_sitter::Range> {
+        let (start, end) = match change {
+            Change::Insert { at, .. } => {
+                let at = self.normalized(*at)?;
+                (at, at)
+            }
+            Change::Delete { start, end } | Change::Replace { start, end, .. } => {
+                let (start, end) = (self.normalized(*start)?, self.normalized(*end)?);
+                (start.min(end), start.max(end))
+            }
+            Change::ReplaceFull(_) => (
+                GridIndex { row: 0, col: 0 },
+                GridIndex {
+                    row: self.br_indexes.row_count().get() - 1,
+                    col: self.text.len() - self.br_indexes.last_row_start(),
+                },
+            ),
+        };
+
+        let byte = |pos: GridIndex| {
+            self.nth_row(pos.row)
+                .map_or(self.text.len(), |rs| rs + pos.col)
+        };
+        Ok(tree_sitter::Range {
+            start_byte: byte(start),
+            end_byte: byte(end),
+            start_point: start.into(),
+            end_point: end.into(),
+        })
+    }
+
+    /// Transform a position from the [`Text`]'s expected encoding to a UTF-8 position.
+    ///
+    /// Unlike [`GridIndex::normalize`] this does not modify the [`Text`]. A position on the row
+    /// after the last row is treated as the end of the [`Text`].
+    fn normalized(&self, pos: GridIndex) -> Result<GridIndex> {
+        let row_count = self.br_indexes.row_count().get();
+        if pos.row == row_count {
+            return Ok(GridIndex {
+                row: row_count - 1,
+                col: self.text.len() - self.br_indexes.last_row_start(),
+            });
+        }
+        let line = self
+            .get_row(pos.row)
+            .ok_or(Error::oob_row(self.br_indexes.row_count(), pos.row))?;
+        self.check_col(pos.row, line, pos.col)?;
+        Ok(GridIndex {
+            row: pos.row,
+            col: self.col_to_utf8(pos.row, line, pos.col)?,
+        })
+    }
+
+    /// Returns `true` if the byte index is in between the bytes of a `\r\n`.
+    fn splits_crlf(&self, byte: usize) -> bool {
+        let bytes = self.text.as_bytes();
+        byte > 0 && bytes.get(byte) == Some(&b'\n') && bytes[byte - 1] == b'\r'
+    }
+
+    /// Converts a byte index of the string to a [`GridIndex`] in the [`Text`]'s expected encoding.
+    ///
+    /// A byte index in between the bytes of a `\r\n` is moved to the end of the row, as such a
+    /// position can not be edited.
+    ///
+    /// # Panics
+    ///
+    /// If the byte index is not on a char boundary.
+    fn byte_to_grid_index(&self, byte: usize) -> GridIndex {
+        let row = self.br_indexes.0[1..].partition_point(|&eol| eol < byte);
+        let row_start = self.nth_row(row).expect("row should exist");
+        let byte = self
+            .br_indexes
+            .0
+            .get(row + 1)
+            .map_or(byte, |&br| byte.min(eol_start(&self.text, br)));
+        GridIndex {
+            row,
+            col: self.encoded_len(&self.text[row_start..byte]),
+        }
+    }
+
+    /// Converts the provided position to a byte index of the string.
+    ///
+    /// The column can be of any [`Column`] type. A [`ByteCol`][`crate::change::ByteCol`] is
+    /// used as is without any encoding conversion, and only checked to be on a char boundary of
+    /// its row. A column past the end of the row returns [`Error::OutOfBoundsColumn`], unless it
+    /// is in the [`Text`]'s expected encoding, in which case [`Text::set_checked_columns`] is
+    /// respected.
+    pub fn byte_index<C: Column>(&self, pos: GridIndex<C>) -> Result<usize> {
+        let range = self
+            .row_byte_range(pos.row)
+            .ok_or(Error::oob_row(self.br_indexes.row_count(), pos.row))?;
+        let line = &self.text[range.clone()];
+        Ok(range.start + pos.col.to_utf8(self, pos.row, line)?.min(line.len()))
+    }
+
+    /// Converts a byte index of the string to a [`GridIndex`] with a UTF-8 column.
+    ///
+    /// Returns [`Error::InvalidByteOffset`] if the index is past the end of the string, is not on
+    /// a char boundary, or is in between the bytes of a line break.
+    fn utf8_grid_index(&self, byte: usize) -> Result<GridIndex> {
+        let invalid = || Error::InvalidByteOffset {
+            byte,
+            len: self.text.len(),
+        };
+        if !self.text.is_char_boundary(byte) {
+            return Err(invalid());
+        }
+
+        let row = self.br_indexes.0[1..].partition_point(|&eol| eol < byte);
+        let line = self.row_byte_range(row).expect("row should exist");
+        if byte > line.end {
+            return Err(invalid());
+        }
+
+        Ok(GridIndex {
+            row,
+            col: byte - line.start,
+        })
+    }
+
+    /// Returns an [`Iterator`] over the rows whose content changed since the last call to
+    /// [`Text::clear_dirty`].
+    ///
+    /// The rows are accumulated across edits, and are shifted when rows are inserted or removed
+    /// before them, so that they always point to the current position of the modified content.
+    /// The rows are yielded in ascending order without duplicates.
+    pub fn dirty_rows(&self) -> impl Iterator<Item = usize> + '_ {
+        self.dirty_rows.rows()
+    }
+
+    /// Clears the rows tracked by [`Text::dirty_rows`].
+    ///
+    /// This should usually be called after the dirty rows have been processed, such as after
+    /// rendering.
+    pub fn clear_dirty(&mut self) {
+        self.dirty_rows.clear();
+    }
+
+    /// Sets whether columns past the end of a line are rejected.
+    ///
+    /// By default, a column that exceeds the length of its line is clamped to the end of the
+    /// line. When enabled, such a column returns [`Error::OutOfBoundsColumn`] instead, which is
+    /// useful to detect malformed positions sent by a client.
+    pub fn set_checked_columns(&mut self, checked: bool) {
+        self.checked_columns = checked;
+    }
+
+    /// Returns an error if checked columns are enabled and the column exceeds the line's length.
+    ///
+    /// `line` must be the content of `row`, excluding the EOL bytes.
+    pub(crate) fn check_col(&self, row: usize, line: &str, col: usize) -> Result<()> {
+        if !self.checked_columns {
+            return Ok(());
+        }
+
+        let max = self.encoded_len(line);
+        if col > max {
+            return Err(Error::OutOfBoundsColumn {
+                row,
+                max,
+                current: col,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Normalizes each of the provided positions, see [`GridIndex::normalize`].
+    ///
+    /// If any of the positions fails to normalize, the line break pushed for the previous
+    /// positions is removed before returning the error.
+    fn normalize_positions<const N: usize, U: Updateable + ?Sized>(
+        &mut self,
+        positions: [&mut GridIndex; N],
+        updateable: &U,
+        text_len: usize,
+    ) -> Result<()> {
+        for pos in positions {
+            if let Err(err) = pos.normalize(self) {
+                self.abort_update(updateable, text_len);
+                return Err(err);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn update_prep<U: Updateable + ?Sized>(&mut self, updateable: &U) {
+        if updateable.needs_old_state() {
+            self.old_br_indexes.clone_from(&self.br_indexes);
+        }
+    }
+
+    /// Brings the [`EolIndexes`] back in sync with the string after an [`Updateable`] returned an
+    /// error or vetoed the edit.
+    ///
+    /// `text_len` is the length of the string before the positions were normalized, as
+    /// normalizing a position may push a line break. If the old state was not copied, the
+    /// [`EolIndexes`] are computed again from the string.
+    #[cold]
+    fn abort_update<U: Updateable + ?Sized>(&mut self, updateable: &U, text_len: usize) {
+        self.text.truncate(text_len);
+        if updateable.needs_old_state() {
+            self.br_indexes.clone_from(&self.old_br_indexes);
+        } else {
+            self.br_indexes = EolIndexes::with_eol_mode(&self.text, self.eol_mode);
+        }
+    }
+}
+
+/// Forwards the edits to the wrapped [`Updateable`] without allowing it to veto them.
+pub(crate) struct Unvetoed<'u, U: ?Sized>(pub(crate) &'u mut U);
+
+impl<U: Updateable + ?Sized> Updateable for Unvetoed<'_, U> {
+    fn needs_old_state(&self) -> bool {
+        self.0.needs_old_state()
+    }
+
+    fn update(&mut self, ctx: UpdateContext) -> Result<()> {
+        self.0.update(ctx)
+    }
+}
+
+/// Provides the [`UpdateContext`] to the [`Updateable`] if it does not veto the edit.
+///
+/// Returns `false` if the edit was vetoed with [`Updateable::should_apply`].
+#[inline]
+fn run_updateable<U: Updateable + ?Sized>(updateable: &mut U, ctx: UpdateContext) -> Result<bool> {
+    if !updateable.should_apply(&ctx) {
+        return Ok(false);
+    }
+
+    updateable.update(ctx)?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::change::GridIndex;
+
+    use super::Text;
+
+    // All index modifying tests must check the resulting string, and breakline indexes.
+
+    #[test]
+    fn nth_row() {
+        let t = Text::new("Apple\nOrange\nBanana\nCoconut\nFruity".into());
+        assert_eq!(t.br_indexes, [0, 5, 12, 19, 27]);
+        assert_eq!(t.nth_row(0), Some(0));
+        assert_eq!(t.nth_row(1), Some(6));
+        assert_eq!(t.nth_row(2), Some(13));
+        assert_eq!(t.nth_row(3), Some(20));
+        assert_eq!(t.nth_row(4), Some(28));
+        assert_eq!(t.nth_row(5), None);
+    }
+
+    #[cfg(feature = "lsp-types")]
+    mod constructor_for {
+        use lsp_types::PositionEncodingKind;
+        use rstest::rstest;
+
+        use super::*;
+        use crate::error::Encoding;
+
+        const UTF8: PositionEncodingKind = PositionEncodingKind::UTF8;
+        const UTF16: PositionEncodingKind = PositionEncodingKind::UTF16;
+        const UTF32: PositionEncodingKind = PositionEncodingKind::UTF32;
+
+        #[rstest]
+        #[case(&[], Encoding::UTF16)]
+        #[case(&[UTF16], Encoding::UTF16)]
+        #[case(&[UTF8], Encoding::UTF8)]
+        #[case(&[UTF32], Encoding::UTF32)]
+        #[case(&[UTF16, UTF32], Encoding::UTF32)]
+        #[case(&[UTF16, UTF8], Encoding::UTF8)]
+        #[case(&[UTF32, UTF8], Encoding::UTF8)]
+        #[case(&[UTF16, UTF32, UTF8], Encoding::UTF8)]
+        fn constructor_for(#[case] encodings: &[PositionEncodingKind], #[case] expected: Encoding) {
+            let t = Text::constructor_for(encodings)(String::new());
+            assert_eq!(t.encoding(), expected);
+        }
+    }
+
+    #[cfg(feature = "lsp-types")]
+    mod apply_lsp_changes {
+        use lsp_types::{Position, Range, TextDocumentContentChangeEvent};
+
+        use super::*;
+
+        fn change(
+            start: (u32, u32),
+            end: (u32, u32),
+            text: &str,
+        ) -> TextDocumentContentChangeEvent {
+            TextDocumentContentChangeEvent {
+                range: Some(Range::new(
+                    Position::new(start.0, start.1),
+                    Position::new(end.0, end.1),
+                )),
+                range_length: None,
+                text: text.to_string(),
+            }
+        }
+
+        #[test]
+        fn in_order() {
+            let mut t = Text::new_utf16("fn main() {}".into());
+            t.apply_lsp_changes(
+                &[
+                    change((0, 11), (0, 11), "\n    let ゲート = 1;\n"),
+                    // only exists after the first change is applied
+                    change((1, 8), (1, 11), "x"),
+                ],
+                &mut (),
+            )
+            .unwrap();
+            assert_eq!(t.text, "fn main() {\n    let x = 1;\n}");
+            assert_eq!(t.br_indexes, [0, 11, 26]);
+        }
+
+        #[test]
+        fn full_replacement() {
+            let mut t = Text::new("Hello".into());
+            t.apply_lsp_changes(
+                &[
+                    change((0, 0), (0, 5), "World"),
+                    TextDocumentContentChangeEvent {
+                        range: None,
+                        range_length: None,
+                        text: "a\nb".into(),
+                    },
+                    change((1, 0), (1, 1), "c"),
+                ],
+                &mut (),
+            )
+            .unwrap();
+            assert_eq!(t.text, "a\nc");
+        }
+    }
+
+    #[cfg(feature = "lsp-types")]
+    mod as_full_change {
+        use super::*;
+
+        #[test]
+        fn round_trip() {
+            let t = Text::new_utf16("Hello\r\nゲート\n".into());
+            let change = t.as_full_change();
+            assert_eq!(change.range, None);
+            assert_eq!(change.text, t.text);
+
+            let mut new = Text::new_utf16(String::new());
+            new.update(change, &mut ()).unwrap();
+            assert_eq!(new, t);
+        }
+    }
+
+    mod delete {
+        use super::*;
+
+        #[test]
+        fn single_line() {
+            let mut t = Text::new("Hello, World!".into());
+            assert_eq!(t.br_indexes, [0]);
+            t.delete(
+                GridIndex { row: 0, col: 1 },
+                GridIndex { row: 0, col: 6 },
+                &mut (),
+            )
+            .unwrap();
+
+            assert_eq!(t.br_indexes, [0]);
+            assert_eq!(t.text, "H World!");
+        }
+
+        #[test]
+        fn multiline() {
+            let mut t = Text::new("Hello, World!\nApples\n Oranges\nPears".into());
+            assert_eq!(t.br_indexes, [0, 13, 20, 29]);
+            t.delete(
+                GridIndex { row: 1, col: 3 },
+                GridIndex { row: 3, col: 2 },
+                &mut (),
+            )
+            .unwrap();
+
+            assert_eq!(t.br_indexes, [0, 13]);
+            assert_eq!(t.text, "Hello, World!\nAppars");
+        }
+
+        #[test]
+        fn in_line_into_start() {
+            let mut t = Text::new("Hello, World!\nApples\n Oranges\nPears".into());
+            assert_eq!(t.br_indexes, [0, 13, 20, 29]);
+            t.delete(
+                GridIndex { row: 0, col: 1 },
+                GridIndex { row: 0, col: 4 },
+                &mut (),
+            )
+            .unwrap();
+
+            assert_eq!(t.br_indexes, [0, 10, 17, 26]);
+            assert_eq!(t.text, "Ho, World!\nApples\n Oranges\nPears");
+        }
+
+        #[test]
+        fn in_line_at_start() {
+            let mut t = Text::new("Hello, World!\nApples\n Oranges\nPears".into());
+            assert_eq!(t.br_indexes, [0, 13, 20, 29]);
+            t.delete(
+                GridIndex { row: 0, col: 0 },
+                GridIndex { row: 0, col: 4 },
+                &mut (),
+            )
+            .unwrap();
+
+            assert_eq!(t.br_indexes, [0, 9, 16, 25]);
+            assert_eq!(t.text, "o, World!\nApples\n Oranges\nPears");
+        }
+
+        #[test]
+        fn across_first_line() {
+            let mut t = Text::new("Hello, World!\nApplbs\n Oranges\nPears".into());
+            assert_eq!(t.br_indexes, [0, 13, 20, 29]);
+            t.delete(
+                GridIndex { row: 0, col: 3 },
+                GridIndex { row: 1, col: 4 },
+                &mut (),
+            )
+            .unwrap();
+
+            assert_eq!(t.br_indexes, [0, 5, 14]);
+            assert_eq!(t.text, "Helbs\n Oranges\nPears");
+        }
+
+        #[test]
+        fn across_last_line() {
+            let mut t = Text::new("Hello, World!\nApplbs\n Oranges\nPears".into());
+            assert_eq!(t.br_indexes, [0, 13, 20, 29]);
+            t.delete(
+                GridIndex { row: 2, col: 3 },
+                GridIndex { row: 3, col: 2 },
+                &mut (),
+            )
+            .unwrap();
+
+            assert_eq!(t.br_indexes, [0, 13, 20]);
+            assert_eq!(t.text, "Hello, World!\nApplbs\n Orars");
+        }
+
+        #[test]
+        fn in_line_at_middle() {
+            let mut t = Text::new("Hello, World!\nApples\n Oranges\nPears".into());
+            assert_eq!(t.br_indexes, [0, 13, 20, 29]);
+            t.delete(
+                GridIndex { row: 2, col: 1 },
+                GridIndex { row: 2, col: 4 },
+                &mut (),
+            )
+            .unwrap();
+
+            assert_eq!(t.br_indexes, [0, 13, 20, 26]);
+            assert_eq!(t.text, "Hello, World!\nApples\n nges\nPears");
+        }
+
+        #[test]
+        fn in_line_at_end() {
+            let mut t = Text::new("Hello, World!\nApples\n Oranges\nPears".into());
+            assert_eq!(t.br_indexes, [0, 13, 20, 29]);
+            t.delete(
+                GridIndex { row: 3, col: 1 },
+                GridIndex { row: 3, col: 4 },
+                &mut (),
+            )
+            .unwrap();
+
+            assert_eq!(t.br_indexes, [0, 13, 20, 29]);
+            assert_eq!(t.text, "Hello, World!\nApples\n Oranges\nPs");
+        }
+
+        #[test]
+        fn from_start() {
+            let mut t = Text::new("Hello, World!\nApples\n Oranges\nPears".into());
+            assert_eq!(t.br_indexes, [0, 13, 20, 29]);
+            t.delete(
+                GridIndex { row: 0, col: 0 },
+                GridIndex { row: 0, col: 5 },
+                &mut (),
+            )
+            .unwrap();
+
+            assert_eq!(t.br_indexes, [0, 8, 15, 24]);
+            assert_eq!(t.text, ", World!\nApples\n Oranges\nPears");
+        }
+
+        #[test]
+        fn from_end() {
+            let mut t = Text::new("Hello, World!\nApples\n Oranges\nPears".into());
+            assert_eq!(t.br_indexes, [0, 13, 20, 29]);
+            t.delete(
+                GridIndex { row: 3, col: 0 },
+                GridIndex { row: 3, col: 5 },
+                &mut (),
+            )
+            .unwrap();
+
+            assert_eq!(t.br_indexes, [0, 13, 20, 29]);
+            assert_eq!(t.text, "Hello, World!\nApples\n Oranges\n");
+        }
+
+        #[test]
+        fn br() {
+            let mut t = Text::new("Hello, World!\nBadApple\n".into());
+            assert_eq!(t.br_indexes, [0, 13, 22]);
+            t.delete(
+                GridIndex { row: 1, col: 8 },
+                GridIndex { row: 2, col: 0 },
+                &mut (),
+            )
+            .unwrap();
+
+            assert_eq!(t.br_indexes, [0, 13]);
+            assert_eq!(t.text, "Hello, World!\nBadApple");
+        }
+
+        #[test]
+        fn br_chain() {
+            let mut t = Text::new("Hello, World!\n\n\nBadApple\n".into());
+            assert_eq!(t.br_indexes, [0, 13, 14, 15, 24]);
+            t.delete(
+                GridIndex { row: 1, col: 0 },
+                GridIndex { row: 2, col: 0 },
+                &mut (),
+            )
+            .unwrap();
+
+            assert_eq!(t.br_indexes, [0, 13, 14, 23]);
+            assert_eq!(t.text, "Hello, World!\n\nBadApple\n");
+        }
+
+        #[test]
+        fn long_text_single_byte() {
+            let mut t = Text::new(
+                "Hello, World!\nBanana\nHuman\nInteresting\nSuper\nMohawk\nShrek is a great movie."
+                    .into(),
+            );
+            assert_eq!(t.br_indexes, [0, 13, 20, 26, 38, 44, 51]);
+            t.delete(
+                GridIndex { row: 1, col: 3 },
+                GridIndex { row: 5, col: 2 },
+                &mut (),
+            )
+            .unwrap();
+            assert_eq!(t.br_indexes, [0, 13, 21]);
+            assert_eq!(t.text, "Hello, World!\nBanhawk\nShrek is a great movie.");
+        }
+
+        #[test]
+        fn long_text_multi_byte() {
+            let mut t = Text::new(
+                "\
+誰かがかつて世界が私をロールつもりである私に言いました
+私は小屋で最もシャープなツールではありません
+彼女は彼女の指と親指でダムのようなものを探していました
+彼女の額の「L」の形をしました
+
+さて、年が来て起動し、彼らが来て停止しません
+ルールに供給され、私は地上走行をヒット
+楽しみのために生きることはない意味がありませんでした
+あなたの脳は、スマート取得しますが、あなたの頭はダム取得します
+
+見るために、あまりを行うことがそんなに
+だから、裏通りを取ると間違って何ですか？
+あなたが行っていない場合は、あなたが知っていることは決してないだろう
+あなたが輝くない場合は輝くことは決してないだろう"
+                    .into(),
+            );
+            assert_eq!(
+                t.br_indexes,
+                [0, 81, 148, 230, 274, 275, 342, 400, 479, 573, 574, 632, 693, 796]
+            );
+            t.delete(
+                GridIndex { row: 1, col: 3 },
+                GridIndex { row: 5, col: 0 },
+                &mut (),
+            )
+            .unwrap();
+            assert_eq!(
+                t.br_indexes,
+                [0, 81, 151, 209, 288, 382, 383, 441, 502, 605]
+            );
+            assert_eq!(
+                t.text,
+                "\
+誰かがかつて世界が私をロールつもりである私に言いました
+私さて、年が来て起動し、彼らが来て停止しません
+ルールに供給され、私は地上走行をヒット
+楽しみのために生きることはない意味がありませんでした
+あなたの脳は、スマート取得しますが、あなたの頭はダム取得します
+
+見るために、あまりを行うことがそんなに
+だから、裏通りを取ると間違って何ですか？
+あなたが行っていない場合は、あなたが知っていることは決してないだろう
+あなたが輝くない場合は輝くことは決してないだろう"
+            );
+        }
+    }
+
+    mod insert {
+        use crate::{
+            error::{Encoding, Error},
+            updateables::UpdateContext,
+        };
+
+        use super::*;
+
+        #[test]
+        fn in_between_char_boundaries() {
+            let mut t = Text::new("abc\nゲート".into());
+            let err = t
+                .insert("!", GridIndex { row: 1, col: 4 }, &mut ())
+                .unwrap_err();
+            assert_eq!(
+                err,
+                Error::InBetweenCharBoundries {
+                    encoding: Encoding::UTF8,
+                    row: 1,
+                    col: 4,
+                    line_len: 9
+                }
+            );
+            assert_eq!(
+                err.to_string(),
+                "Provided column position 4 of row 1 with length 9 is between char boundries for UTF8."
+            );
+        }
+
+        #[test]
+        fn old_state() {
+            let mut t = Text::new("a\nb".into());
+            t.insert("\n", GridIndex { row: 0, col: 0 }, &mut ())
+                .unwrap();
+            // `()` does not read the old positions, so they are not copied
+            assert_eq!(t.old_br_indexes, []);
+            t.insert(
+                "\n",
+                GridIndex { row: 0, col: 0 },
+                &mut |_: UpdateContext| Ok(()),
+            )
+            .unwrap();
+            assert_eq!(t.old_br_indexes, [0, 0, 2]);
+            assert_eq!(t.br_indexes, [0, 0, 1, 3]);
+        }
+
+        #[test]
+        fn into_empty() {
+            let mut t = Text::new(String::new());
+            assert_eq!(t.br_indexes.0, [0]);
+            t.insert("Hello, World!", GridIndex { row: 0, col: 0 }, &mut ())
+                .unwrap();
+
+            assert_eq!(t.text, "Hello, World!");
+            assert_eq!(t.br_indexes, [0]);
+        }
+
+        #[test]
+        fn in_start() {
+            let mut t = Text::new(String::from("Apples"));
+            assert_eq!(t.br_indexes.0, [0]);
+            t.insert("Hello, World!", GridIndex { row: 0, col: 0 }, &mut ())
+                .unwrap();
+
+            assert_eq!(t.text, "Hello, World!Apples");
+            assert_eq!(t.br_indexes, [0]);
+        }
+
+        #[test]
+        fn in_end() {
+            let mut t = Text::new(String::from("Apples"));
+            assert_eq!(t.br_indexes.0, [0]);
+            t.insert("Hello, \nWorld!\n", GridIndex { row: 0, col: 6 }, &mut ())
+                .unwrap();
+
+            assert_eq!(t.text, "ApplesHello, \nWorld!\n");
+            assert_eq!(t.br_indexes, [0, 13, 20]);
+        }
+
+        #[test]
+        fn end_of_multiline() {
+            let mut t = Text::new(String::from("Apples\nBashdjad\nashdkasdh\nasdsad"));
+            assert_eq!(t.br_indexes.0, [0, 6, 15, 25]);
+            t.insert("Hello, \nWorld!\n", GridIndex { row: 3, col: 2 }, &mut ())
+                .unwrap();
+
+            assert_eq!(
+                t.text,
+                "Apples\nBashdjad\nashdkasdh\nasHello, \nWorld!\ndsad"
+            );
+            assert_eq!(t.br_indexes, [0, 6, 15, 25, 35, 42]);
+        }
+
+        #[test]
+        fn after_last_row() {
+            let mut t = Text::new(String::from("ABC\nDEF"));
+            t.insert("G", GridIndex { row: 2, col: 0 }, &mut ())
+                .unwrap();
+
+            assert_eq!(t.text, "ABC\nDEF\nG");
+            assert_eq!(t.br_indexes, [0, 3, 7]);
+        }
+
+        #[test]
+        fn multi_line_in_middle() {
+            let mut t = Text::new(String::from("ABC\nDEF"));
+            assert_eq!(t.br_indexes.0, [0, 3]);
+            t.insert("Hello,\n World!\n", GridIndex { row: 1, col: 1 }, &mut ())
+                .unwrap();
+
+            assert_eq!(t.text, "ABC\nDHello,\n World!\nEF");
+            assert_eq!(t.br_indexes.0, [0, 3, 11, 19]);
+        }
+
+        #[test]
+        fn single_line_in_middle() {
+            let mut t = Text::new(String::from("ABC\nDEF"));
+            assert_eq!(t.br_indexes.0, [0, 3]);
+            t.insert("Hello, World!", GridIndex { row: 0, col: 1 }, &mut ())
+                .unwrap();
+
+            assert_eq!(t.text, "AHello, World!BC\nDEF");
+            assert_eq!(t.br_indexes.0, [0, 16]);
+        }
+
+        #[test]
+        fn multi_byte() {
+            let mut t = Text::new("シュタインズ・ゲートは素晴らしいです。".into());
+            assert_eq!(t.br_indexes.0, [0]);
+            t.insert(
+                "\nHello, ゲートWorld!\n",
+                GridIndex { row: 0, col: 3 },
+                &mut (),
+            )
+            .unwrap();
+
+            assert_eq!(
+                t.text,
+                "シ\nHello, ゲートWorld!\nュタインズ・ゲートは素晴らしいです。"
+            );
+            assert_eq!(t.br_indexes, [0, 3, 26]);
+            assert_eq!(
+                &t.text[t.br_indexes.0[1] + 1..t.br_indexes.0[2]],
+                "Hello, ゲートWorld!"
+            );
+            assert_eq!(
+                &t.text[t.br_indexes.0[2] + 1..],
+                "ュタインズ・ゲートは素晴らしいです。"
+            )
+        }
+
+        #[test]
+        fn long_text_single_byte() {
+            let mut t = Text::new(
+                "1234567\nABCD\nHELLO\nWORLD\nSOMELONGLINEFORTESTINGVARIOUSCASES\nAHAHHAHAH".into(),
+            );
+
+            assert_eq!(t.br_indexes.0, [0, 7, 12, 18, 24, 59]);
+
+            t.insert(
+                "Apple Juice\nBananaMilkshake\nWobbly",
+                GridIndex { row: 4, col: 5 },
+                &mut (),
+            )
+            .unwrap();
+
+            assert_eq!(
+                t.text,
+                "1234567\nABCD\nHELLO\nWORLD\nSOMELApple Juice\nBananaMilkshake\nWobblyONGLINEFORTESTINGVARIOUSCASES\nAHAHHAHAH"
+            );
+            assert_eq!(t.br_indexes, [0, 7, 12, 18, 24, 41, 57, 93]);
+
+            assert_eq!(
+                &t.text[t.br_indexes.row_start(0).unwrap()..t.br_indexes.0[1]],
+                "1234567"
+            );
+            assert_eq!(
+                &t.text[t.br_indexes.row_start(1).unwrap()..t.br_indexes.0[2]],
+                "ABCD"
+            );
+            assert_eq!(
+                &t.text[t.br_indexes.row_start(2).unwrap()..t.br_indexes.0[3]],
+                "HELLO"
+            );
+            assert_eq!(
+                &t.text[t.br_indexes.row_start(3).unwrap()..t.br_indexes.0[4]],
+                "WORLD"
+            );
+            assert_eq!(
+                &t.text[t.br_indexes.row_start(4).unwrap()..t.br_indexes.0[5]],
+                "SOMELApple Juice"
+            );
+            assert_eq!(
+                &t.text[t.br_indexes.row_start(5).unwrap()..t.br_indexes.0[6]],
+                "BananaMilkshake"
+            );
+            assert_eq!(
+                &t.text[t.br_indexes.row_start(6).unwrap()..t.br_indexes.0[7]],
+                "WobblyONGLINEFORTESTINGVARIOUSCASES"
+            );
+            assert_eq!(&t.text[t.br_indexes.row_start(7).unwrap()..], "AHAHHAHAH");
+        }
+
+        #[test]
+        fn long_text_multi_byte() {
+            let mut t = Text::new(
+                "シュタ\nHello, ゲートWorld!\nインズ・ゲートは素晴らしいです。\nこんにちは世界！"
+                    .to_string(),
+            );
+
+            assert_eq!(t.br_indexes, [0, 9, 32, 81]);
+
+            t.insert(
+                "Olá, mundo!\nWaltuh Put the fork away Waltuh.",
+                GridIndex { row: 2, col: 3 },
+                &mut (),
+            )
+            .unwrap();
+
+            assert_eq!(
+                t.text,
+                "シュタ\nHello, ゲートWorld!\nイOlá, mundo!\nWaltuh Put the fork away Waltuh.ンズ・ゲートは素晴らしいです。\nこんにちは世界！"
+            );
+
+            assert_eq!(t.br_indexes, [0, 9, 32, 48, 126]);
+
+            assert_eq!(
+                &t.text[t.br_indexes.row_start(0).unwrap()..t.br_indexes.0[1]],
+                "シュタ"
+            );
+            assert_eq!(
+                &t.text[t.br_indexes.row_start(1).unwrap()..t.br_indexes.0[2]],
+                "Hello, ゲートWorld!"
+            );
+            assert_eq!(
+                &t.text[t.br_indexes.row_start(2).unwrap()..t.br_indexes.0[3]],
+                "イOlá, mundo!"
+            );
+            assert_eq!(
+                &t.text[t.br_indexes.row_start(3).unwrap()..t.br_indexes.0[4]],
+                "Waltuh Put the fork away Waltuh.ンズ・ゲートは素晴らしいです。"
+            );
+            assert_eq!(
+                &t.text[t.br_indexes.row_start(4).unwrap()..],
+                "こんにちは世界！"
+            );
+        }
+    }
+
+    mod split_off {
+        use super::*;
+        use crate::{
+            core::eol_indexes::EolIndexes,
+            updateables::{ChangeContext, UpdateContext},
+        };
+
+        const S: &str = "fn main() {\r\n    ゲート();\n}\n";
+
+        #[test]
+        fn round_trip() {
+            for at in [
+                GridIndex { row: 0, col: 0 },
+                GridIndex { row: 0, col: 11 },
+                GridIndex { row: 1, col: 6 },
+                GridIndex { row: 2, col: 1 },
+                GridIndex { row: 3, col: 0 },
+            ] {
+                let mut t = Text::new_utf16(S.into());
+                let tail = t.split_off(at, &mut ()).unwrap();
+                assert_eq!(t.br_indexes, EolIndexes::new(&t.text));
+                assert_eq!(tail.br_indexes, EolIndexes::new(&tail.text));
+                assert_eq!(format!("{t}{tail}"), S);
+
+                t.append_text(&tail, &mut ()).unwrap();
+                assert_eq!(t, Text::new_utf16(S.into()));
+            }
+        }
+
+        #[test]
+        fn split() {
+            let mut t = Text::new_utf16(S.into());
+            let tail = t.split_off(GridIndex { row: 1, col: 6 }, &mut ()).unwrap();
+            assert_eq!(t.text, "fn main() {\r\n    ゲー");
+            assert_eq!(t.br_indexes, [0, 12]);
+            assert_eq!(tail.text, "ト();\n}\n");
+            assert_eq!(tail.br_indexes, [0, 6, 8]);
+        }
+
+        #[test]
+        fn append() {
+            let mut t = Text::new("a\nb".into());
+            let mut ctx = None;
+            t.append_text(&Text::new("c\r\nd\n".into()), &mut |c: UpdateContext| {
+                ctx = Some(format!("{:?}", c.change));
+                Ok(())
+            })
+            .unwrap();
+            assert_eq!(t.text, "a\nbc\r\nd\n");
+            assert_eq!(t.br_indexes, [0, 1, 5, 7]);
+            assert_eq!(
+                ctx.unwrap(),
+                format!(
+                    "{:?}",
+                    ChangeContext::Insert {
+                        position: GridIndex { row: 1, col: 1 },
+                        text: "c\r\nd\n",
+                        inserted_br_indexes: &[5, 7]
+                    }
+                )
+            );
+        }
+    }
+
+    mod insert_char {
+        use rstest::rstest;
+
+        use super::*;
+        use crate::{core::eol_indexes::EolIndexes, updateables::UpdateContext};
+
+        #[rstest]
+        #[case('a', GridIndex { row: 0, col: 0 })]
+        #[case('ゲ', GridIndex { row: 1, col: 2 })]
+        #[case('\n', GridIndex { row: 0, col: 3 })]
+        #[case('\n', GridIndex { row: 1, col: 0 })]
+        #[case('\r', GridIndex { row: 3, col: 1 })]
+        #[case('😀', GridIndex { row: 3, col: 0 })]
+        #[case('\n', GridIndex { row: 4, col: 0 })]
+        fn matches_insert(#[case] c: char, #[case] at: GridIndex) {
+            const S: &str = "abc\r\nゲート\n\nde";
+            let mut t = Text::new_utf16(S.into());
+            let mut ctx = None;
+            t.insert_char(c, at, &mut |c: UpdateContext| {
+                ctx = Some(format!("{c:?}"));
+                Ok(())
+            })
+            .unwrap();
+
+            let mut expected = Text::new_utf16(S.into());
+            let mut expected_ctx = None;
+            expected
+                .insert(&c.to_string(), at, &mut |c: UpdateContext| {
+                    expected_ctx = Some(format!("{c:?}"));
+                    Ok(())
+                })
+                .unwrap();
+
+            assert_eq!(t, expected);
+            assert_eq!(t.br_indexes, EolIndexes::new(&t.text));
+            assert_eq!(ctx, expected_ctx);
+            assert_eq!(
+                t.dirty_rows().collect::<Vec<_>>(),
+                expected.dirty_rows().collect::<Vec<_>>()
+            );
+        }
+    }
+
+    mod replace {
+        use super::*;
+
+        #[test]
+        fn in_line_start() {
+            let mut t = Text::new("Hello, World!\nBye World!\nhahaFunny".into());
+
+            assert_eq!(t.br_indexes, [0, 13, 24]);
+
+            t.replace(
+                "This Should replace some stuff",
+                GridIndex { row: 0, col: 3 },
+                GridIndex { row: 0, col: 5 },
+                &mut (),
+            )
+            .unwrap();
+
+            assert_eq!(
+                t.text,
+                "HelThis Should replace some stuff, World!\nBye World!\nhahaFunny"
+            );
+            assert_eq!(t.br_indexes, [0, 41, 52]);
+        }
+
+        #[test]
+        fn in_line_middle() {
+            let mut t = Text::new("Hello, World!\nBye World!\nhahaFunny".into());
+
+            assert_eq!(t.br_indexes, [0, 13, 24]);
+
+            t.replace(
+                "This Should replace some stuff",
+                GridIndex { row: 1, col: 3 },
+                GridIndex { row: 1, col: 5 },
+                &mut (),
+            )
+            .unwrap();
+
+            assert_eq!(
+                t.text,
+                "Hello, World!\nByeThis Should replace some stufforld!\nhahaFunny"
+            );
+            assert_eq!(t.br_indexes, [0, 13, 52]);
+        }
+
+        #[test]
+        fn in_line_end() {
+            let mut t = Text::new("Hello, World!\nBye World!\nhahaFunny".into());
+
+            assert_eq!(t.br_indexes, [0, 13, 24]);
+            t.replace(
+                "Wappow! There he stood.",
+                GridIndex { row: 0, col: 4 },
+                GridIndex { row: 0, col: 13 },
+                &mut (),
+            )
+            .unwrap();
+
+            assert_eq!(t.text, "HellWappow! There he stood.\nBye World!\nhahaFunny");
+            assert_eq!(t.br_indexes, [0, 27, 38]);
+        }
+
+        #[test]
+        fn across_first_line() {
+            let mut t = Text::new("Hello, World!\nBye World!\nhahaFunny".into());
+
+            assert_eq!(t.br_indexes, [0, 13, 24]);
+            t.replace(
+                "This replaced with the content in the first line\n and second line",
+                GridIndex { row: 0, col: 5 },
+                GridIndex { row: 1, col: 3 },
+                &mut (),
+            )
+            .unwrap();
+
+            assert_eq!(t.text, "HelloThis replaced with the content in the first line\n and second line World!\nhahaFunny");
+            assert_eq!(t.br_indexes, [0, 53, 77]);
+        }
+
+        #[test]
+        fn across_start_and_end_line() {
+            let mut t = Text::new("Hello, World!\nBye World!\nhahaFunny\nInteresting!".into());
+
+            assert_eq!(t.br_indexes, [0, 13, 24, 34]);
+            t.replace(
+                "What a wonderful world!\nWowzers\nSome Random text",
+                GridIndex { row: 0, col: 3 },
+                GridIndex { row: 3, col: 6 },
+                &mut (),
+            )
+            .unwrap();
+
+            assert_eq!(
+                t.text,
+                "HelWhat a wonderful world!\nWowzers\nSome Random textsting!"
+            );
+
+            assert_eq!(t.br_indexes, [0, 26, 34]);
+        }
+
+        #[test]
+        fn across_end_line() {
+            let mut t = Text::new("Hello, World!\nBye World!\nhahaFunny\nInteresting!".into());
+
+            assert_eq!(t.br_indexes, [0, 13, 24, 34]);
+            t.replace(
+                "What a wonderful world!\nWowzers\nSome Random text",
+                GridIndex { row: 2, col: 3 },
+                GridIndex { row: 3, col: 6 },
+                &mut (),
+            )
+            .unwrap();
+
+            assert_eq!(
+                t.text,
+                "Hello, World!\nBye World!\nhahWhat a wonderful world!\nWowzers\nSome Random textsting!"
+            );
+
+            assert_eq!(t.br_indexes, [0, 13, 24, 51, 59]);
+        }
+
+        #[test]
+        fn middle_in_line() {
+            let mut t = Text::new("Hello, World!\nBye World!\nhahaFunny\nInteresting!".into());
+
+            assert_eq!(t.br_indexes, [0, 13, 24, 34]);
+            t.replace(
+                "I am in the middle!\nNo one can stop me.",
+                GridIndex { row: 2, col: 1 },
+                GridIndex { row: 2, col: 5 },
+                &mut (),
+            )
+            .unwrap();
+
+            assert_eq!(t.text, "Hello, World!\nBye World!\nhI am in the middle!\nNo one can stop me.unny\nInteresting!");
+            assert_eq!(t.br_indexes, [0, 13, 24, 45, 69]);
+        }
+
+        #[test]
+        fn middle_no_br_replacement() {
+            let mut t = Text::new("Hello, World!\nBye World!\nhahaFunny\nInteresting!".into());
+
+            assert_eq!(t.br_indexes, [0, 13, 24, 34]);
+            t.replace(
+                "Look ma, no line breaks",
+                GridIndex { row: 1, col: 3 },
+                GridIndex { row: 1, col: 6 },
+                &mut (),
+            )
+            .unwrap();
+
+            assert_eq!(
+                t.text,
+                "Hello, World!\nByeLook ma, no line breaksrld!\nhahaFunny\nInteresting!"
+            );
+            assert_eq!(t.br_indexes, [0, 13, 44, 54]);
+        }
+
+        #[test]
+        fn start_no_br_replacement() {
+            let mut t = Text::new("Hello, World!\nBye World!\nhahaFunny\nInteresting!".into());
+
+            assert_eq!(t.br_indexes, [0, 13, 24, 34]);
+            t.replace(
+                "Look ma, no line breaks",
+                GridIndex { row: 0, col: 3 },
+                GridIndex { row: 0, col: 8 },
+                &mut (),
+            )
+            .unwrap();
+
+            assert_eq!(
+                t.text,
+                "HelLook ma, no line breaksorld!\nBye World!\nhahaFunny\nInteresting!"
+            );
+            assert_eq!(t.br_indexes, [0, 31, 42, 52]);
+        }
+
+        #[test]
+        fn end_no_br_replacement() {
+            let mut t = Text::new("Hello, World!\nBye World!\nhahaFunny\nInteresting!".into());
+
+            assert_eq!(t.br_indexes, [0, 13, 24, 34]);
+            t.replace(
+                "Look ma, no line breaks",
+                GridIndex { row: 3, col: 3 },
+                GridIndex { row: 3, col: 8 },
+                &mut (),
+            )
+            .unwrap();
+
+            assert_eq!(
+                t.text,
+                "Hello, World!\nBye World!\nhahaFunny\nIntLook ma, no line breaksing!"
+            );
+            assert_eq!(t.br_indexes, [0, 13, 24, 34]);
+        }
+
+        #[test]
+        fn across_start_and_end_no_br_replacement() {
+            let mut t = Text::new("Hello, World!\nBye World!\nhahaFunny\nInteresting!".into());
+
+            assert_eq!(t.br_indexes, [0, 13, 24, 34]);
+            t.replace(
+                "Look ma, no line breaks",
+                GridIndex { row: 0, col: 3 },
+                GridIndex { row: 3, col: 8 },
+                &mut (),
+            )
+            .unwrap();
+
+            assert_eq!(t.text, "HelLook ma, no line breaksing!");
+            assert_eq!(t.br_indexes, [0]);
+        }
+        #[test]
+        fn all() {
+            let mut t =
+                Text::new("SomeText\nSome Other Text\nSome somsoemesome\n wowoas \n\n".into());
+
+            assert_eq!(t.br_indexes, [0, 8, 24, 42, 51, 52]);
+            t.replace(
+                "Hello, World!\nBye World!",
+                GridIndex { row: 0, col: 0 },
+                GridIndex { row: 6, col: 0 },
+                &mut (),
+            )
+            .unwrap();
+
+            assert_eq!(t.text, "Hello, World!\nBye World!");
+            assert_eq!(t.br_indexes, [0, 13]);
+        }
+    }
+
+    mod rebuild_indexes {
+        use super::*;
+
+        #[test]
+        fn after_manual_edit() {
+            let mut t = Text::new_utf16("Hello\nWorld".into());
+            t.clear_dirty();
+            t.text.insert_str(5, ",\r\nゲート");
+            t.text.push_str("\n!");
+            t.rebuild_indexes();
+            assert!(t.verify_invariants());
+            assert_eq!(t.br_indexes, [0, 7, 17, 23]);
+            assert_eq!(t.dirty_rows().collect::<Vec<_>>(), [0, 1, 2, 3]);
+
+            t.insert("ア", GridIndex { row: 1, col: 1 }, &mut ())
+                .unwrap();
+            t.delete(
+                GridIndex { row: 2, col: 5 },
+                GridIndex { row: 3, col: 0 },
+                &mut (),
+            )
+            .unwrap();
+            assert_eq!(t.text, "Hello,\r\nゲアート\nWorld!");
+            assert!(t.verify_invariants());
+        }
+    }
+
+    mod edit_raw {
+        use super::*;
+
+        #[test]
+        fn uppercase() {
+            let mut t = Text::new_utf16("straße\r\nゲート\nworld".into());
+            {
+                let mut s = t.edit_raw();
+                let upper = s.to_uppercase();
+                *s = upper;
+            }
+            assert_eq!(t.text, "STRASSE\r\nゲート\nWORLD");
+            assert_eq!(t.br_indexes, [0, 8, 18]);
+            assert!(t.verify_invariants());
+        }
+
+        #[test]
+        fn modify_lines() {
+            let mut t = Text::new("hello\nworld".into());
+            {
+                let mut s = t.edit_raw();
+                s.insert_str(0, "a\r\n");
+                s.push_str("\n\n");
+            }
+            assert_eq!(t.br_indexes, [0, 2, 8, 14, 15]);
+            assert!(t.verify_invariants());
+            t.insert("!", GridIndex { row: 3, col: 0 }, &mut ())
+                .unwrap();
+            assert_eq!(t.text, "a\r\nhello\nworld\n!\n");
+        }
+    }
+
+    mod verify_invariants {
+        use super::*;
+
+        #[test]
+        fn in_sync() {
+            let mut t = Text::new_utf16("Hello\r\nゲート\n🦀\rWorld".into());
+            assert!(t.verify_invariants());
+            t.insert("a\nb", GridIndex { row: 2, col: 2 }, &mut ())
+                .unwrap();
+            assert!(t.verify_invariants());
+            assert!(Text::new(String::new()).verify_invariants());
+        }
+
+        #[test]
+        fn corrupted() {
+            let mut t = Text::new("Hello\nWorld".into());
+            t.br_indexes.0[1] = 4;
+            assert!(!t.verify_invariants());
+
+            let mut t = Text::new("Hello\nWorld".into());
+            t.text.push('\n');
+            assert!(!t.verify_invariants());
+
+            let mut t = Text::new("Hello\nWorld".into());
+            t.br_indexes.0.pop();
+            assert!(!t.verify_invariants());
+        }
+    }
+
+    mod paragraphs {
+        use super::*;
+
+        #[test]
+        fn paragraphs() {
+            let t = Text::new(
+                "\n\r\nFirst line\nsecond line\n\n\n\r\n  \n\tThird\r\n\nLast\r\n\n".into(),
+            );
+            assert_eq!(
+                t.paragraphs().collect::<Vec<_>>(),
+                ["First line\nsecond line", "  \n\tThird", "Last"]
+            );
+        }
+
+        #[test]
+        fn single() {
+            let t = Text::new("a\r\nb".into());
+            assert_eq!(t.paragraphs().collect::<Vec<_>>(), ["a\r\nb"]);
+        }
+
+        #[test]
+        fn empty() {
+            assert_eq!(Text::new(String::new()).paragraphs().count(), 0);
+            assert_eq!(Text::new("\n\r\n\r".into()).paragraphs().count(), 0);
+        }
+    }
+
+    mod row_byte_range {
+        use rstest::rstest;
+
+        use super::*;
+
+        #[rstest]
+        #[case("Hello\nゲート\n\nWorld")]
+        #[case("Hello\r\nゲート\r\n\r\nWorld\r\n")]
+        #[case("Hello\rゲート\r\n\nWorld\r")]
+        fn matches_row_start(#[case] s: &str) {
+            let t = Text::new(s.into());
+            for row in 0..t.line_count() {
+                let start = t.br_indexes.row_start(row).unwrap();
+                let line = t.get_row(row).unwrap();
+                assert_eq!(t.row_byte_range(row), Some(start..start + line.len()));
+
+                let end = t.br_indexes.row_start(row + 1).unwrap_or(s.len());
+                assert_eq!(t.row_byte_range_with_eol(row), Some(start..end));
+            }
+
+            assert_eq!(t.row_byte_range(t.line_count()), None);
+            assert_eq!(t.row_byte_range_with_eol(t.line_count()), None);
+        }
+
+        #[test]
+        fn crlf() {
+            let t = Text::new("ab\r\nゲ\r\n".into());
+            assert_eq!(t.row_byte_range(0), Some(0..2));
+            assert_eq!(t.row_byte_range_with_eol(0), Some(0..4));
+            assert_eq!(t.row_byte_range(1), Some(4..7));
+            assert_eq!(t.row_byte_range_with_eol(1), Some(4..9));
+            assert_eq!(t.row_byte_range(2), Some(9..9));
+            assert_eq!(t.row_byte_range_with_eol(2), Some(9..9));
+        }
+    }
+
+    mod first_last_line {
+        use super::*;
+
+        #[test]
+        fn multiple_lines() {
+            let t = Text::new("ゲート\r\nmiddle\nlast".into());
+            assert_eq!(t.first_line(), "ゲート");
+            assert_eq!(t.last_line(), "last");
+
+            let t = Text::new("first\r\nmiddle\r\n".into());
+            assert_eq!(t.first_line(), "first");
+            assert_eq!(t.last_line(), "");
+        }
+
+        #[test]
+        fn single_line() {
+            let t = Text::new("only".into());
+            assert_eq!(t.first_line(), "only");
+            assert_eq!(t.last_line(), "only");
+        }
+
+        #[test]
+        fn empty() {
+            let t = Text::new(String::new());
+            assert_eq!(t.first_line(), "");
+            assert_eq!(t.last_line(), "");
+        }
+    }
+
+    mod line_at_byte {
+        use rstest::rstest;
+
+        use super::*;
+
+        const S: &str = "Hello\r\nゲート\n\r\rWorld";
+
+        #[rstest]
+        #[case(0, Some((0, "Hello")))]
+        #[case(4, Some((0, "Hello")))]
+        #[case(5, Some((0, "Hello")))]
+        #[case(6, Some((0, "Hello")))]
+        #[case(7, Some((1, "ゲート")))]
+        #[case(9, Some((1, "ゲート")))]
+        #[case(16, Some((1, "ゲート")))]
+        #[case(17, Some((2, "")))]
+        #[case(18, Some((3, "")))]
+        #[case(19, Some((4, "World")))]
+        #[case(24, Some((4, "World")))]
+        #[case(25, None)]
+        fn line_at_byte(#[case] byte: usize, #[case] expected: Option<(usize, &str)>) {
+            let t = Text::new(S.into());
+            assert_eq!(t.line_at_byte(byte), expected);
+        }
+
+        #[test]
+        fn empty() {
+            let t = Text::new(String::new());
+            assert_eq!(t.line_at_byte(0), Some((0, "")));
+            assert_eq!(t.line_at_byte(1), None);
+        }
+    }
+
+    mod retain {
+        use super::*;
+        use crate::updateables::UpdateContext;
+
+        #[test]
+        fn retain() {
+            let mut t = Text::new("a\u{7}b\r\nゲ\u{1b}ート\n\u{0}\nc".into());
+            let mut calls = 0;
+            t.retain(
+                |c| c == '\n' || c == '\r' || !c.is_control(),
+                &mut |_: UpdateContext| {
+                    calls += 1;
+                    Ok(())
+                },
+            )
+            .unwrap();
+            assert_eq!(calls, 1);
+            assert_eq!(t.text, "ab\r\nゲート\n\nc");
+            assert_eq!(t.br_indexes, [0, 3, 13, 14]);
+
+            t.retain(|c| c.is_ascii(), &mut ()).unwrap();
+            assert_eq!(t.text, "ab\r\n\n\nc");
+            assert_eq!(t.br_indexes, [0, 3, 4, 5]);
+
+            t.retain(|c| c != '\n', &mut ()).unwrap();
+            assert_eq!(t.text, "ab\rc");
+            assert_eq!(t.br_indexes, [0, 2]);
+        }
+
+        #[test]
+        fn unchanged() {
+            let mut t = Text::new("Hello\nWorld".into());
+            t.retain(|_| true, &mut |_: UpdateContext| {
+                panic!("nothing should be updated")
+            })
+            .unwrap();
+            assert_eq!(t.text, "Hello\nWorld");
+        }
+    }
+
+    mod insert_multi {
+        use super::*;
+        use crate::updateables::{ChangeContext, UpdateContext};
+
+        #[test]
+        fn three_positions() {
+            let mut t = Text::new_utf16("ゲート\nab\r\ncd".into());
+            let mut positions = vec![];
+            let mut record = |ctx: UpdateContext| {
+                let ChangeContext::Insert { position, .. } = ctx.change else {
+                    panic!("expected an insertion");
+                };
+                // the context is in UTF-8 and relative to the content before the insertion
+                positions.push((position, ctx.old_str.to_string()));
+                Ok(())
+            };
+            t.insert_multi(
+                "X",
+                &[
+                    GridIndex { row: 0, col: 1 },
+                    GridIndex { row: 2, col: 2 },
+                    GridIndex { row: 1, col: 0 },
+                ],
+                &mut record,
+            )
+            .unwrap();
+            assert_eq!(t.text, "ゲXート\nXab\r\ncdX");
+            assert_eq!(t.br_indexes, [0, 10, 15]);
+            assert_eq!(
+                positions,
+                [
+                    (GridIndex { row: 2, col: 2 }, "ゲート\nab\r\ncd".into()),
+                    (GridIndex { row: 1, col: 0 }, "ゲート\nab\r\ncdX".into()),
+                    (GridIndex { row: 0, col: 3 }, "ゲート\nXab\r\ncdX".into()),
+                ]
+            );
+        }
+
+        #[test]
+        fn line_breaks() {
+            let mut t = Text::new("ab\ncd".into());
+            t.insert_multi(
+                "\r\n",
+                &[GridIndex { row: 0, col: 1 }, GridIndex { row: 1, col: 1 }],
+                &mut (),
+            )
+            .unwrap();
+            assert_eq!(t.text, "a\r\nb\nc\r\nd");
+            assert_eq!(t.br_indexes, [0, 2, 4, 7]);
+        }
+    }
+
+    mod delete_ranges {
+        use super::*;
+        use crate::error::Error;
+
+        #[test]
+        fn non_overlapping() {
+            let mut t = Text::new_utf16("ゲート\nab\r\ncd\nef".into());
+            t.delete_ranges(
+                &[
+                    (GridIndex { row: 2, col: 1 }, GridIndex { row: 3, col: 1 }),
+                    (GridIndex { row: 0, col: 1 }, GridIndex { row: 0, col: 2 }),
+                    // reversed, and touching the first range
+                    (GridIndex { row: 2, col: 1 }, GridIndex { row: 1, col: 1 }),
+                ],
+                &mut (),
+            )
+            .unwrap();
+            assert_eq!(t.text, "ゲト\naf");
+            assert_eq!(t.br_indexes, [0, 6]);
+        }
+
+        #[test]
+        fn overlapping() {
+            let mut t = Text::new("abc\ndef".into());
+            let err = t
+                .delete_ranges(
+                    &[
+                        (GridIndex { row: 1, col: 0 }, GridIndex { row: 1, col: 2 }),
+                        (GridIndex { row: 0, col: 1 }, GridIndex { row: 1, col: 1 }),
+                    ],
+                    &mut (),
+                )
+                .unwrap_err();
+            assert_eq!(
+                err,
+                Error::OverlappingRanges {
+                    first: (GridIndex { row: 0, col: 1 }, GridIndex { row: 1, col: 1 }),
+                    second: (GridIndex { row: 1, col: 0 }, GridIndex { row: 1, col: 2 }),
+                }
+            );
+            assert_eq!(
+                err.to_string(),
+                "Range 0:1..1:1 overlaps with range 1:0..1:2."
+            );
+            // nothing is deleted
+            assert_eq!(t.text, "abc\ndef");
+            assert_eq!(t.br_indexes, [0, 3]);
+        }
+    }
+
+    mod byte_edits {
+        use super::*;
+        use crate::{
+            error::Error,
+            updateables::{ChangeContext, UpdateContext},
+        };
+
+        const S: &str = "ゲート\r\nab\ncd";
+
+        #[test]
+        fn insert_at_byte() {
+            let mut t = Text::new_utf16(S.into());
+            t.insert_at_byte("X", 3, &mut ()).unwrap();
+            assert_eq!(t.text, "ゲXート\r\nab\ncd");
+            t.insert_at_byte("1\n", 15, &mut ()).unwrap();
+            assert_eq!(t.text, "ゲXート\r\nab\n1\ncd");
+            assert_eq!(t.br_indexes, [0, 11, 14, 16]);
+            // the end of the string
+            t.insert_at_byte("!", t.text.len(), &mut ()).unwrap();
+            assert_eq!(t.text, "ゲXート\r\nab\n1\ncd!");
+            assert!(t.verify_invariants());
+        }
+
+        #[test]
+        fn delete_byte_range() {
+            let mut t = Text::new_utf16(S.into());
+            t.delete_byte_range(3..6, &mut ()).unwrap();
+            assert_eq!(t.text, "ゲト\r\nab\ncd");
+            // across line breaks
+            t.delete_byte_range(6..11, &mut ()).unwrap();
+            assert_eq!(t.text, "ゲトcd");
+            assert_eq!(t.br_indexes, [0]);
+        }
+
+        #[test]
+        fn invalid() {
+            let mut t = Text::new(S.into());
+            let err = t.insert_at_byte("X", 1, &mut ()).unwrap_err();
+            assert_eq!(err, Error::InvalidByteOffset { byte: 1, len: 16 });
+            assert_eq!(
+                err.to_string(),
+                "Provided byte offset 1 is not a valid position in a string with length 16."
+            );
+            // in between "\r\n"
+            assert_eq!(
+                t.insert_at_byte("X", 10, &mut ()),
+                Err(Error::InvalidByteOffset { byte: 10, len: 16 })
+            );
+            assert_eq!(
+                t.delete_byte_range(0..17, &mut ()),
+                Err(Error::InvalidByteOffset { byte: 17, len: 16 })
+            );
+            assert_eq!(
+                t.delete_byte_range(4..9, &mut ()),
+                Err(Error::InvalidByteOffset { byte: 4, len: 16 })
+            );
+            assert_eq!(t.text, S);
+            assert_eq!(t.br_indexes, [0, 10, 13]);
+        }
+
+        #[test]
+        fn updateable() {
+            let mut t = Text::new_utf16(S.into());
+            let mut positions = None;
+            t.delete_byte_range(9..13, &mut |ctx: UpdateContext| {
+                let ChangeContext::Delete { start, end } = ctx.change else {
+                    panic!("expected a deletion");
+                };
+                positions = Some((start, end));
+                Ok(())
+            })
+            .unwrap();
+            // the positions are UTF-8 columns
+            assert_eq!(
+                positions,
+                Some((GridIndex { row: 0, col: 9 }, GridIndex { row: 1, col: 2 }))
+            );
+            assert_eq!(t.text, "ゲート\ncd");
+        }
+    }
+
+    mod update_many {
+        use super::*;
+        use crate::{change::Change, error::Error};
+
+        #[test]
+        fn mixed() {
+            let mut t = Text::new_utf16("Hello\nゲート\nWorld".into());
+            t.update_many(
+                [
+                    Change::Insert {
+                        at: GridIndex { row: 0, col: 5 },
+                        text: ",".into(),
+                    },
+                    Change::Delete {
+                        start: GridIndex { row: 1, col: 0 },
+                        end: GridIndex { row: 2, col: 0 },
+                    },
+                    Change::Replace {
+                        start: GridIndex { row: 1, col: 0 },
+                        end: GridIndex { row: 1, col: 1 },
+                        text: "w".into(),
+                    },
+                ],
+                &mut (),
+            )
+            .unwrap();
+            assert_eq!(t.text, "Hello,\nworld");
+            assert_eq!(t.br_indexes, [0, 6]);
+        }
+
+        #[test]
+        fn stops_on_error() {
+            let mut t = Text::new("Hello".into());
+            let err = t
+                .update_many(
+                    [
+                        Change::Insert {
+                            at: GridIndex { row: 0, col: 0 },
+                            text: "a".into(),
+                        },
+                        Change::Insert {
+                            at: GridIndex { row: 5, col: 0 },
+                            text: "b".into(),
+                        },
+                        Change::Insert {
+                            at: GridIndex { row: 0, col: 0 },
+                            text: "c".into(),
+                        },
+                    ],
+                    &mut (),
+                )
+                .unwrap_err();
+            assert!(matches!(err, Error::OutOfBoundsRow { .. }));
+            assert_eq!(t.text, "aHello");
+        }
+    }
+
+    #[cfg(any(feature = "tree-sitter", feature = "lsp-types"))]
+    mod into_grid_index {
+        use super::*;
+
+        #[cfg(feature = "tree-sitter")]
+        #[test]
+        fn point() {
+            use tree_sitter::Point;
+
+            let mut t = Text::new("Hello\nWorld".into());
+            t.insert("!", Point { row: 0, column: 5 }, &mut ()).unwrap();
+            t.insert_char('?', Point { row: 1, column: 5 }, &mut ())
+                .unwrap();
+            t.delete(
+                Point { row: 0, column: 0 },
+                Point { row: 0, column: 1 },
+                &mut (),
+            )
+            .unwrap();
+            t.replace(
+                "w",
+                Point { row: 1, column: 0 },
+                Point { row: 1, column: 1 },
+                &mut (),
+            )
+            .unwrap();
+            assert_eq!(t.text, "ello!\nworld?");
+            assert_eq!(t.br_indexes, [0, 5]);
+        }
+
+        #[cfg(feature = "lsp-types")]
+        #[test]
+        fn position() {
+            use lsp_types::Position;
+
+            let mut t = Text::new_utf16("Hello\nゲート".into());
+            t.insert("!", Position::new(0, 5), &mut ()).unwrap();
+            t.insert_char('?', Position::new(1, 3), &mut ()).unwrap();
+            t.delete(Position::new(0, 0), Position::new(0, 1), &mut ())
+                .unwrap();
+            t.replace("ケ", Position::new(1, 0), Position::new(1, 1), &mut ())
+                .unwrap();
+            assert_eq!(t.text, "ello!\nケート?");
+            assert_eq!(t.br_indexes, [0, 5]);
+        }
+    }
+
+    mod encoding {
+        use super::*;
+        use crate::error::Encoding;
+
+        #[test]
+        fn constructors() {
+            assert_eq!(Text::new(String::new()).encoding(), Encoding::UTF8);
+            assert_eq!(Text::new_utf16(String::new()).encoding(), Encoding::UTF16);
+            assert_eq!(Text::new_utf32(String::new()).encoding(), Encoding::UTF32);
+            #[cfg(feature = "unicode-segmentation")]
+            assert_eq!(
+                Text::new_graphemes(String::new()).encoding(),
+                Encoding::Graphemes
+            );
+            assert_eq!(
+                Text::from_utf8_lossy(b"a", Encoding::UTF32).encoding(),
+                Encoding::UTF32
+            );
+        }
+
+        #[test]
+        fn with_encoding() {
+            let t = Text::new_utf16("ゲート😀\nab".into());
+            assert_eq!(t.line_lengths().collect::<Vec<_>>(), [5, 2]);
+            let mut t = t.with_encoding(Encoding::UTF8);
+            assert_eq!(t.encoding(), Encoding::UTF8);
+            assert_eq!(t.text, "ゲート😀\nab");
+            assert_eq!(t.br_indexes, [0, 13]);
+            assert_eq!(t.line_lengths().collect::<Vec<_>>(), [13, 2]);
+
+            // column 2 is now a byte position, which is inside of the first character
+            assert!(t
+                .insert("x", GridIndex { row: 0, col: 2 }, &mut ())
+                .is_err());
+            t.insert("x", GridIndex { row: 0, col: 3 }, &mut ())
+                .unwrap();
+            assert_eq!(t.text, "ゲxート😀\nab");
+        }
+    }
+
+    mod from_lines {
+        use super::*;
+
+        #[test]
+        fn from_lines() {
+            let lines = vec!["fn main() {", "    ゲート();", "", "}"];
+            let t = Text::from_lines(&lines);
+            assert_eq!(t.text, "fn main() {\n    ゲート();\n\n}");
+            assert_eq!(t.br_indexes, [0, 11, 28, 29]);
+            assert_eq!(t.lines().collect::<Vec<_>>(), lines);
+
+            let t = Text::from_lines(["a", ""].map(String::from));
+            assert_eq!(t.text, "a\n");
+            assert_eq!(t.br_indexes, [0, 1]);
+        }
+
+        #[test]
+        fn empty() {
+            let t = Text::from_lines(Vec::<&str>::new());
+            assert_eq!(t, Text::new(String::new()));
+        }
+    }
+
+    mod from_utf8 {
+        use crate::error::Encoding;
+
+        use super::*;
+
+        #[test]
+        fn valid() {
+            let bytes = "Hello, World!\r\nシュタインズ\nゲート".as_bytes();
+            let t = Text::from_utf8(bytes, Encoding::UTF8).unwrap();
+            assert_eq!(t, Text::new("Hello, World!\r\nシュタインズ\nゲート".into()));
+            assert_eq!(t.br_indexes, [0, 14, 33]);
+
+            let t = Text::from_utf8_lossy(bytes, Encoding::UTF16);
+            assert_eq!(
+                t,
+                Text::new_utf16("Hello, World!\r\nシュタインズ\nゲート".into())
+            );
+        }
+
+        #[test]
+        fn invalid() {
+            let bytes = b"Hello\xFF\nWorld\xE3\x82!";
+            assert!(Text::from_utf8(bytes, Encoding::UTF8).is_err());
+
+            let t = Text::from_utf8_lossy(bytes, Encoding::UTF8);
+            assert_eq!(t.text, "Hello\u{FFFD}\nWorld\u{FFFD}!");
+            assert_eq!(t.br_indexes, [0, 8]);
+            assert_eq!(t.get_row(1), Some("World\u{FFFD}!"));
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    mod serde {
+        use super::*;
+
+        const S: &str = "シュタインズ\n\r\nHello, 😀\r\n\nゲート";
+
+        fn round_trip(t: Text) {
+            let s = serde_json::to_string(&t).unwrap();
+            let de: Text = serde_json::from_str(&s).unwrap();
+            assert_eq!(de.br_indexes, t.br_indexes);
+            assert_eq!(
+                de.line_lengths().collect::<Vec<_>>(),
+                t.line_lengths().collect::<Vec<_>>()
+            );
+            assert_eq!(de, t);
+        }
+
+        #[test]
+        fn utf8() {
+            round_trip(Text::new(S.into()));
+        }
+
+        #[test]
+        fn utf16() {
+            round_trip(Text::new_utf16(S.into()));
+        }
+
+        #[test]
+        fn utf32() {
+            round_trip(Text::new_utf32(S.into()));
+        }
+
+        #[test]
+        fn encoding_is_restored() {
+            let s = serde_json::to_string(&Text::new_utf16(S.into())).unwrap();
+            let de: Text = serde_json::from_str(&s).unwrap();
+            assert_ne!(de, Text::new(S.into()));
+            assert_eq!(de.line_lengths().collect::<Vec<_>>(), [6, 0, 9, 0, 3]);
+        }
+
+        #[test]
+        fn bom_and_eol_mode() {
+            round_trip(Text::new_with_bom_handling(format!("\u{FEFF}{S}")));
+            round_trip(Text::new_unicode_eol(format!("{S}\u{2028}")));
+        }
+    }
+
+    mod as_str {
+        use super::*;
+
+        fn len(s: &str) -> usize {
+            s.len()
+        }
+
+        #[test]
+        fn as_str() {
+            let t = Text::new("Hello\nゲート".into());
+            assert_eq!(t.as_str(), "Hello\nゲート");
+            assert_eq!(len(t.as_str()), 15);
+            assert_eq!(len(t.as_ref()), 15);
+        }
+    }
+
+    mod hash {
+        use std::collections::HashSet;
+
+        use super::*;
+
+        #[test]
+        fn lookup() {
+            let mut set = HashSet::new();
+            assert!(set.insert(Text::new("Hello\nWorld".into())));
+            assert!(set.insert(Text::new_utf16("Hello\nWorld".into())));
+            assert!(!set.insert(Text::new("Hello\nWorld".into())));
+            assert!(set.contains(&Text::new("Hello\nWorld".into())));
+            assert!(!set.contains(&Text::new("Hello".into())));
+            assert_eq!(set.len(), 2);
+        }
+
+        #[test]
+        fn consistent_with_eq() {
+            use std::hash::{BuildHasher, RandomState};
+
+            let state = RandomState::new();
+            let a = Text::new_utf16("Hello\r\nWorld".into());
+            let b = Text::new_utf16("Hello\r\nWorld".into());
+            assert_eq!(a, b);
+            assert_eq!(state.hash_one(&a), state.hash_one(&b));
+            let utf8 = Text::new("Hello\r\nWorld".into());
+            assert_ne!(a, utf8);
+            assert_ne!(state.hash_one(&a), state.hash_one(&utf8));
+        }
+    }
+
+    mod reserve {
+        use super::*;
+
+        #[test]
+        fn reserve() {
+            let mut t = Text::new("Hello\nWorld".into());
+            let expected = t.clone();
+            t.reserve(1024);
+            assert!(t.capacity() >= 11 + 1024);
+            assert!(t.br_indexes.0.capacity() >= 2 + 32);
+            assert_eq!(t, expected);
+
+            let mut t = Text::new("a\nb\nc\nd".repeat(256));
+            t.reserve(1024);
+            assert!(t.br_indexes.0.capacity() >= t.line_count() + 32);
+
+            let mut t = Text::new(String::new());
+            t.reserve(1024);
+            assert!(t.capacity() >= 1024);
+            assert!(t.br_indexes.0.capacity() < 32);
+        }
+    }
+
+    mod heap_size {
+        use super::*;
+
+        #[test]
+        fn heap_size() {
+            let mut t = Text::new("Hello\nWorld".into());
+            assert!(t.heap_size() >= t.text.len() + 2 * size_of::<usize>());
+
+            let before = t.heap_size();
+            t.insert(
+                &"ゲート\n".repeat(64),
+                GridIndex { row: 1, col: 0 },
+                &mut (),
+            )
+            .unwrap();
+            assert!(t.heap_size() > before);
+            assert!(t.heap_size() >= t.text.len());
+        }
+    }
+
+    mod line_count {
+        use super::*;
+
+        #[test]
+        fn line_count() {
+            assert_eq!(Text::new(String::new()).line_count(), 1);
+            assert_eq!(Text::new("\n".into()).line_count(), 2);
+            assert_eq!(Text::new("a\r\nb\nc".into()).line_count(), 3);
+        }
+    }
+
+    mod get_row {
+        use super::*;
+
+        #[test]
+        fn matches_lines() {
+            for s in [
+                "シュタインズ\n\r\nHello, 😀\r\n\nゲート",
+                "\n\n",
+                "\r\n",
+                "",
+                "abc\r\ndef\r",
+            ] {
+                let t = Text::new(s.into());
+                for row in 0..t.br_indexes.row_count().get() + 2 {
+                    assert_eq!(t.get_row(row), t.lines().nth(row));
+                }
+            }
+        }
+    }
+
+    mod row_affixes {
+        use super::*;
+
+        const S: &str = "- ゲート😀\r\n* item;\n\nシュタインズ";
+
+        #[test]
+        fn starts_with() {
+            let t = Text::new(S.into());
+            assert!(t.row_starts_with(0, "- "));
+            assert!(t.row_starts_with(0, "- ゲ"));
+            assert!(t.row_starts_with(1, "* "));
+            assert!(!t.row_starts_with(1, "- "));
+            assert!(t.row_starts_with(2, ""));
+            assert!(!t.row_starts_with(2, "\n"));
+            assert!(t.row_starts_with(3, "シュ"));
+            // "ゲ" and "ケ" only differ in their last byte
+            assert!(!t.row_starts_with(0, "- ケ"));
+            assert!(!t.row_ends_with(3, "ス"));
+            assert!(!t.row_starts_with(4, ""));
+        }
+
+        #[test]
+        fn ends_with() {
+            let t = Text::new(S.into());
+            assert!(t.row_ends_with(0, "😀"));
+            assert!(t.row_ends_with(0, "ト😀"));
+            // EOL bytes are not a part of the row's content
+            assert!(!t.row_ends_with(0, "\r\n"));
+            assert!(!t.row_ends_with(0, "\n"));
+            assert!(t.row_ends_with(1, ";"));
+            assert!(t.row_ends_with(2, ""));
+            assert!(t.row_ends_with(3, "ンズ"));
+            assert!(!t.row_ends_with(3, "ゲート"));
+            assert!(!t.row_ends_with(4, ""));
+        }
+    }
+
+    mod lines_in {
+        use super::*;
+
+        #[test]
+        fn matches_lines() {
+            for s in [
+                "シュタインズ\n\r\nHello, 😀\r\n\nゲート",
+                "\n\n",
+                "",
+                "abc\r\ndef\r",
+            ] {
+                let t = Text::new(s.into());
+                let count = t.line_count();
+                for start in 0..count + 2 {
+                    for end in 0..count + 2 {
+                        let expected: Vec<_> = t
+                            .lines()
+                            .skip(start)
+                            .take(end.saturating_sub(start))
+                            .collect();
+                        let lines: Vec<_> = t.lines_in(start..end).collect();
+                        assert_eq!(lines, expected, "{s:?} {start}..{end}");
+                        assert_eq!(t.lines_in(start..end).len(), expected.len());
+                    }
+                }
+            }
+        }
+
+        #[test]
+        fn rev() {
+            let t = Text::new("a\r\nb\nc\nd".into());
+            assert_eq!(t.lines_in(1..3).rev().collect::<Vec<_>>(), ["c", "b"]);
+            assert_eq!(t.lines_in(2..10).rev().collect::<Vec<_>>(), ["d", "c"]);
+        }
+    }
+
+    mod char_at {
+        use super::*;
+
+        const S: &str = "aゲ😀\r\nb";
+
+        #[test]
+        fn utf16() {
+            let t = Text::new_utf16(S.into());
+            let at = |row, col| t.char_at(GridIndex { row, col });
+            assert_eq!(at(0, 0), Some('a'));
+            assert_eq!(at(0, 1), Some('ゲ'));
+            assert_eq!(at(0, 2), Some('😀'));
+            // in between a surrogate pair
+            assert_eq!(at(0, 3), None);
+            assert_eq!(at(0, 4), None);
+            assert_eq!(at(0, 5), None);
+            assert_eq!(at(1, 0), Some('b'));
+            assert_eq!(at(1, 1), None);
+            assert_eq!(at(2, 0), None);
+
+            assert_eq!(t.byte_at(GridIndex { row: 0, col: 1 }), Some(0xE3));
+            assert_eq!(t.byte_at(GridIndex { row: 0, col: 4 }), None);
+        }
+
+        #[test]
+        fn utf8() {
+            let t = Text::new(S.into());
+            let at = |row, col| t.char_at(GridIndex { row, col });
+            assert_eq!(at(0, 1), Some('ゲ'));
+            assert_eq!(at(0, 4), Some('😀'));
+            // in between the bytes of a char
+            assert_eq!(at(0, 2), None);
+            assert_eq!(t.byte_at(GridIndex { row: 0, col: 2 }), None);
+            assert_eq!(t.byte_at(GridIndex { row: 1, col: 0 }), Some(b'b'));
+        }
+    }
+
+    mod char_indices {
+        use super::*;
+
+        const S: &str = "aゲ😀\r\n\nb";
+
+        fn positions(t: &Text) -> Vec<(usize, usize, char)> {
+            t.char_indices()
+                .map(|(pos, c)| (pos.row, pos.col, c))
+                .collect()
+        }
+
+        #[test]
+        fn utf8() {
+            let t = Text::new(S.into());
+            assert_eq!(
+                positions(&t),
+                [
+                    (0, 0, 'a'),
+                    (0, 1, 'ゲ'),
+                    (0, 4, '😀'),
+                    (0, 8, '\r'),
+                    (0, 8, '\n'),
+                    (1, 0, '\n'),
+                    (2, 0, 'b')
+                ]
+            );
+        }
+
+        #[test]
+        fn utf16() {
+            let t = Text::new_utf16(S.into());
+            assert_eq!(
+                positions(&t),
+                [
+                    (0, 0, 'a'),
+                    (0, 1, 'ゲ'),
+                    (0, 2, '😀'),
+                    (0, 4, '\r'),
+                    (0, 4, '\n'),
+                    (1, 0, '\n'),
+                    (2, 0, 'b')
+                ]
+            );
+        }
+
+        #[test]
+        fn utf32() {
+            let t = Text::new_utf32(S.into());
+            assert_eq!(
+                positions(&t),
+                [
+                    (0, 0, 'a'),
+                    (0, 1, 'ゲ'),
+                    (0, 2, '😀'),
+                    (0, 3, '\r'),
+                    (0, 3, '\n'),
+                    (1, 0, '\n'),
+                    (2, 0, 'b')
+                ]
+            );
+        }
+
+        #[cfg(feature = "unicode-segmentation")]
+        #[test]
+        fn graphemes() {
+            let t = Text::new_graphemes("e\u{301}x\r🇯🇵".into());
+            assert_eq!(
+                positions(&t),
+                [
+                    (0, 0, 'e'),
+                    (0, 0, '\u{301}'),
+                    (0, 1, 'x'),
+                    (0, 2, '\r'),
+                    (1, 0, '🇯'),
+                    (1, 0, '🇵'),
+                ]
+            );
+        }
+
+        #[test]
+        fn crlf_positions_are_editable() {
+            let t = Text::new_utf16(S.into());
+            let crlf: Vec<_> = t
+                .char_indices()
+                .filter(|(_, c)| matches!(c, '\r' | '\n'))
+                .map(|(pos, _)| pos)
+                .take(2)
+                .collect();
+            // both chars of the "\r\n" are at the end of the row
+            assert_eq!(crlf, [GridIndex { row: 0, col: 4 }; 2]);
+            for pos in crlf {
+                let mut t = t.clone();
+                t.set_checked_columns(true);
+                t.insert("!", pos, &mut ()).unwrap();
+                assert_eq!(t.text, "aゲ😀!\r\n\nb");
+            }
+        }
+
+        #[test]
+        fn matches_char_at() {
+            let t = Text::new_utf16(S.into());
+            for (pos, c) in t.char_indices().filter(|(_, c)| !matches!(c, '\r' | '\n')) {
+                assert_eq!(t.char_at(pos), Some(c));
+            }
+        }
+    }
+
+    mod eq_ignoring_eol {
+        use super::*;
+
+        #[test]
+        fn lf_and_crlf() {
+            let lf = Text::new("Hello\nゲート\n\nWorld\n".into());
+            let crlf = Text::new_utf16("Hello\r\nゲート\r\n\r\nWorld\r\n".into());
+            assert_ne!(lf.text, crlf.text);
+            assert!(lf.eq_ignoring_eol(&crlf));
+            assert!(crlf.eq_ignoring_eol(&lf));
+        }
+
+        #[test]
+        fn different_content() {
+            let lf = Text::new("Hello\nWorld\n".into());
+            assert!(!lf.eq_ignoring_eol(&Text::new("Hello\r\nWorld".into())));
+            assert!(!lf.eq_ignoring_eol(&Text::new("Hello\r\nWorld!\r\n".into())));
+            assert!(!lf.eq_ignoring_eol(&Text::new("Hello World\n".into())));
+        }
+    }
+
+    mod slice {
+        use std::borrow::Cow;
+
+        use super::*;
+        use crate::core::lines::EolKind;
+
+        const S: &str = "fn main() {\r\n    ゲート();\n}\r}\u{2028}";
+
+        #[test]
+        fn borrowed() {
+            let t = Text::new_utf16(S.into());
+            let slice = t
+                .slice(
+                    GridIndex { row: 0, col: 10 },
+                    GridIndex { row: 2, col: 1 },
+                    None,
+                )
+                .unwrap();
+            assert!(matches!(slice, Cow::Borrowed("{\r\n    ゲート();\n}")));
+
+            // reversed positions on a single line
+            let slice = t
+                .slice(
+                    GridIndex { row: 1, col: 9 },
+                    GridIndex { row: 1, col: 4 },
+                    None,
+                )
+                .unwrap();
+            assert!(matches!(slice, Cow::Borrowed("ゲート()")));
+        }
+
+        #[test]
+        fn normalized() {
+            let t = Text::new_utf16(S.into());
+            let start = GridIndex { row: 0, col: 10 };
+            let end = GridIndex { row: 4, col: 0 };
+            let slice = |eol| t.slice(start, end, Some(eol)).unwrap();
+            assert!(matches!(slice(EolKind::Lf), Cow::Owned(_)));
+            // U+2028 is only a line break in the unicode mode
+            assert_eq!(slice(EolKind::Lf), "{\n    ゲート();\n}\n}\u{2028}");
+            assert_eq!(slice(EolKind::CrLf), "{\r\n    ゲート();\r\n}\r\n}\u{2028}");
+            assert_eq!(slice(EolKind::Cr), "{\r    ゲート();\r}\r}\u{2028}");
+
+            let t = Text::new_unicode_eol(S.into());
+            let slice = t.slice(start, end, Some(EolKind::Lf)).unwrap();
+            assert_eq!(slice, "{\n    ゲート();\n}\n}\n");
+            assert_eq!(
+                t.slice(start, GridIndex { row: 1, col: 4 }, Some(EolKind::Lf))
+                    .unwrap(),
+                "{\n    "
+            );
+        }
+
+        #[test]
+        fn invalid() {
+            let t = Text::new(S.into());
+            assert!(t
+                .slice(
+                    GridIndex { row: 0, col: 0 },
+                    GridIndex { row: 6, col: 0 },
+                    None
+                )
+                .is_err());
+            assert!(t
+                .slice(
+                    GridIndex { row: 1, col: 5 },
+                    GridIndex { row: 1, col: 6 },
+                    Some(EolKind::Lf)
+                )
+                .is_err());
+        }
+    }
+
+    mod to_string_with_eol {
+        use super::*;
+        use crate::core::lines::EolKind;
+
+        const S: &str = "fn main() {\r\n    ゲート();\n}\r}\r\n";
+
+        #[test]
+        fn lf() {
+            let t = Text::new(S.into());
+            assert_eq!(
+                t.to_string_with_eol(EolKind::Lf),
+                "fn main() {\n    ゲート();\n}\n}\n"
+            );
+            // the content is left as is
+            assert_eq!(t.text, S);
+        }
+
+        #[test]
+        fn crlf() {
+            let t = Text::new(S.into());
+            assert_eq!(
+                t.to_string_with_eol(EolKind::CrLf),
+                "fn main() {\r\n    ゲート();\r\n}\r\n}\r\n"
+            );
+        }
+
+        #[test]
+        fn no_line_breaks() {
+            for eol in [EolKind::Lf, EolKind::CrLf, EolKind::Cr] {
+                assert_eq!(Text::new("ゲート".into()).to_string_with_eol(eol), "ゲート");
+                assert_eq!(Text::new("".into()).to_string_with_eol(eol), "");
+            }
+        }
+    }
+
+    mod sub_text {
+        use super::*;
+
+        const S: &str = "fn main() {\r\n    ゲート();\n}\n";
+
+        #[test]
+        fn single_line() {
+            let t = Text::new_utf16(S.into());
+            let sub = t
+                .sub_text(GridIndex { row: 1, col: 4 }, GridIndex { row: 1, col: 9 })
+                .unwrap();
+            assert_eq!(sub, Text::new_utf16("ゲート()".into()));
+            assert_eq!(sub.br_indexes, [0]);
+        }
+
+        #[test]
+        fn multi_line() {
+            let t = Text::new_utf16(S.into());
+            let sub = t
+                .sub_text(GridIndex { row: 0, col: 10 }, GridIndex { row: 2, col: 1 })
+                .unwrap();
+            assert_eq!(sub.text, "{\r\n    ゲート();\n}");
+            assert_eq!(sub.br_indexes, [0, 2, 19]);
+            assert_eq!(sub.line_lengths().collect::<Vec<_>>(), [1, 10, 1]);
+
+            // reversed positions and the row after the last row
+            let sub = t
+                .sub_text(GridIndex { row: 4, col: 0 }, GridIndex { row: 2, col: 0 })
+                .unwrap();
+            assert_eq!(sub.text, "}\n");
+            assert_eq!(sub.br_indexes, [0, 1]);
+        }
+
+        #[test]
+        fn invalid() {
+            let t = Text::new(S.into());
+            assert!(t
+                .sub_text(GridIndex { row: 0, col: 0 }, GridIndex { row: 5, col: 0 })
+                .is_err());
+            // in between the bytes of a char
+            assert!(t
+                .sub_text(GridIndex { row: 1, col: 5 }, GridIndex { row: 2, col: 0 })
+                .is_err());
+        }
+    }
+
+    mod ends_with_newline {
+        use super::*;
+
+        #[test]
+        fn ends_with_newline() {
+            assert!(Text::new("a\n".into()).ends_with_newline());
+            assert!(Text::new("a\r\n".into()).ends_with_newline());
+            assert!(Text::new("a\n\n".into()).ends_with_newline());
+            assert!(Text::new("\n".into()).ends_with_newline());
+            assert!(!Text::new("a\nb".into()).ends_with_newline());
+            assert!(!Text::new(String::new()).ends_with_newline());
+        }
+    }
+
+    mod visual_column {
+        use super::*;
+
+        #[test]
+        fn tabs() {
+            let t = Text::new_utf16("\tab\tc\n  \tゲ\t😀x".into());
+            let col = |row, col, width| t.visual_column(GridIndex { row, col }, width).unwrap();
+            assert_eq!(col(0, 0, 4), 0);
+            assert_eq!(col(0, 1, 4), 4);
+            assert_eq!(col(0, 3, 4), 6);
+            assert_eq!(col(0, 4, 4), 8);
+            assert_eq!(col(0, 5, 4), 9);
+            assert_eq!(col(0, 4, 8), 16);
+            assert_eq!(col(0, 4, 3), 6);
+
+            assert_eq!(col(1, 2, 4), 2);
+            assert_eq!(col(1, 3, 4), 4);
+            assert_eq!(col(1, 4, 4), 5);
+            assert_eq!(col(1, 5, 4), 8);
+            assert_eq!(col(1, 7, 4), 9);
+            assert_eq!(col(1, 5, 2), 6);
+        }
+
+        #[test]
+        fn bad_row() {
+            let t = Text::new("a\tb".into());
+            assert!(t.visual_column(GridIndex { row: 1, col: 0 }, 4).is_err());
+        }
+    }
+
+    mod line_lengths {
+        use super::*;
+
+        const S: &str = "シュタインズ\n\r\nHello, 😀\r\n\nゲート";
+
+        #[test]
+        fn utf8() {
+            let t = Text::new(S.into());
+            assert_eq!(t.line_lengths().collect::<Vec<_>>(), [18, 0, 11, 0, 9]);
+        }
+
+        #[test]
+        fn utf16() {
+            let t = Text::new_utf16(S.into());
+            assert_eq!(t.line_lengths().collect::<Vec<_>>(), [6, 0, 9, 0, 3]);
+        }
+
+        #[test]
+        fn utf32() {
+            let t = Text::new_utf32(S.into());
+            assert_eq!(t.line_lengths().collect::<Vec<_>>(), [6, 0, 8, 0, 3]);
+        }
+
+        #[test]
+        fn empty() {
+            let t = Text::new_utf16(String::new());
+            assert_eq!(t.line_lengths().collect::<Vec<_>>(), [0]);
+            let t = Text::new_utf16("\n".into());
+            assert_eq!(t.line_lengths().collect::<Vec<_>>(), [0, 0]);
+        }
+    }
+
+    #[cfg(feature = "unicode-segmentation")]
+    mod graphemes {
+        use crate::error::{Encoding, Error};
+
+        use super::*;
+
+        // flag emoji are two scalars, "e\u{301}" is an "e" with a combining acute accent
+        const S: &str = "🇯🇵 e\u{301}\u{302}x\n👩‍👩‍👧 ok";
+
+        #[test]
+        fn line_lengths() {
+            let t = Text::new_graphemes(S.into());
+            assert_eq!(t.line_lengths().collect::<Vec<_>>(), [4, 4]);
+        }
+
+        #[test]
+        fn insert() {
+            let mut t = Text::new_graphemes(S.into());
+            t.insert("!", GridIndex { row: 0, col: 3 }, &mut ())
+                .unwrap();
+            assert_eq!(t.get_row(0), Some("🇯🇵 e\u{301}\u{302}!x"));
+            t.insert("?", GridIndex { row: 1, col: 1 }, &mut ())
+                .unwrap();
+            assert_eq!(t.get_row(1), Some("👩‍👩‍👧? ok"));
+        }
+
+        #[test]
+        fn delete() {
+            let mut t = Text::new_graphemes(S.into());
+            t.delete(
+                GridIndex { row: 0, col: 0 },
+                GridIndex { row: 0, col: 3 },
+                &mut (),
+            )
+            .unwrap();
+            assert_eq!(t.get_row(0), Some("x"));
+        }
+
+        #[test]
+        fn find() {
+            let t = Text::new_graphemes(S.into());
+            assert_eq!(t.find("x"), Some(GridIndex { row: 0, col: 3 }));
+            assert_eq!(t.find("ok"), Some(GridIndex { row: 1, col: 2 }));
+        }
+
+        #[test]
+        fn in_between_graphemes() {
+            let mut t = Text::new_graphemes(S.into());
+            let mut pos = GridIndex { row: 0, col: 3 };
+            pos.normalize(&mut t).unwrap();
+            assert_eq!(pos.col, 14);
+            // pointing between the "e" and its accents
+            let mut pos = GridIndex { row: 0, col: 10 };
+            assert_eq!(
+                pos.denormalize(&t),
+                Err(Error::InBetweenCharBoundries {
+                    encoding: Encoding::Graphemes,
+                    row: 0,
+                    col: 10,
+                    line_len: 4,
+                })
+            );
+        }
+    }
+
+    mod row_len_in_encoding {
+        use super::*;
+
+        const S: &str = "ゲート😀\r\nab\n";
+
+        #[test]
+        fn row_len_in_encoding() {
+            let t = Text::new_utf16(S.into());
+            assert_eq!(t.row_len_in_encoding(0), Some(5));
+            assert_eq!(t.row_len_in_encoding(1), Some(2));
+            assert_eq!(t.row_len_in_encoding(2), Some(0));
+            assert_eq!(t.row_len_in_encoding(3), None);
+
+            assert_eq!(Text::new(S.into()).row_len_in_encoding(0), Some(13));
+            assert_eq!(Text::new_utf32(S.into()).row_len_in_encoding(0), Some(4));
+        }
+    }
+
+    mod line_spans {
+        use super::*;
+
+        const S: &str = "シュタインズ\n\r\nHello, 😀\r\n\nゲート";
+
+        fn spans(lens: &[usize]) -> Vec<(GridIndex, GridIndex)> {
+            lens.iter()
+                .enumerate()
+                .map(|(row, &col)| (GridIndex { row, col: 0 }, GridIndex { row, col }))
+                .collect()
+        }
+
+        #[test]
+        fn utf8() {
+            let t = Text::new(S.into());
+            assert_eq!(
+                t.line_spans().collect::<Vec<_>>(),
+                spans(&[18, 0, 11, 0, 9])
+            );
+        }
+
+        #[test]
+        fn utf16() {
+            let t = Text::new_utf16(S.into());
+            assert_eq!(t.line_spans().collect::<Vec<_>>(), spans(&[6, 0, 9, 0, 3]));
+        }
+
+        #[test]
+        fn trailing_eol() {
+            let t = Text::new_utf32("😀😀\n".into());
+            assert_eq!(t.line_spans().collect::<Vec<_>>(), spans(&[2, 0]));
+        }
+    }
+
+    mod diff {
+        use crate::change::Change;
+
+        use super::*;
+
+        fn assert_diff(mut t: Text, new: &str, count: usize) {
+            let changes = t.diff(new);
+            assert_eq!(changes.len(), count);
+            for change in changes {
+                t.update(change, &mut ()).unwrap();
+            }
+            assert_eq!(t.text, new);
+            assert_eq!(t.br_indexes, Text::new(new.to_string()).br_indexes);
+        }
+
+        #[test]
+        fn same() {
+            assert_diff(
+                Text::new("Hello, World!\nApples".into()),
+                "Hello, World!\nApples",
+                0,
+            );
+        }
+
+        #[test]
+        fn prefix_only() {
+            let t = Text::new_utf16("Hello, World!\nシュタインズ".into());
+            assert_eq!(
+                t.diff("Hello, World!\nシュタインズ・ゲート\n"),
+                [Change::Insert {
+                    at: GridIndex { row: 1, col: 6 },
+                    text: "・ゲート\n".into()
+                }]
+            );
+            assert_diff(t.clone(), "Hello, World!\nシュタインズ・ゲート\n", 1);
+            assert_diff(t, "Hello, World!\nシュ", 1);
+        }
+
+        #[test]
+        fn suffix_only() {
+            let t = Text::new_utf16("Hello, World!\nシュタインズ".into());
+            assert_diff(t.clone(), "ゲート\r\nHello, World!\nシュタインズ", 1);
+            assert_diff(t, "World!\nシュタインズ", 1);
+        }
+
+        #[test]
+        fn middle() {
+            let t = Text::new_utf32("Hello, World!\nシュタインズ\nApples".into());
+            assert_eq!(
+                t.diff("Hello, ゲート\nApples"),
+                [Change::Replace {
+                    start: GridIndex { row: 0, col: 7 },
+                    end: GridIndex { row: 1, col: 6 },
+                    text: "ゲート".into()
+                }]
+            );
+            assert_diff(t.clone(), "Hello, ゲート\nApples", 1);
+            assert_diff(t.clone(), "Hello, Apples", 1);
+            assert_diff(t, "Hello\n\n, World!\nシュタインズ\nApples", 1);
+        }
+
+        #[test]
+        fn crlf() {
+            let t = Text::new("Hello\r\nWorld".into());
+            assert_diff(t.clone(), "Hello\rWorld", 1);
+            assert_diff(t.clone(), "Hello\nWorld", 1);
+            assert_diff(t, "Hello\r\n\r\nWorld", 1);
+        }
+    }
+
+    mod sub_range_edit {
+        use crate::change::Change;
+
+        use super::*;
+
+        const OUTER: (GridIndex, GridIndex) =
+            (GridIndex { row: 1, col: 4 }, GridIndex { row: 3, col: 2 });
+
+        fn text() -> Text {
+            Text::new("Hello, World!\nlet s = \"SELECT *\nFROM table\nWHERE\";\nBye".into())
+        }
+
+        #[test]
+        fn inside() {
+            let t = text();
+            assert_eq!(
+                t.sub_range_edit(
+                    OUTER,
+                    &Change::Insert {
+                        at: GridIndex { row: 1, col: 8 },
+                        text: "a".into()
+                    }
+                ),
+                Some(Change::Insert {
+                    at: GridIndex { row: 0, col: 4 },
+                    text: "a".into()
+                })
+            );
+            assert_eq!(
+                t.sub_range_edit(
+                    OUTER,
+                    &Change::Replace {
+                        start: GridIndex { row: 1, col: 9 },
+                        end: GridIndex { row: 2, col: 4 },
+                        text: "a".into()
+                    }
+                ),
+                Some(Change::Replace {
+                    start: GridIndex { row: 0, col: 5 },
+                    end: GridIndex { row: 1, col: 4 },
+                    text: "a".into()
+                })
+            );
+            assert_eq!(
+                t.sub_range_edit(
+                    OUTER,
+                    &Change::Insert {
+                        at: GridIndex { row: 3, col: 2 },
+                        text: "a".into()
+                    }
+                ),
+                Some(Change::Insert {
+                    at: GridIndex { row: 2, col: 2 },
+                    text: "a".into()
+                })
+            );
+        }
+
+        #[test]
+        fn partially_overlapping() {
+            let t = text();
+            assert_eq!(
+                t.sub_range_edit(
+                    OUTER,
+                    &Change::Delete {
+                        start: GridIndex { row: 0, col: 5 },
+                        end: GridIndex { row: 1, col: 6 },
+                    }
+                ),
+                Some(Change::Delete {
+                    start: GridIndex { row: 0, col: 0 },
+                    end: GridIndex { row: 0, col: 2 },
+                })
+            );
+            assert_eq!(
+                t.sub_range_edit(
+                    OUTER,
+                    &Change::Replace {
+                        start: GridIndex { row: 2, col: 1 },
+                        end: GridIndex { row: 4, col: 1 },
+                        text: "abc".into()
+                    }
+                ),
+                Some(Change::Replace {
+                    start: GridIndex { row: 1, col: 1 },
+                    end: GridIndex { row: 2, col: 2 },
+                    text: "abc".into()
+                })
+            );
+        }
+
+        #[test]
+        fn outside() {
+            let t = text();
+            for change in [
+                Change::Insert {
+                    at: GridIndex { row: 1, col: 3 },
+                    text: "a".into(),
+                },
+                Change::Insert {
+                    at: GridIndex { row: 4, col: 0 },
+                    text: "a".into(),
+                },
+                Change::Delete {
+                    start: GridIndex { row: 0, col: 0 },
+                    end: GridIndex { row: 1, col: 4 },
+                },
+                Change::Replace {
+                    start: GridIndex { row: 3, col: 2 },
+                    end: GridIndex { row: 4, col: 2 },
+                    text: "a".into(),
+                },
+                Change::ReplaceFull("a".into()),
+            ] {
+                assert_eq!(t.sub_range_edit(OUTER, &change), None);
+            }
+        }
+    }
+
+    #[cfg(feature = "tree-sitter")]
+    mod ts_range_of {
+        use tree_sitter::{Point, Range};
+
+        use crate::change::Change;
+
+        use super::*;
+
+        fn text() -> Text {
+            Text::new_utf16("Hello, World!\nシュタインズ・ゲート\r\nApples".into())
+        }
+
+        #[test]
+        fn insert() {
+            let range = text()
+                .ts_range_of(&Change::Insert {
+                    at: GridIndex { row: 1, col: 2 },
+                    text: "abc".into(),
+                })
+                .unwrap();
+            assert_eq!(
+                range,
+                Range {
+                    start_byte: 20,
+                    end_byte: 20,
+                    start_point: Point { row: 1, column: 6 },
+                    end_point: Point { row: 1, column: 6 },
+                }
+            );
+        }
+
+        #[test]
+        fn delete() {
+            let range = text()
+                .ts_range_of(&Change::Delete {
+                    start: GridIndex { row: 0, col: 7 },
+                    end: GridIndex { row: 1, col: 7 },
+                })
+                .unwrap();
+            assert_eq!(
+                range,
+                Range {
+                    start_byte: 7,
+                    end_byte: 35,
+                    start_point: Point { row: 0, column: 7 },
+                    end_point: Point { row: 1, column: 21 },
+                }
+            );
+        }
+
+        #[test]
+        fn replace() {
+            let range = text()
+                .ts_range_of(&Change::Replace {
+                    start: GridIndex { row: 2, col: 3 },
+                    end: GridIndex { row: 1, col: 10 },
+                    text: "abc".into(),
+                })
+                .unwrap();
+            assert_eq!(
+                range,
+                Range {
+                    start_byte: 44,
+                    end_byte: 49,
+                    start_point: Point { row: 1, column: 30 },
+                    end_point: Point { row: 2, column: 3 },
+                }
+            );
+        }
+
+        #[test]
+        fn replace_full() {
+            let range = text()
+                .ts_range_of(&Change::ReplaceFull("abc".into()))
+                .unwrap();
+            assert_eq!(
+                range,
+                Range {
+                    start_byte: 0,
+                    end_byte: 52,
+                    start_point: Point { row: 0, column: 0 },
+                    end_point: Point { row: 2, column: 6 },
+                }
+            );
+        }
+
+        #[test]
+        fn oob_row() {
+            assert!(text()
+                .ts_range_of(&Change::Delete {
+                    start: GridIndex { row: 0, col: 7 },
+                    end: GridIndex { row: 5, col: 7 },
+                })
+                .is_err());
+        }
+    }
+
+    mod smart_home {
+        use super::*;
+
+        #[test]
+        fn past_indentation() {
+            let t = Text::new_utf16("Hello\n\t\u{3000} ゲート, World!".into());
+            assert_eq!(
+                t.smart_home(GridIndex { row: 1, col: 6 }),
+                GridIndex { row: 1, col: 3 }
+            );
+            assert_eq!(
+                t.smart_home(GridIndex { row: 0, col: 3 }),
+                GridIndex { row: 0, col: 0 }
+            );
+        }
+
+        #[test]
+        fn at_indentation() {
+            let t = Text::new_utf16("Hello\n\t\u{3000} ゲート, World!".into());
+            assert_eq!(
+                t.smart_home(GridIndex { row: 1, col: 3 }),
+                GridIndex { row: 1, col: 0 }
+            );
+        }
+
+        #[test]
+        fn at_start() {
+            let t = Text::new_utf16("Hello\n\t\u{3000} ゲート, World!\n   ".into());
+            assert_eq!(
+                t.smart_home(GridIndex { row: 1, col: 0 }),
+                GridIndex { row: 1, col: 0 }
+            );
+            assert_eq!(
+                t.smart_home(GridIndex { row: 1, col: 1 }),
+                GridIndex { row: 1, col: 0 }
+            );
+            assert_eq!(
+                t.smart_home(GridIndex { row: 2, col: 3 }),
+                GridIndex { row: 2, col: 0 }
+            );
+        }
+
+        #[test]
+        fn oob_row() {
+            let t = Text::new("Hello".into());
+            assert_eq!(
+                t.smart_home(GridIndex { row: 5, col: 2 }),
+                GridIndex { row: 5, col: 2 }
+            );
+        }
     }
 
-    mod delete {
+    mod word_boundaries {
         use super::*;
 
         #[test]
-        fn single_line() {
-            let mut t = Text::new("Hello, World!".into());
-            assert_eq!(t.br_indexes, [0]);
-            t.delete(
-                GridIndex { row: 0, col: 1 },
-                GridIndex { row: 0, col: 6 },
-                &mut (),
-            )
-            .unwrap();
+        fn mixed() {
+            let t = Text::new("\nfoo_bar(ゲート, x1)  -> é;\r\n".into());
+            assert_eq!(
+                t.word_boundaries(1).collect::<Vec<_>>(),
+                [0..7, 8..17, 19..21, 27..29]
+            );
+        }
 
-            assert_eq!(t.br_indexes, [0]);
-            assert_eq!(t.text, "H World!");
+        #[test]
+        fn empty() {
+            let t = Text::new("\n  ;; \r\n".into());
+            assert_eq!(t.word_boundaries(0).count(), 0);
+            assert_eq!(t.word_boundaries(1).count(), 0);
+            assert_eq!(t.word_boundaries(2).count(), 0);
+            assert_eq!(t.word_boundaries(3).count(), 0);
         }
+    }
+
+    mod find {
+        use super::*;
 
         #[test]
-        fn multiline() {
-            let mut t = Text::new("Hello, World!\nApples\n Oranges\nPears".into());
-            assert_eq!(t.br_indexes, [0, 13, 20, 29]);
-            t.delete(
-                GridIndex { row: 1, col: 3 },
-                GridIndex { row: 3, col: 2 },
-                &mut (),
-            )
-            .unwrap();
+        fn find() {
+            let t = Text::new_utf16("Hello, World!\nシュタインズ・ゲート\r\nゲート".into());
+            assert_eq!(t.find("Hello"), Some(GridIndex { row: 0, col: 0 }));
+            assert_eq!(t.find("World"), Some(GridIndex { row: 0, col: 7 }));
+            assert_eq!(t.find("ゲート"), Some(GridIndex { row: 1, col: 7 }));
+            assert_eq!(t.find("Apples"), None);
+            assert_eq!(t.find(""), None);
+        }
 
-            assert_eq!(t.br_indexes, [0, 13]);
-            assert_eq!(t.text, "Hello, World!\nAppars");
+        #[test]
+        fn line_boundary() {
+            let t = Text::new_utf16("Hello, World!\nシュタインズ・ゲート\r\nゲート".into());
+            assert_eq!(t.find("\nシュ"), Some(GridIndex { row: 0, col: 13 }));
+            assert_eq!(t.find("シュ"), Some(GridIndex { row: 1, col: 0 }));
+            assert_eq!(t.find("\r\nゲ"), Some(GridIndex { row: 1, col: 10 }));
         }
 
         #[test]
-        fn in_line_into_start() {
-            let mut t = Text::new("Hello, World!\nApples\n Oranges\nPears".into());
-            assert_eq!(t.br_indexes, [0, 13, 20, 29]);
-            t.delete(
-                GridIndex { row: 0, col: 1 },
-                GridIndex { row: 0, col: 4 },
-                &mut (),
-            )
-            .unwrap();
+        fn inside_crlf() {
+            let mut t = Text::new("a\r\nb\nc\r\n".into());
+            assert_eq!(t.find("\n"), Some(GridIndex { row: 0, col: 1 }));
+            assert_eq!(t.rfind("\nc"), Some(GridIndex { row: 1, col: 1 }));
+            assert_eq!(t.rfind("\n"), Some(GridIndex { row: 2, col: 1 }));
+            assert_eq!(
+                t.find_all("\n").collect::<Vec<_>>(),
+                [
+                    GridIndex { row: 0, col: 1 },
+                    GridIndex { row: 1, col: 1 },
+                    GridIndex { row: 2, col: 1 },
+                ]
+            );
 
-            assert_eq!(t.br_indexes, [0, 10, 17, 26]);
-            assert_eq!(t.text, "Ho, World!\nApples\n Oranges\nPears");
+            // the positions can be edited
+            let at = t.find("\n").unwrap();
+            t.insert("x", at, &mut ()).unwrap();
+            assert_eq!(t.text, "ax\r\nb\nc\r\n");
         }
 
         #[test]
-        fn in_line_at_start() {
-            let mut t = Text::new("Hello, World!\nApples\n Oranges\nPears".into());
-            assert_eq!(t.br_indexes, [0, 13, 20, 29]);
-            t.delete(
-                GridIndex { row: 0, col: 0 },
-                GridIndex { row: 0, col: 4 },
-                &mut (),
-            )
-            .unwrap();
-
-            assert_eq!(t.br_indexes, [0, 9, 16, 25]);
-            assert_eq!(t.text, "o, World!\nApples\n Oranges\nPears");
+        fn rfind() {
+            let t = Text::new_utf16("ゲートHello, World!\nシュタインズ・ゲート\r\nゲート".into());
+            assert_eq!(t.rfind("ゲート"), Some(GridIndex { row: 2, col: 0 }));
+            assert_eq!(t.rfind("World"), t.find("World"));
+            assert_eq!(t.rfind("Apples"), None);
+            assert_eq!(t.rfind(""), None);
         }
 
         #[test]
-        fn across_first_line() {
-            let mut t = Text::new("Hello, World!\nApplbs\n Oranges\nPears".into());
-            assert_eq!(t.br_indexes, [0, 13, 20, 29]);
-            t.delete(
-                GridIndex { row: 0, col: 3 },
-                GridIndex { row: 1, col: 4 },
-                &mut (),
-            )
-            .unwrap();
+        fn rfind_at_end() {
+            let t = Text::new_utf16("Hello, World!\nシュタインズ・ゲート\nゲート!".into());
+            assert_eq!(t.rfind("!"), Some(GridIndex { row: 2, col: 3 }));
+            assert_eq!(t.rfind("ト!"), Some(GridIndex { row: 2, col: 2 }));
+            assert_eq!(t.find("ト!"), t.rfind("ト!"));
+        }
 
-            assert_eq!(t.br_indexes, [0, 5, 14]);
-            assert_eq!(t.text, "Helbs\n Oranges\nPears");
+        #[test]
+        fn find_all() {
+            let t = Text::new_utf32("ゲートゲート\nゲーゲート\nゲ".into());
+            assert_eq!(
+                t.find_all("ゲート").collect::<Vec<_>>(),
+                [
+                    GridIndex { row: 0, col: 0 },
+                    GridIndex { row: 0, col: 3 },
+                    GridIndex { row: 1, col: 2 },
+                ]
+            );
+            assert_eq!(t.find_all("").count(), 0);
         }
 
         #[test]
-        fn across_last_line() {
-            let mut t = Text::new("Hello, World!\nApplbs\n Oranges\nPears".into());
-            assert_eq!(t.br_indexes, [0, 13, 20, 29]);
-            t.delete(
-                GridIndex { row: 2, col: 3 },
-                GridIndex { row: 3, col: 2 },
-                &mut (),
-            )
-            .unwrap();
+        fn find_all_overlapping() {
+            let t = Text::new("aaa\naaaa".into());
+            assert_eq!(
+                t.find_all("aa").collect::<Vec<_>>(),
+                [
+                    GridIndex { row: 0, col: 0 },
+                    GridIndex { row: 1, col: 0 },
+                    GridIndex { row: 1, col: 2 },
+                ]
+            );
+        }
+    }
 
-            assert_eq!(t.br_indexes, [0, 13, 20]);
-            assert_eq!(t.text, "Hello, World!\nApplbs\n Orars");
+    mod replace_all {
+        use super::*;
+
+        #[test]
+        fn grow() {
+            let mut t = Text::new_utf16("ゲート, World!\nシュタインズ・ゲート\nゲート".into());
+            let count = t.replace_all("ゲート", "Gate", &mut ()).unwrap();
+            assert_eq!(count, 3);
+            assert_eq!(t.text, "Gate, World!\nシュタインズ・Gate\nGate");
+            assert_eq!(t, Text::new_utf16(t.text.clone()));
         }
 
         #[test]
-        fn in_line_at_middle() {
-            let mut t = Text::new("Hello, World!\nApples\n Oranges\nPears".into());
-            assert_eq!(t.br_indexes, [0, 13, 20, 29]);
-            t.delete(
-                GridIndex { row: 2, col: 1 },
-                GridIndex { row: 2, col: 4 },
-                &mut (),
-            )
-            .unwrap();
+        fn new_lines() {
+            let mut t = Text::new("a, b, c\nd, e".into());
+            let count = t.replace_all(", ", "\r\n", &mut ()).unwrap();
+            assert_eq!(count, 3);
+            assert_eq!(t.text, "a\r\nb\r\nc\nd\r\ne");
+            assert_eq!(t.br_indexes, [0, 2, 5, 7, 10]);
+        }
 
-            assert_eq!(t.br_indexes, [0, 13, 20, 26]);
-            assert_eq!(t.text, "Hello, World!\nApples\n nges\nPears");
+        #[test]
+        fn remove_lines() {
+            let mut t = Text::new("a\nb\nc\n".into());
+            let count = t.replace_all("\n", "", &mut ()).unwrap();
+            assert_eq!(count, 3);
+            assert_eq!(t.text, "abc");
+            assert_eq!(t.br_indexes, [0]);
         }
 
         #[test]
-        fn in_line_at_end() {
-            let mut t = Text::new("Hello, World!\nApples\n Oranges\nPears".into());
-            assert_eq!(t.br_indexes, [0, 13, 20, 29]);
-            t.delete(
-                GridIndex { row: 3, col: 1 },
-                GridIndex { row: 3, col: 4 },
-                &mut (),
-            )
-            .unwrap();
+        fn crlf() {
+            let mut t = Text::new("a\r\nb\r\nc".into());
+            assert_eq!(t.replace_all("\n", "", &mut ()).unwrap(), 2);
+            assert_eq!(t.text, "abc");
+            assert_eq!(t.br_indexes, [0]);
 
-            assert_eq!(t.br_indexes, [0, 13, 20, 29]);
-            assert_eq!(t.text, "Hello, World!\nApples\n Oranges\nPs");
+            let mut t = Text::new("a\r\nb\r\nc".into());
+            assert_eq!(t.replace_all("\r", "\n", &mut ()).unwrap(), 2);
+            assert_eq!(t.text, "a\nb\nc");
+            assert_eq!(t.br_indexes, [0, 1, 3]);
         }
 
         #[test]
-        fn from_start() {
-            let mut t = Text::new("Hello, World!\nApples\n Oranges\nPears".into());
-            assert_eq!(t.br_indexes, [0, 13, 20, 29]);
-            t.delete(
-                GridIndex { row: 0, col: 0 },
-                GridIndex { row: 0, col: 5 },
-                &mut (),
-            )
-            .unwrap();
-
-            assert_eq!(t.br_indexes, [0, 8, 15, 24]);
-            assert_eq!(t.text, ", World!\nApples\n Oranges\nPears");
+        fn mixed_eol() {
+            let mut t = Text::new_utf16("x\r\nゲ\nz\rw".into());
+            assert_eq!(t.replace_all("\n", " ", &mut ()).unwrap(), 2);
+            assert_eq!(t.text, "x ゲ z\rw");
+            assert_eq!(t, Text::new_utf16(t.text.clone()));
         }
 
         #[test]
-        fn from_end() {
-            let mut t = Text::new("Hello, World!\nApples\n Oranges\nPears".into());
-            assert_eq!(t.br_indexes, [0, 13, 20, 29]);
-            t.delete(
-                GridIndex { row: 3, col: 0 },
-                GridIndex { row: 3, col: 5 },
-                &mut (),
-            )
-            .unwrap();
+        fn no_match() {
+            let mut t = Text::new("Hello, World!".into());
+            let mut called = false;
+            let count = t
+                .replace_all(
+                    "Apples",
+                    "Oranges",
+                    &mut |_: crate::updateables::UpdateContext| {
+                        called = true;
+                        Ok(())
+                    },
+                )
+                .unwrap();
+            assert_eq!(count, 0);
+            assert!(!called);
+            assert_eq!(t.replace_all("", "Oranges", &mut ()).unwrap(), 0);
+            assert_eq!(t.text, "Hello, World!");
+        }
+    }
 
-            assert_eq!(t.br_indexes, [0, 13, 20, 29]);
-            assert_eq!(t.text, "Hello, World!\nApples\n Oranges\n");
+    #[cfg(feature = "regex")]
+    mod find_regex {
+        use super::*;
+        use regex::Regex;
+
+        #[test]
+        fn single_line() {
+            let t = Text::new_utf16("fn ゲート() {}\nfn main() {}".into());
+            let re = Regex::new(r"fn \w+").unwrap();
+            assert_eq!(
+                t.find_regex(&re).collect::<Vec<_>>(),
+                [
+                    (GridIndex { row: 0, col: 0 }, GridIndex { row: 0, col: 6 }),
+                    (GridIndex { row: 1, col: 0 }, GridIndex { row: 1, col: 7 }),
+                ]
+            );
         }
 
         #[test]
-        fn br() {
-            let mut t = Text::new("Hello, World!\nBadApple\n".into());
-            assert_eq!(t.br_indexes, [0, 13, 22]);
-            t.delete(
-                GridIndex { row: 1, col: 8 },
-                GridIndex { row: 2, col: 0 },
-                &mut (),
-            )
-            .unwrap();
+        fn multi_line() {
+            let t = Text::new("a {\r\n  b\r\n} c {\n}".into());
+            let re = Regex::new(r"\{[^}]*\}").unwrap();
+            assert_eq!(
+                t.find_regex(&re).collect::<Vec<_>>(),
+                [
+                    (GridIndex { row: 0, col: 2 }, GridIndex { row: 2, col: 1 }),
+                    (GridIndex { row: 2, col: 4 }, GridIndex { row: 3, col: 1 }),
+                ]
+            );
+        }
 
-            assert_eq!(t.br_indexes, [0, 13]);
-            assert_eq!(t.text, "Hello, World!\nBadApple");
+        #[test]
+        fn inside_crlf() {
+            let t = Text::new("a\r\nb\r\nc".into());
+            let re = Regex::new(r"\n").unwrap();
+            assert_eq!(
+                t.find_regex(&re).collect::<Vec<_>>(),
+                [
+                    (GridIndex { row: 0, col: 1 }, GridIndex { row: 1, col: 0 }),
+                    (GridIndex { row: 1, col: 1 }, GridIndex { row: 2, col: 0 }),
+                ]
+            );
+            let re = Regex::new(r"b\r").unwrap();
+            assert_eq!(
+                t.find_regex(&re).collect::<Vec<_>>(),
+                [(GridIndex { row: 1, col: 0 }, GridIndex { row: 2, col: 0 })]
+            );
         }
 
         #[test]
-        fn br_chain() {
-            let mut t = Text::new("Hello, World!\n\n\nBadApple\n".into());
-            assert_eq!(t.br_indexes, [0, 13, 14, 15, 24]);
-            t.delete(
-                GridIndex { row: 1, col: 0 },
-                GridIndex { row: 2, col: 0 },
-                &mut (),
-            )
-            .unwrap();
+        fn anchored() {
+            let t = Text::new("abc\nabd\nxab".into());
+            let re = Regex::new(r"(?m)^ab").unwrap();
+            assert_eq!(
+                t.find_regex(&re).collect::<Vec<_>>(),
+                [
+                    (GridIndex { row: 0, col: 0 }, GridIndex { row: 0, col: 2 }),
+                    (GridIndex { row: 1, col: 0 }, GridIndex { row: 1, col: 2 }),
+                ]
+            );
 
-            assert_eq!(t.br_indexes, [0, 13, 14, 23]);
-            assert_eq!(t.text, "Hello, World!\n\nBadApple\n");
+            let re = Regex::new(r"ab$").unwrap();
+            assert_eq!(
+                t.find_regex(&re).collect::<Vec<_>>(),
+                [(GridIndex { row: 2, col: 1 }, GridIndex { row: 2, col: 3 })]
+            );
         }
+    }
+
+    mod replace_full {
+        use super::*;
 
         #[test]
-        fn long_text_single_byte() {
-            let mut t = Text::new(
-                "Hello, World!\nBanana\nHuman\nInteresting\nSuper\nMohawk\nShrek is a great movie."
-                    .into(),
-            );
-            assert_eq!(t.br_indexes, [0, 13, 20, 26, 38, 44, 51]);
-            t.delete(
-                GridIndex { row: 1, col: 3 },
-                GridIndex { row: 5, col: 2 },
-                &mut (),
-            )
-            .unwrap();
-            assert_eq!(t.br_indexes, [0, 13, 21]);
-            assert_eq!(t.text, "Hello, World!\nBanhawk\nShrek is a great movie.");
+        fn keeps_larger_allocation() {
+            let mut t = Text::new("a".repeat(1024));
+            let cap = t.text.capacity();
+            t.replace_full(String::from("Hello\nWorld").into(), &mut ())
+                .unwrap();
+            assert_eq!(t.text, "Hello\nWorld");
+            assert_eq!(t.text.capacity(), cap);
+            assert_eq!(t.br_indexes, [0, 5]);
         }
 
         #[test]
-        fn long_text_multi_byte() {
-            let mut t = Text::new(
-                "\
-誰かがかつて世界が私をロールつもりである私に言いました
-私は小屋で最もシャープなツールではありません
-彼女は彼女の指と親指でダムのようなものを探していました
-彼女の額の「L」の形をしました
+        fn takes_larger_allocation() {
+            let mut t = Text::new("a".into());
+            let s = "a\n".repeat(1024);
+            let ptr = s.as_ptr();
+            t.replace_full(s.into(), &mut ()).unwrap();
+            assert_eq!(t.text.as_ptr(), ptr);
+            assert_eq!(t.br_indexes.row_count().get(), 1025);
+        }
+    }
 
-さて、年が来て起動し、彼らが来て停止しません
-ルールに供給され、私は地上走行をヒット
-楽しみのために生きることはない意味がありませんでした
-あなたの脳は、スマート取得しますが、あなたの頭はダム取得します
+    mod checked_columns {
+        use crate::error::Error;
 
-見るために、あまりを行うことがそんなに
-だから、裏通りを取ると間違って何ですか？
-あなたが行っていない場合は、あなたが知っていることは決してないだろう
-あなたが輝くない場合は輝くことは決してないだろう"
-                    .into(),
-            );
-            assert_eq!(
-                t.br_indexes,
-                [0, 81, 148, 230, 274, 275, 342, 400, 479, 573, 574, 632, 693, 796]
-            );
+        use super::*;
+
+        #[test]
+        fn clamped() {
+            let mut t = Text::new_utf16("ゲート\nab".into());
+            t.insert("!", GridIndex { row: 0, col: 10 }, &mut ())
+                .unwrap();
+            assert_eq!(t.text, "ゲート!\nab");
             t.delete(
-                GridIndex { row: 1, col: 3 },
-                GridIndex { row: 5, col: 0 },
+                GridIndex { row: 1, col: 1 },
+                GridIndex { row: 1, col: 10 },
                 &mut (),
             )
             .unwrap();
-            assert_eq!(
-                t.br_indexes,
-                [0, 81, 151, 209, 288, 382, 383, 441, 502, 605]
-            );
-            assert_eq!(
-                t.text,
-                "\
-誰かがかつて世界が私をロールつもりである私に言いました
-私さて、年が来て起動し、彼らが来て停止しません
-ルールに供給され、私は地上走行をヒット
-楽しみのために生きることはない意味がありませんでした
-あなたの脳は、スマート取得しますが、あなたの頭はダム取得します
-
-見るために、あまりを行うことがそんなに
-だから、裏通りを取ると間違って何ですか？
-あなたが行っていない場合は、あなたが知っていることは決してないだろう
-あなたが輝くない場合は輝くことは決してないだろう"
-            );
+            assert_eq!(t.text, "ゲート!\na");
         }
-    }
-
-    mod insert {
-        use super::*;
 
         #[test]
-        fn into_empty() {
-            let mut t = Text::new(String::new());
-            assert_eq!(t.br_indexes.0, [0]);
-            t.insert("Hello, World!", GridIndex { row: 0, col: 0 }, &mut ())
+        fn checked() {
+            let mut t = Text::new_utf16("ゲート\nab".into());
+            t.set_checked_columns(true);
+            assert_eq!(
+                t.insert("!", GridIndex { row: 0, col: 4 }, &mut ()),
+                Err(Error::OutOfBoundsColumn {
+                    row: 0,
+                    max: 3,
+                    current: 4
+                })
+            );
+            assert_eq!(
+                t.replace(
+                    "!",
+                    GridIndex { row: 1, col: 0 },
+                    GridIndex { row: 1, col: 3 },
+                    &mut ()
+                ),
+                Err(Error::OutOfBoundsColumn {
+                    row: 1,
+                    max: 2,
+                    current: 3
+                })
+            );
+            assert_eq!(t.text, "ゲート\nab");
+
+            // the end of a row is still a valid position
+            t.insert("!", GridIndex { row: 0, col: 3 }, &mut ())
                 .unwrap();
+            t.delete(
+                GridIndex { row: 1, col: 0 },
+                GridIndex { row: 1, col: 2 },
+                &mut (),
+            )
+            .unwrap();
+            assert_eq!(t.text, "ゲート!\n");
 
-            assert_eq!(t.text, "Hello, World!");
-            assert_eq!(t.br_indexes, [0]);
+            t.set_checked_columns(false);
+            t.insert("?", GridIndex { row: 0, col: 10 }, &mut ())
+                .unwrap();
+            assert_eq!(t.text, "ゲート!?\n");
         }
 
         #[test]
-        fn in_start() {
-            let mut t = Text::new(String::from("Apples"));
-            assert_eq!(t.br_indexes.0, [0]);
-            t.insert("Hello, World!", GridIndex { row: 0, col: 0 }, &mut ())
-                .unwrap();
-
-            assert_eq!(t.text, "Hello, World!Apples");
+        fn new_row_unchanged_on_error() {
+            let mut t = Text::new("a".into());
+            t.set_checked_columns(true);
+            assert!(t
+                .replace(
+                    "!",
+                    GridIndex { row: 1, col: 0 },
+                    GridIndex { row: 0, col: 9 },
+                    &mut ()
+                )
+                .is_err());
+            assert!(t
+                .delete(
+                    GridIndex { row: 1, col: 0 },
+                    GridIndex { row: 0, col: 9 },
+                    &mut ()
+                )
+                .is_err());
+            assert!(t
+                .insert("!", GridIndex { row: 1, col: 1 }, &mut ())
+                .is_err());
+            assert!(t
+                .insert_char('!', GridIndex { row: 1, col: 1 }, &mut ())
+                .is_err());
+            assert_eq!(t.text, "a");
             assert_eq!(t.br_indexes, [0]);
         }
 
         #[test]
-        fn in_end() {
-            let mut t = Text::new(String::from("Apples"));
-            assert_eq!(t.br_indexes.0, [0]);
-            t.insert("Hello, \nWorld!\n", GridIndex { row: 0, col: 6 }, &mut ())
-                .unwrap();
+        fn checked_utf8() {
+            let mut t = Text::new("abc".into());
+            t.set_checked_columns(true);
+            assert_eq!(
+                t.insert("!", GridIndex { row: 0, col: 4 }, &mut ()),
+                Err(Error::OutOfBoundsColumn {
+                    row: 0,
+                    max: 3,
+                    current: 4
+                })
+            );
+        }
+    }
 
-            assert_eq!(t.text, "ApplesHello, \nWorld!\n");
-            assert_eq!(t.br_indexes, [0, 13, 20]);
+    mod swap_buffer {
+        use super::*;
+
+        #[test]
+        fn swap() {
+            let mut t = Text::new("Hello, World!\nApples\n Oranges\nPears".into());
+            let old_ptr = t.text.as_ptr();
+            let old_cap = t.text.capacity();
+
+            let new = String::from("シュタインズ\r\nゲート");
+            let new_ptr = new.as_ptr();
+            let old = t.swap_buffer(new, &mut ()).unwrap();
+
+            assert_eq!(old, "Hello, World!\nApples\n Oranges\nPears");
+            assert_eq!(old.as_ptr(), old_ptr);
+            assert_eq!(old.capacity(), old_cap);
+            assert_eq!(t.text.as_ptr(), new_ptr);
+            assert_eq!(t, Text::new("シュタインズ\r\nゲート".into()));
+            assert_eq!(t.br_indexes, [0, 19]);
+            assert_eq!(t.old_br_indexes, [0, 13, 20, 29]);
         }
+    }
+
+    mod snapshot {
+        use crate::{
+            error::Error,
+            updateables::{ChangeContext, UpdateContext},
+        };
+
+        use super::*;
 
         #[test]
-        fn end_of_multiline() {
-            let mut t = Text::new(String::from("Apples\nBashdjad\nashdkasdh\nasdsad"));
-            assert_eq!(t.br_indexes.0, [0, 6, 15, 25]);
-            t.insert("Hello, \nWorld!\n", GridIndex { row: 3, col: 2 }, &mut ())
+        fn restore() {
+            let mut t = Text::new("Hello, World!\nApples\n Oranges\nPears".into());
+            t.insert("シュタインズ\n", GridIndex { row: 1, col: 0 }, &mut ())
                 .unwrap();
+            let expected = t.clone();
+            let snapshot = t.snapshot();
 
-            assert_eq!(
-                t.text,
-                "Apples\nBashdjad\nashdkasdh\nasHello, \nWorld!\ndsad"
-            );
-            assert_eq!(t.br_indexes, [0, 6, 15, 25, 35, 42]);
+            t.delete(
+                GridIndex { row: 0, col: 3 },
+                GridIndex { row: 3, col: 2 },
+                &mut (),
+            )
+            .unwrap();
+            assert_eq!(t.text, "Helranges\nPears");
+
+            let mut old = String::new();
+            let mut new = String::new();
+            t.restore(snapshot, &mut |ctx: UpdateContext| {
+                let ChangeContext::ReplaceFull { text } = ctx.change else {
+                    panic!("restore should provide a ReplaceFull context");
+                };
+                new.push_str(text);
+                old.push_str(ctx.old_str);
+                assert_eq!(ctx.old_breaklines, &[0, 9]);
+                Ok(())
+            })
+            .unwrap();
+
+            assert_eq!(old, "Helranges\nPears");
+            assert_eq!(new, expected.text);
+            assert_eq!(t, expected);
+            assert_eq!(t.br_indexes, [0, 13, 32, 39, 48]);
         }
 
         #[test]
-        fn multi_line_in_middle() {
-            let mut t = Text::new(String::from("ABC\nDEF"));
-            assert_eq!(t.br_indexes.0, [0, 3]);
-            t.insert("Hello,\n World!\n", GridIndex { row: 1, col: 1 }, &mut ())
-                .unwrap();
-
-            assert_eq!(t.text, "ABC\nDHello,\n World!\nEF");
-            assert_eq!(t.br_indexes.0, [0, 3, 11, 19]);
+        fn transaction_rollback() {
+            let mut t = Text::new("Hello\nWorld".into());
+            let expected = t.clone();
+            let mut restored = false;
+            let err = t
+                .transaction(
+                    &mut |ctx: UpdateContext| {
+                        if let ChangeContext::ReplaceFull { text } = ctx.change {
+                            assert_eq!(text, "Hello\nWorld");
+                            assert_eq!(ctx.old_str, "Hello,\nWorld");
+                            restored = true;
+                        }
+                        Ok(())
+                    },
+                    |t, u| {
+                        t.insert(",", GridIndex { row: 0, col: 5 }, u)?;
+                        t.insert("!", GridIndex { row: 5, col: 0 }, u)?;
+                        Ok(())
+                    },
+                )
+                .unwrap_err();
+
+            assert!(matches!(err, Error::OutOfBoundsRow { .. }));
+            assert!(restored);
+            assert_eq!(t, expected);
         }
 
         #[test]
-        fn single_line_in_middle() {
-            let mut t = Text::new(String::from("ABC\nDEF"));
-            assert_eq!(t.br_indexes.0, [0, 3]);
-            t.insert("Hello, World!", GridIndex { row: 0, col: 1 }, &mut ())
-                .unwrap();
+        fn transaction_commit() {
+            let mut t = Text::new("Hello\nWorld".into());
+            t.transaction(&mut (), |t, u| {
+                t.insert(",", GridIndex { row: 0, col: 5 }, u)?;
+                t.insert("!", GridIndex { row: 1, col: 5 }, u)?;
+                Ok(())
+            })
+            .unwrap();
+            assert_eq!(t.text, "Hello,\nWorld!");
+            assert_eq!(t.br_indexes, [0, 6]);
+        }
+    }
 
-            assert_eq!(t.text, "AHello, World!BC\nDEF");
-            assert_eq!(t.br_indexes.0, [0, 16]);
+    mod crlf {
+        use super::*;
+        use crate::{
+            core::eol_indexes::EolIndexes,
+            error::Result,
+            updateables::{ChangeContext, UpdateContext},
+        };
+
+        /// Checks that the breaklines provided to an [`Updateable`] match the edited string.
+        fn check_breaklines(expected: &'static str) -> impl FnMut(UpdateContext) -> Result<()> {
+            move |ctx: UpdateContext| {
+                assert_eq!(ctx.breaklines, &EolIndexes::new(expected));
+                Ok(())
+            }
         }
 
         #[test]
-        fn multi_byte() {
-            let mut t = Text::new("シュタインズ・ゲートは素晴らしいです。".into());
-            assert_eq!(t.br_indexes.0, [0]);
-            t.insert(
-                "\nHello, ゲートWorld!\n",
-                GridIndex { row: 0, col: 3 },
-                &mut (),
+        fn delete_joins_cr_and_lf() {
+            let mut t = Text::new("a\rb\nc".into());
+            t.clear_dirty();
+            t.delete(
+                GridIndex { row: 1, col: 0 },
+                GridIndex { row: 1, col: 1 },
+                &mut check_breaklines("a\r\nc"),
             )
             .unwrap();
+            assert_eq!(t.text, "a\r\nc");
+            assert_eq!(t.br_indexes, [0, 2]);
+            assert_eq!(t.lines().collect::<Vec<_>>(), ["a", "c"]);
+            assert_eq!(t.dirty_rows().collect::<Vec<_>>(), [0]);
+            assert!(t.verify_invariants());
+        }
 
-            assert_eq!(
-                t.text,
-                "シ\nHello, ゲートWorld!\nュタインズ・ゲートは素晴らしいです。"
-            );
-            assert_eq!(t.br_indexes, [0, 3, 26]);
-            assert_eq!(
-                &t.text[t.br_indexes.0[1] + 1..t.br_indexes.0[2]],
-                "Hello, ゲートWorld!"
-            );
-            assert_eq!(
-                &t.text[t.br_indexes.0[2] + 1..],
-                "ュタインズ・ゲートは素晴らしいです。"
+        #[test]
+        fn delete_across_rows_joins_cr_and_lf() {
+            let mut t = Text::new("a\rb\r\nc\rd\ne".into());
+            t.delete(
+                GridIndex { row: 1, col: 0 },
+                GridIndex { row: 3, col: 1 },
+                &mut check_breaklines("a\r\ne"),
             )
+            .unwrap();
+            assert_eq!(t.text, "a\r\ne");
+            assert_eq!(t.br_indexes, [0, 2]);
+            assert_eq!(t.lines().collect::<Vec<_>>(), ["a", "e"]);
         }
 
         #[test]
-        fn long_text_single_byte() {
-            let mut t = Text::new(
-                "1234567\nABCD\nHELLO\nWORLD\nSOMELONGLINEFORTESTINGVARIOUSCASES\nAHAHHAHAH".into(),
-            );
+        fn delete_crlf() {
+            let mut t = Text::new("a\r\nb".into());
+            t.delete(
+                GridIndex { row: 0, col: 1 },
+                GridIndex { row: 1, col: 0 },
+                &mut (),
+            )
+            .unwrap();
+            assert_eq!(t.text, "ab");
+            assert_eq!(t.br_indexes, [0]);
+        }
 
-            assert_eq!(t.br_indexes.0, [0, 7, 12, 18, 24, 59]);
+        #[test]
+        fn replace_joins_cr_and_lf() {
+            let mut t = Text::new("a\rb\nc".into());
+            t.replace(
+                "\nx",
+                GridIndex { row: 1, col: 0 },
+                GridIndex { row: 1, col: 1 },
+                &mut check_breaklines("a\r\nx\nc"),
+            )
+            .unwrap();
+            assert_eq!(t.text, "a\r\nx\nc");
+            assert_eq!(t.br_indexes, [0, 2, 4]);
 
-            t.insert(
-                "Apple Juice\nBananaMilkshake\nWobbly",
-                GridIndex { row: 4, col: 5 },
-                &mut (),
+            let mut t = Text::new("a\rb\nc".into());
+            t.replace(
+                "x\r",
+                GridIndex { row: 1, col: 0 },
+                GridIndex { row: 1, col: 1 },
+                &mut check_breaklines("a\rx\r\nc"),
             )
             .unwrap();
+            assert_eq!(t.text, "a\rx\r\nc");
+            assert_eq!(t.br_indexes, [0, 1, 4]);
 
-            assert_eq!(
-                t.text,
-                "1234567\nABCD\nHELLO\nWORLD\nSOMELApple Juice\nBananaMilkshake\nWobblyONGLINEFORTESTINGVARIOUSCASES\nAHAHHAHAH"
-            );
-            assert_eq!(t.br_indexes, [0, 7, 12, 18, 24, 41, 57, 93]);
+            let mut t = Text::new("a\rb\nc".into());
+            t.clear_dirty();
+            t.replace(
+                "\nx\r",
+                GridIndex { row: 1, col: 0 },
+                GridIndex { row: 1, col: 1 },
+                &mut check_breaklines("a\r\nx\r\nc"),
+            )
+            .unwrap();
+            assert_eq!(t.text, "a\r\nx\r\nc");
+            assert_eq!(t.br_indexes, [0, 2, 5]);
+            assert_eq!(t.lines().collect::<Vec<_>>(), ["a", "x", "c"]);
+            assert_eq!(t.dirty_rows().collect::<Vec<_>>(), [0, 1]);
 
-            assert_eq!(
-                &t.text[t.br_indexes.row_start(0).unwrap()..t.br_indexes.0[1]],
-                "1234567"
-            );
-            assert_eq!(
-                &t.text[t.br_indexes.row_start(1).unwrap()..t.br_indexes.0[2]],
-                "ABCD"
-            );
-            assert_eq!(
-                &t.text[t.br_indexes.row_start(2).unwrap()..t.br_indexes.0[3]],
-                "HELLO"
-            );
-            assert_eq!(
-                &t.text[t.br_indexes.row_start(3).unwrap()..t.br_indexes.0[4]],
-                "WORLD"
-            );
-            assert_eq!(
-                &t.text[t.br_indexes.row_start(4).unwrap()..t.br_indexes.0[5]],
-                "SOMELApple Juice"
-            );
-            assert_eq!(
-                &t.text[t.br_indexes.row_start(5).unwrap()..t.br_indexes.0[6]],
-                "BananaMilkshake"
-            );
-            assert_eq!(
-                &t.text[t.br_indexes.row_start(6).unwrap()..t.br_indexes.0[7]],
-                "WobblyONGLINEFORTESTINGVARIOUSCASES"
-            );
-            assert_eq!(&t.text[t.br_indexes.row_start(7).unwrap()..], "AHAHHAHAH");
+            let mut t = Text::new("a\rb\nc".into());
+            t.replace(
+                "",
+                GridIndex { row: 1, col: 0 },
+                GridIndex { row: 1, col: 1 },
+                &mut check_breaklines("a\r\nc"),
+            )
+            .unwrap();
+            assert_eq!(t.text, "a\r\nc");
+            assert_eq!(t.br_indexes, [0, 2]);
         }
 
         #[test]
-        fn long_text_multi_byte() {
-            let mut t = Text::new(
-                "シュタ\nHello, ゲートWorld!\nインズ・ゲートは素晴らしいです。\nこんにちは世界！"
-                    .to_string(),
-            );
+        fn insert_cr_before_lf() {
+            let mut t = Text::new("a\nb".into());
+            t.clear_dirty();
+            t.insert(
+                "\r",
+                GridIndex { row: 0, col: 1 },
+                &mut check_breaklines("a\r\nb"),
+            )
+            .unwrap();
+            assert_eq!(t.text, "a\r\nb");
+            assert_eq!(t.line_count(), 2);
+            assert_eq!(t.br_indexes, [0, 2]);
+            assert_eq!(t.dirty_rows().collect::<Vec<_>>(), [0]);
 
-            assert_eq!(t.br_indexes, [0, 9, 32, 81]);
+            let mut t = Text::new("\nb".into());
+            t.insert(
+                "x\r",
+                GridIndex { row: 0, col: 0 },
+                &mut check_breaklines("x\r\nb"),
+            )
+            .unwrap();
+            assert_eq!(t.text, "x\r\nb");
+            assert_eq!(t.br_indexes, [0, 2]);
+        }
 
+        #[test]
+        fn insert_lf_after_cr() {
+            let mut t = Text::new("a\rb".into());
+            t.clear_dirty();
             t.insert(
-                "Olá, mundo!\nWaltuh Put the fork away Waltuh.",
-                GridIndex { row: 2, col: 3 },
-                &mut (),
+                "\nx\n",
+                GridIndex { row: 1, col: 0 },
+                &mut check_breaklines("a\r\nx\nb"),
             )
             .unwrap();
+            assert_eq!(t.text, "a\r\nx\nb");
+            assert_eq!(t.lines().collect::<Vec<_>>(), ["a", "x", "b"]);
+            assert_eq!(t.br_indexes, [0, 2, 4]);
+            assert_eq!(t.dirty_rows().collect::<Vec<_>>(), [0, 1, 2]);
+        }
 
-            assert_eq!(
-                t.text,
-                "シュタ\nHello, ゲートWorld!\nイOlá, mundo!\nWaltuh Put the fork away Waltuh.ンズ・ゲートは素晴らしいです。\nこんにちは世界！"
-            );
+        #[test]
+        fn insert_char() {
+            let mut t = Text::new("a\nb\rc".into());
+            t.insert_char(
+                '\r',
+                GridIndex { row: 0, col: 1 },
+                &mut |ctx: UpdateContext| {
+                    let ChangeContext::Insert {
+                        inserted_br_indexes,
+                        ..
+                    } = ctx.change
+                    else {
+                        panic!("expected an insert");
+                    };
+                    assert!(inserted_br_indexes.is_empty());
+                    Ok(())
+                },
+            )
+            .unwrap();
+            assert_eq!(t.text, "a\r\nb\rc");
+            assert_eq!(t.br_indexes, [0, 2, 4]);
 
-            assert_eq!(t.br_indexes, [0, 9, 32, 48, 126]);
+            t.insert_char(
+                '\n',
+                GridIndex { row: 2, col: 0 },
+                &mut check_breaklines("a\r\nb\r\nc"),
+            )
+            .unwrap();
+            assert_eq!(t.text, "a\r\nb\r\nc");
+            assert_eq!(t.br_indexes, [0, 2, 5]);
+            assert!(t.verify_invariants());
+        }
 
-            assert_eq!(
-                &t.text[t.br_indexes.row_start(0).unwrap()..t.br_indexes.0[1]],
-                "シュタ"
-            );
-            assert_eq!(
-                &t.text[t.br_indexes.row_start(1).unwrap()..t.br_indexes.0[2]],
-                "Hello, ゲートWorld!"
-            );
-            assert_eq!(
-                &t.text[t.br_indexes.row_start(2).unwrap()..t.br_indexes.0[3]],
-                "イOlá, mundo!"
-            );
-            assert_eq!(
-                &t.text[t.br_indexes.row_start(3).unwrap()..t.br_indexes.0[4]],
-                "Waltuh Put the fork away Waltuh.ンズ・ゲートは素晴らしいです。"
-            );
-            assert_eq!(
-                &t.text[t.br_indexes.row_start(4).unwrap()..],
-                "こんにちは世界！"
-            );
+        #[test]
+        fn append_text() {
+            let mut t = Text::new("a\r".into());
+            t.append_text(
+                &Text::new("\nb\n".into()),
+                &mut check_breaklines("a\r\nb\n"),
+            )
+            .unwrap();
+            assert_eq!(t.text, "a\r\nb\n");
+            assert_eq!(t.br_indexes, [0, 2, 4]);
+        }
+
+        #[test]
+        fn cr_at_start() {
+            let mut t = Text::new("\rb\nc".into());
+            t.delete(
+                GridIndex { row: 1, col: 0 },
+                GridIndex { row: 1, col: 1 },
+                &mut (),
+            )
+            .unwrap();
+            assert_eq!(t.text, "\r\nc");
+            assert_eq!(t.br_indexes, [0, 1]);
+            assert!(t.verify_invariants());
         }
     }
 
-    mod replace {
+    mod dirty_rows {
         use super::*;
 
         #[test]
-        fn in_line_start() {
-            let mut t = Text::new("Hello, World!\nBye World!\nhahaFunny".into());
-
-            assert_eq!(t.br_indexes, [0, 13, 24]);
-
-            t.replace(
-                "This Should replace some stuff",
+        fn accumulates_until_cleared() {
+            let mut t = Text::new("Hello, World!\nApples\n Oranges\nPears".into());
+            assert_eq!(t.dirty_rows().count(), 0);
+            t.insert("123", GridIndex { row: 3, col: 1 }, &mut ())
+                .unwrap();
+            t.delete(
+                GridIndex { row: 0, col: 1 },
                 GridIndex { row: 0, col: 3 },
-                GridIndex { row: 0, col: 5 },
                 &mut (),
             )
             .unwrap();
+            assert_eq!(t.dirty_rows().collect::<Vec<_>>(), [0, 3]);
 
-            assert_eq!(
-                t.text,
-                "HelThis Should replace some stuff, World!\nBye World!\nhahaFunny"
-            );
-            assert_eq!(t.br_indexes, [0, 41, 52]);
+            t.clear_dirty();
+            assert_eq!(t.dirty_rows().count(), 0);
         }
 
         #[test]
-        fn in_line_middle() {
-            let mut t = Text::new("Hello, World!\nBye World!\nhahaFunny".into());
-
-            assert_eq!(t.br_indexes, [0, 13, 24]);
+        fn shifted_by_new_rows() {
+            let mut t = Text::new("Hello, World!\nApples\n Oranges\nPears".into());
+            t.insert("123", GridIndex { row: 3, col: 1 }, &mut ())
+                .unwrap();
+            t.insert("\n\n", GridIndex { row: 0, col: 1 }, &mut ())
+                .unwrap();
+            assert_eq!(t.dirty_rows().collect::<Vec<_>>(), [0, 1, 2, 5]);
+            assert_eq!(t.get_row(5), Some("P123ears"));
 
             t.replace(
-                "This Should replace some stuff",
-                GridIndex { row: 1, col: 3 },
-                GridIndex { row: 1, col: 5 },
+                "Bye",
+                GridIndex { row: 0, col: 0 },
+                GridIndex { row: 3, col: 3 },
                 &mut (),
             )
             .unwrap();
-
-            assert_eq!(
-                t.text,
-                "Hello, World!\nByeThis Should replace some stufforld!\nhahaFunny"
-            );
-            assert_eq!(t.br_indexes, [0, 13, 52]);
+            assert_eq!(t.dirty_rows().collect::<Vec<_>>(), [0, 2]);
+            assert_eq!(t.get_row(2), Some("P123ears"));
         }
+    }
+
+    mod unicode_eol {
+        use super::*;
+        use crate::core::{eol_indexes::EolIndexes, lines::EolMode};
 
         #[test]
-        fn in_line_end() {
-            let mut t = Text::new("Hello, World!\nBye World!\nhahaFunny".into());
+        fn line_separator_splits_rows() {
+            let t = Text::new_unicode_eol("Hello\u{2028}World".into());
+            assert_eq!(t.eol_mode(), EolMode::Unicode);
+            assert_eq!(t.br_indexes, [0, 7]);
+            assert_eq!(t.line_count(), 2);
+            assert_eq!(t.get_row(0), Some("Hello"));
+            assert_eq!(t.get_row(1), Some("World"));
+            assert_eq!(t.lines().collect::<Vec<_>>(), ["Hello", "World"]);
+            assert!(t.verify_invariants());
+        }
 
-            assert_eq!(t.br_indexes, [0, 13, 24]);
-            t.replace(
-                "Wappow! There he stood.",
-                GridIndex { row: 0, col: 4 },
-                GridIndex { row: 0, col: 13 },
-                &mut (),
-            )
-            .unwrap();
+        #[test]
+        fn default_ignores_unicode_breaks() {
+            let t = Text::new("Hello\u{2028}World\u{85}".into());
+            assert_eq!(t.eol_mode(), EolMode::CrLf);
+            assert_eq!(t.line_count(), 1);
+            assert!(!t.ends_with_newline());
+        }
 
-            assert_eq!(t.text, "HellWappow! There he stood.\nBye World!\nhahaFunny");
-            assert_eq!(t.br_indexes, [0, 27, 38]);
+        #[test]
+        fn mixed_breaks() {
+            let t = Text::new_unicode_eol("a\u{85}b\u{2029}c\r\nd\u{2028}".into());
+            assert_eq!(t.lines().collect::<Vec<_>>(), ["a", "b", "c", "d", ""]);
+            assert_eq!(t.row_byte_range(1), Some(3..4));
+            assert_eq!(t.row_byte_range_with_eol(1), Some(3..7));
+            assert!(t.ends_with_newline());
         }
 
         #[test]
-        fn across_first_line() {
-            let mut t = Text::new("Hello, World!\nBye World!\nhahaFunny".into());
+        fn edits() {
+            let mut t = Text::new_unicode_eol("ab\u{2028}cd".into());
+            t.insert("x\u{2029}y", GridIndex { row: 1, col: 1 }, &mut ())
+                .unwrap();
+            assert_eq!(t.text, "ab\u{2028}cx\u{2029}yd");
+            assert_eq!(t.get_row(2), Some("yd"));
+            assert!(t.verify_invariants());
+
+            t.insert_char('\u{85}', GridIndex { row: 0, col: 1 }, &mut ())
+                .unwrap();
+            assert_eq!(t.lines().collect::<Vec<_>>(), ["a", "b", "cx", "yd"]);
+            assert!(t.verify_invariants());
 
-            assert_eq!(t.br_indexes, [0, 13, 24]);
             t.replace(
-                "This replaced with the content in the first line\n and second line",
-                GridIndex { row: 0, col: 5 },
-                GridIndex { row: 1, col: 3 },
+                "\u{2028}",
+                GridIndex { row: 1, col: 1 },
+                GridIndex { row: 2, col: 2 },
                 &mut (),
             )
             .unwrap();
+            assert_eq!(t.lines().collect::<Vec<_>>(), ["a", "b", "", "yd"]);
+            assert!(t.verify_invariants());
 
-            assert_eq!(t.text, "HelloThis replaced with the content in the first line\n and second line World!\nhahaFunny");
-            assert_eq!(t.br_indexes, [0, 53, 77]);
-        }
-
-        #[test]
-        fn across_start_and_end_line() {
-            let mut t = Text::new("Hello, World!\nBye World!\nhahaFunny\nInteresting!".into());
-
-            assert_eq!(t.br_indexes, [0, 13, 24, 34]);
-            t.replace(
-                "What a wonderful world!\nWowzers\nSome Random text",
-                GridIndex { row: 0, col: 3 },
-                GridIndex { row: 3, col: 6 },
+            t.delete(
+                GridIndex { row: 0, col: 1 },
+                GridIndex { row: 3, col: 0 },
                 &mut (),
             )
             .unwrap();
+            assert_eq!(t.text, "ayd");
+            assert!(t.verify_invariants());
+        }
+
+        #[test]
+        fn kept_by_derived_texts() {
+            let mut t = Text::new_unicode_eol("a\u{2028}b".into());
+            let sub = t
+                .sub_text(GridIndex { row: 0, col: 0 }, GridIndex { row: 1, col: 1 })
+                .unwrap();
+            assert_eq!(sub.eol_mode(), EolMode::Unicode);
+            assert_eq!(sub.line_count(), 2);
 
+            t.replace_full("c\u{2029}d".into(), &mut ()).unwrap();
             assert_eq!(
-                t.text,
-                "HelWhat a wonderful world!\nWowzers\nSome Random textsting!"
+                t.br_indexes,
+                EolIndexes::with_eol_mode(&t.text, EolMode::Unicode)
             );
 
-            assert_eq!(t.br_indexes, [0, 26, 34]);
+            // the line breaks are searched again when appending a text with another mode
+            t.append_text(&Text::new("\u{85}e".into()), &mut ())
+                .unwrap();
+            assert_eq!(t.lines().collect::<Vec<_>>(), ["c", "d", "e"]);
+            assert!(t.verify_invariants());
         }
+    }
+
+    mod lf_only {
+        use super::*;
+        use crate::core::lines::EolMode;
 
         #[test]
-        fn across_end_line() {
-            let mut t = Text::new("Hello, World!\nBye World!\nhahaFunny\nInteresting!".into());
+        fn lf_only() {
+            let t = Text::new_lf_only("a\r\nb\rc\n".into());
+            assert_eq!(t.eol_mode(), EolMode::LfOnly);
+            assert_eq!(t.br_indexes, [0, 2, 6]);
+            assert_eq!(t.lines().collect::<Vec<_>>(), ["a", "b\rc", ""]);
+            assert!(t.verify_invariants());
+        }
 
-            assert_eq!(t.br_indexes, [0, 13, 24, 34]);
-            t.replace(
-                "What a wonderful world!\nWowzers\nSome Random text",
-                GridIndex { row: 2, col: 3 },
-                GridIndex { row: 3, col: 6 },
+        #[test]
+        fn edits() {
+            let mut t = Text::new_lf_only("a\rb\nc".into());
+            // joining the "\r" and "\n" does not change the row count
+            t.delete(
+                GridIndex { row: 0, col: 2 },
+                GridIndex { row: 0, col: 3 },
                 &mut (),
             )
             .unwrap();
+            assert_eq!(t.text, "a\r\nc");
+            assert_eq!(t.br_indexes, [0, 2]);
+            assert!(t.verify_invariants());
 
-            assert_eq!(
-                t.text,
-                "Hello, World!\nBye World!\nhahWhat a wonderful world!\nWowzers\nSome Random textsting!"
-            );
+            t.insert_char('\r', GridIndex { row: 1, col: 0 }, &mut ())
+                .unwrap();
+            t.insert("\r\n", GridIndex { row: 1, col: 0 }, &mut ())
+                .unwrap();
+            assert_eq!(t.text, "a\r\n\r\n\rc");
+            assert_eq!(t.br_indexes, [0, 2, 4]);
+            assert!(t.verify_invariants());
+        }
+    }
 
-            assert_eq!(t.br_indexes, [0, 13, 24, 51, 59]);
+    mod bom {
+        use super::*;
+
+        const S: &str = "\u{FEFF}ab\ncd";
+
+        #[test]
+        fn removed() {
+            let t = Text::new_with_bom_handling(S.into());
+            assert!(t.has_bom());
+            assert_eq!(t.text, "ab\ncd");
+            assert_eq!(t.br_indexes, [0, 2]);
+            assert_eq!(t.get_row(0), Some("ab"));
         }
 
         #[test]
-        fn middle_in_line() {
-            let mut t = Text::new("Hello, World!\nBye World!\nhahaFunny\nInteresting!".into());
+        fn missing() {
+            let t = Text::new_with_bom_handling("ab\ncd".into());
+            assert!(!t.has_bom());
+            assert_eq!(t, Text::new("ab\ncd".into()));
+
+            // without handling, the BOM is a part of the first row
+            let t = Text::new(S.into());
+            assert!(!t.has_bom());
+            assert_eq!(t.get_row(0), Some("\u{FEFF}ab"));
+        }
 
-            assert_eq!(t.br_indexes, [0, 13, 24, 34]);
-            t.replace(
-                "I am in the middle!\nNo one can stop me.",
-                GridIndex { row: 2, col: 1 },
-                GridIndex { row: 2, col: 5 },
-                &mut (),
-            )
-            .unwrap();
+        #[test]
+        fn write_to() {
+            let mut t = Text::new_with_bom_handling(S.into());
+            t.insert("x", GridIndex { row: 0, col: 0 }, &mut ())
+                .unwrap();
+            let mut buf = vec![];
+            t.write_to(&mut buf).unwrap();
+            assert_eq!(buf, "\u{FEFF}xab\ncd".as_bytes());
 
-            assert_eq!(t.text, "Hello, World!\nBye World!\nhI am in the middle!\nNo one can stop me.unny\nInteresting!");
-            assert_eq!(t.br_indexes, [0, 13, 24, 45, 69]);
+            let mut buf = vec![];
+            Text::new("ab".into()).write_to(&mut buf).unwrap();
+            assert_eq!(buf, b"ab");
         }
+    }
 
-        #[test]
-        fn middle_no_br_replacement() {
-            let mut t = Text::new("Hello, World!\nBye World!\nhahaFunny\nInteresting!".into());
+    mod updateable_error {
+        use super::*;
+        use crate::{
+            error::{Error, Result},
+            updateables::{UpdateContext, Updateable},
+        };
 
-            assert_eq!(t.br_indexes, [0, 13, 24, 34]);
-            t.replace(
-                "Look ma, no line breaks",
-                GridIndex { row: 1, col: 3 },
-                GridIndex { row: 1, col: 6 },
-                &mut (),
-            )
-            .unwrap();
+        fn fail(_: UpdateContext) -> Result<()> {
+            Err(Error::OutOfBoundsRow { max: 0, current: 0 })
+        }
 
-            assert_eq!(
-                t.text,
-                "Hello, World!\nByeLook ma, no line breaksrld!\nhahaFunny\nInteresting!"
-            );
-            assert_eq!(t.br_indexes, [0, 13, 44, 54]);
+        /// Fails without requesting the old state, so the indexes are computed again on failure.
+        struct Fail;
+        impl Updateable for Fail {
+            fn needs_old_state(&self) -> bool {
+                false
+            }
+
+            fn update(&mut self, ctx: UpdateContext) -> Result<()> {
+                fail(ctx)
+            }
+        }
+
+        fn edits<U: Updateable>(u: &mut U) {
+            let edits: [fn(&mut Text, &mut U) -> Result<()>; 7] = [
+                |t, u| {
+                    t.delete(
+                        GridIndex { row: 0, col: 2 },
+                        GridIndex { row: 1, col: 1 },
+                        u,
+                    )
+                    .map(drop)
+                },
+                |t, u| t.insert("a\nb", GridIndex { row: 1, col: 0 }, u).map(drop),
+                // a line break is pushed when normalizing the position
+                |t, u| t.insert("a\nb", GridIndex { row: 2, col: 0 }, u).map(drop),
+                |t, u| t.insert_char('\n', GridIndex { row: 0, col: 1 }, u),
+                |t, u| {
+                    t.replace(
+                        "\n",
+                        GridIndex { row: 0, col: 0 },
+                        GridIndex { row: 1, col: 1 },
+                        u,
+                    )
+                    .map(drop)
+                },
+                |t, u| t.replace_full("abc\n".into(), u),
+                |t, u| t.append_text(&Text::new("\nabc".into()), u),
+            ];
+
+            for edit in edits {
+                let mut t = Text::new("ab\r\ncd".into());
+                let expected = t.clone();
+                assert!(edit(&mut t, u).is_err());
+                assert_eq!(t, expected);
+                assert!(t.verify_invariants());
+            }
         }
 
         #[test]
-        fn start_no_br_replacement() {
-            let mut t = Text::new("Hello, World!\nBye World!\nhahaFunny\nInteresting!".into());
+        fn text_is_unchanged() {
+            edits(&mut fail);
+        }
 
-            assert_eq!(t.br_indexes, [0, 13, 24, 34]);
-            t.replace(
-                "Look ma, no line breaks",
-                GridIndex { row: 0, col: 3 },
-                GridIndex { row: 0, col: 8 },
-                &mut (),
-            )
-            .unwrap();
+        #[test]
+        fn text_is_unchanged_without_old_state() {
+            edits(&mut Fail);
+        }
+    }
 
-            assert_eq!(
-                t.text,
-                "HelLook ma, no line breaksorld!\nBye World!\nhahaFunny\nInteresting!"
-            );
-            assert_eq!(t.br_indexes, [0, 31, 42, 52]);
+    mod updateable_veto {
+        use std::ops::Range;
+
+        use super::*;
+        use crate::{
+            error::Result,
+            updateables::{ChangeContext, UpdateContext, Updateable},
+        };
+
+        /// Rejects any edit that touches the locked rows.
+        struct Guard {
+            locked: Range<usize>,
+            updated: usize,
+        }
+
+        impl Updateable for Guard {
+            fn should_apply(&mut self, ctx: &UpdateContext) -> bool {
+                let rows = match ctx.change {
+                    ChangeContext::Insert { position, .. } => position.row..position.row + 1,
+                    ChangeContext::Delete { start, end }
+                    | ChangeContext::Replace { start, end, .. } => start.row..end.row + 1,
+                    ChangeContext::ReplaceFull { .. } => return false,
+                };
+                rows.end <= self.locked.start || self.locked.end <= rows.start
+            }
+
+            fn update(&mut self, _: UpdateContext) -> Result<()> {
+                self.updated += 1;
+                Ok(())
+            }
         }
 
         #[test]
-        fn end_no_br_replacement() {
-            let mut t = Text::new("Hello, World!\nBye World!\nhahaFunny\nInteresting!".into());
+        fn locked_region() {
+            let mut t = Text::new("ab\ncd\nef\ngh".into());
+            let mut guard = Guard {
+                locked: 1..3,
+                updated: 0,
+            };
+            let expected = t.clone();
 
-            assert_eq!(t.br_indexes, [0, 13, 24, 34]);
+            t.insert("x", GridIndex { row: 1, col: 0 }, &mut guard)
+                .unwrap();
+            t.insert_char('\n', GridIndex { row: 2, col: 1 }, &mut guard)
+                .unwrap();
+            t.delete(
+                GridIndex { row: 0, col: 1 },
+                GridIndex { row: 1, col: 0 },
+                &mut guard,
+            )
+            .unwrap();
             t.replace(
-                "Look ma, no line breaks",
-                GridIndex { row: 3, col: 3 },
-                GridIndex { row: 3, col: 8 },
-                &mut (),
+                "x",
+                GridIndex { row: 2, col: 0 },
+                GridIndex { row: 3, col: 1 },
+                &mut guard,
             )
             .unwrap();
+            t.replace_full("x".into(), &mut guard).unwrap();
+            assert_eq!(t.swap_buffer("x".into(), &mut guard).unwrap(), "x");
+            assert_eq!(t, expected);
+            assert_eq!(guard.updated, 0);
+            assert_eq!(t.dirty_rows().next(), None);
+
+            // edits outside of the locked region are applied
+            t.insert("x", GridIndex { row: 0, col: 0 }, &mut guard)
+                .unwrap();
+            t.delete(
+                GridIndex { row: 3, col: 0 },
+                GridIndex { row: 3, col: 1 },
+                &mut guard,
+            )
+            .unwrap();
+            assert_eq!(t.text, "xab\ncd\nef\nh");
+            assert_eq!(guard.updated, 2);
+            assert!(t.verify_invariants());
+        }
 
-            assert_eq!(
-                t.text,
-                "Hello, World!\nBye World!\nhahaFunny\nIntLook ma, no line breaksing!"
-            );
-            assert_eq!(t.br_indexes, [0, 13, 24, 34]);
+        #[test]
+        fn any_veto_cancels() {
+            let mut t = Text::new("ab\ncd".into());
+            let mut guards = [
+                Guard {
+                    locked: 5..6,
+                    updated: 0,
+                },
+                Guard {
+                    locked: 0..1,
+                    updated: 0,
+                },
+            ];
+            t.insert("x", GridIndex { row: 0, col: 0 }, &mut guards[..])
+                .unwrap();
+            assert_eq!(t.text, "ab\ncd");
+            assert!(guards.iter().all(|g| g.updated == 0));
         }
 
         #[test]
-        fn across_start_and_end_no_br_replacement() {
-            let mut t = Text::new("Hello, World!\nBye World!\nhahaFunny\nInteresting!".into());
+        fn transaction_rollback() {
+            let mut t = Text::new("locked\nfree".into());
+            let mut guard = Guard {
+                locked: 0..1,
+                updated: 0,
+            };
+            let res = t.transaction(&mut guard, |t, u| {
+                t.insert("X", GridIndex { row: 1, col: 0 }, u)?;
+                Err(crate::error::Error::OutOfBoundsRow { max: 0, current: 0 })
+            });
+            assert!(res.is_err());
+            assert_eq!(t.text, "locked\nfree");
+            // the insert and the restore
+            assert_eq!(guard.updated, 2);
+            assert!(t.verify_invariants());
+        }
+    }
 
-            assert_eq!(t.br_indexes, [0, 13, 24, 34]);
-            t.replace(
-                "Look ma, no line breaks",
-                GridIndex { row: 0, col: 3 },
-                GridIndex { row: 3, col: 8 },
-                &mut (),
-            )
-            .unwrap();
+    mod edited_span {
+        use super::*;
+        use crate::{
+            error::Result,
+            updateables::{UpdateContext, Updateable},
+        };
 
-            assert_eq!(t.text, "HelLook ma, no line breaksing!");
-            assert_eq!(t.br_indexes, [0]);
+        #[test]
+        fn insert() {
+            let mut t = Text::new_utf16("ゲート\nab".into());
+            let end = t
+                .insert("12\nシュ", GridIndex { row: 0, col: 1 }, &mut ())
+                .unwrap();
+            assert_eq!(t.text, "ゲ12\nシュート\nab");
+            assert_eq!(end, GridIndex { row: 1, col: 2 });
+
+            let end = t
+                .insert("😀", GridIndex { row: 2, col: 1 }, &mut ())
+                .unwrap();
+            assert_eq!(end, GridIndex { row: 2, col: 3 });
+
+            // inserting at the row count pushes a line break first
+            let end = t
+                .insert("x\n", GridIndex { row: 3, col: 0 }, &mut ())
+                .unwrap();
+            assert_eq!(t.text, "ゲ12\nシュート\na😀b\nx\n");
+            assert_eq!(end, GridIndex { row: 4, col: 0 });
         }
+
         #[test]
-        fn all() {
-            let mut t =
-                Text::new("SomeText\nSome Other Text\nSome somsoemesome\n wowoas \n\n".into());
+        fn delete() {
+            let mut t = Text::new("abc\ndef\nghi".into());
+            let pos = t
+                .delete(
+                    GridIndex { row: 1, col: 1 },
+                    GridIndex { row: 2, col: 2 },
+                    &mut (),
+                )
+                .unwrap();
+            assert_eq!(t.text, "abc\ndi");
+            assert_eq!(pos, GridIndex { row: 1, col: 1 });
+
+            // the start is a part of the joined "\r\n" afterwards
+            let mut t = Text::new("a\rb\nc".into());
+            let pos = t
+                .delete(
+                    GridIndex { row: 1, col: 0 },
+                    GridIndex { row: 1, col: 1 },
+                    &mut (),
+                )
+                .unwrap();
+            assert_eq!(t.text, "a\r\nc");
+            assert_eq!(pos, GridIndex { row: 0, col: 1 });
+        }
 
-            assert_eq!(t.br_indexes, [0, 8, 24, 42, 51, 52]);
-            t.replace(
-                "Hello, World!\nBye World!",
-                GridIndex { row: 0, col: 0 },
-                GridIndex { row: 6, col: 0 },
-                &mut (),
-            )
-            .unwrap();
+        #[test]
+        fn replace() {
+            let mut t = Text::new_utf16("abc\ndef\nghi".into());
+            // shrinking replacement
+            let end = t
+                .replace(
+                    "ゲ\nx",
+                    GridIndex { row: 0, col: 1 },
+                    GridIndex { row: 2, col: 2 },
+                    &mut (),
+                )
+                .unwrap();
+            assert_eq!(t.text, "aゲ\nxi");
+            assert_eq!(end, GridIndex { row: 1, col: 1 });
+
+            let end = t
+                .replace(
+                    "y\r",
+                    GridIndex { row: 0, col: 0 },
+                    GridIndex { row: 0, col: 2 },
+                    &mut (),
+                )
+                .unwrap();
+            assert_eq!(t.text, "y\r\nxi");
+            assert_eq!(end, GridIndex { row: 0, col: 1 });
+        }
 
-            assert_eq!(t.text, "Hello, World!\nBye World!");
-            assert_eq!(t.br_indexes, [0, 13]);
+        #[test]
+        fn vetoed() {
+            struct Veto;
+            impl Updateable for Veto {
+                fn should_apply(&mut self, _: &UpdateContext) -> bool {
+                    false
+                }
+
+                fn update(&mut self, _: UpdateContext) -> Result<()> {
+                    Ok(())
+                }
+            }
+
+            let mut t = Text::new("ab\ncd".into());
+            let (start, end) = (GridIndex { row: 0, col: 1 }, GridIndex { row: 1, col: 1 });
+            assert_eq!(t.insert("x", end, &mut Veto).unwrap(), end);
+            assert_eq!(t.delete(start, end, &mut Veto).unwrap(), start);
+            assert_eq!(t.replace("x", start, end, &mut Veto).unwrap(), end);
         }
     }
 
@@ -1,5 +1,7 @@
 use std::{fmt::Display, num::NonZeroUsize};
 
+use crate::change::GridIndex;
+
 /// A type alias for the libraries result type. ([`Result<(), Error>`])
 pub type Result<T> = std::result::Result<T, Error>;
 
@@ -7,15 +9,54 @@ pub type Result<T> = std::result::Result<T, Error>;
 #[derive(Clone, Debug, PartialEq, Eq)]
 #[non_exhaustive]
 pub enum Error {
-    OutOfBoundsRow { max: usize, current: usize },
-    InBetweenCharBoundries { encoding: Encoding },
+    OutOfBoundsRow {
+        max: usize,
+        current: usize,
+    },
+    OutOfBoundsColumn {
+        row: usize,
+        max: usize,
+        current: usize,
+    },
+    /// The column is in between the boundaries of the encoding.
+    ///
+    /// `col` is the column that was provided for `row`, and `line_len` is the length of the row
+    /// in the [`Text`](crate::core::text::Text)'s expected encoding.
+    InBetweenCharBoundries {
+        encoding: Encoding,
+        row: usize,
+        col: usize,
+        line_len: usize,
+    },
+    /// The byte index is past the end of the string, is not on a char boundary, or is in between
+    /// the bytes of a line break.
+    ///
+    /// `len` is the length of the string in bytes.
+    InvalidByteOffset {
+        byte: usize,
+        len: usize,
+    },
+    /// Two of the provided ranges overlap.
+    ///
+    /// The ranges are ordered by their start positions, and are in the
+    /// [`Text`](crate::core::text::Text)'s expected encoding.
+    OverlappingRanges {
+        first: (GridIndex, GridIndex),
+        second: (GridIndex, GridIndex),
+    },
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
 pub enum Encoding {
     UTF8,
     UTF16,
     UTF32,
+    /// Extended grapheme clusters.
+    #[cfg_attr(docsrs, doc(cfg(feature = "unicode-segmentation")))]
+    #[cfg(feature = "unicode-segmentation")]
+    Graphemes,
 }
 
 impl Display for Error {
@@ -24,10 +65,41 @@ impl Display for Error {
             Self::OutOfBoundsRow { max, current } => {
                 write!(f, "Current max row index is {max}, {current} was provided.")
             }
-            Self::InBetweenCharBoundries { encoding } => {
+            Self::OutOfBoundsColumn { row, max, current } => {
+                write!(
+                    f,
+                    "Current max column index for row {row} is {max}, {current} was provided."
+                )
+            }
+            Self::InBetweenCharBoundries {
+                encoding,
+                row,
+                col,
+                line_len,
+            } => {
+                write!(
+                    f,
+                    "Provided column position {col} of row {row} with length {line_len} is between char boundries for {encoding:?}."
+                )
+            }
+            Self::InvalidByteOffset { byte, len } => {
+                write!(
+                    f,
+                    "Provided byte offset {byte} is not a valid position in a string with length {len}."
+                )
+            }
+            Self::OverlappingRanges { first, second } => {
                 write!(
                     f,
-                    "Provided column position is between char boundries for {encoding:?}."
+                    "Range {}:{}..{}:{} overlaps with range {}:{}..{}:{}.",
+                    first.0.row,
+                    first.0.col,
+                    first.1.row,
+                    first.1.col,
+                    second.0.row,
+                    second.0.col,
+                    second.1.row,
+                    second.1.col
                 )
             }
         }
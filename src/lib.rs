@@ -17,7 +17,8 @@
 //! UTF-32. When starting up an LSP, the client provides encoding it will use. With that
 //! information we can store a function pointer and create new [`Text`][`core::text::Text::update`]'s as needed.
 //!
-//! The example below works the same way to how it is done in `rust-analyzer`.
+//! [`Text::constructor_for`][`core::text::Text::constructor_for`] selects the constructor in the
+//! same way it is done in `rust-analyzer`, preferring UTF-8, then UTF-32, and falling back to UTF-16.
 //! ```
 //! # fn get_client_encoding() -> Option<Vec<PositionEncodingKind>> {None}
 //! use texter::core::text::Text;
@@ -26,21 +27,7 @@
 //! fn decide_encoding() -> fn(String) -> Text {
 //!     // The type provided in client capabilities.
 //!     let encodings: Option<Vec<PositionEncodingKind>> = get_client_encoding();
-//!     let Some(encodings) = encodings else {
-//!         return Text::new_utf16;
-//!     };
-//!
-//!     // Hope that we can use anything other than UTF-16
-//!     for encoding in encodings {
-//!         if encoding == PositionEncodingKind::UTF8 {
-//!             return Text::new;
-//!         } else if encoding == PositionEncodingKind::UTF32 {
-//!             return Text::new_utf32;
-//!         }
-//!     }
-//!
-//!     // Too bad, UTF-16 it is.
-//!     Text::new_utf16
+//!     Text::constructor_for(encodings.as_deref().unwrap_or_default())
 //! }
 //! ```
 //!
@@ -61,11 +48,16 @@
 
 mod utils;
 
+pub mod actions;
 pub mod change;
 pub mod core;
 pub mod error;
 pub mod updateables;
 
+#[cfg_attr(docsrs, doc(cfg(feature = "proptest")))]
+#[cfg(feature = "proptest")]
+pub mod strategies;
+
 #[cfg_attr(docsrs, doc(cfg(feature = "lsp-types")))]
 #[cfg(feature = "lsp-types")]
 pub use lsp_types;
@@ -0,0 +1,119 @@
+//! [`proptest`](mod@proptest) strategies for generating [`Text`]s and [`Change`]s.
+//!
+//! The strategies can be used to property test an [`Updateable`] against the edit engine. The
+//! generated content contains a mix of line breaks and multibyte characters, and the generated
+//! changes only reference positions that exist in the [`Text`] they are generated for.
+//!
+//! ```
+//! use proptest::prelude::*;
+//! use texter::{core::history::History, error::Encoding, strategies};
+//!
+//! proptest! {
+//!     fn undo_restores((text, change) in strategies::text(Encoding::UTF16)
+//!         .prop_flat_map(|t| (Just(t.clone()), strategies::change_for(&t))))
+//!     {
+//!         let mut history = History::new(text.clone());
+//!         history.update(change, &mut ()).unwrap();
+//!         history.undo(&mut ()).unwrap();
+//!         prop_assert_eq!(history.text(), &text);
+//!     }
+//! }
+//! # undo_restores();
+//! ```
+//!
+//! [`Updateable`]: crate::updateables::Updateable
+use proptest::{prelude::*, sample::select};
+
+use crate::{
+    change::{Change, GridIndex},
+    core::text::Text,
+    error::Encoding,
+};
+
+/// The pieces the generated strings are built from.
+const PIECES: &[&str] = &[
+    "a", "b", " ", "\t", "\n", "\r\n", "\r", "é", "ゲ", "😀", "\u{2028}",
+];
+
+/// Returns a [`Strategy`] that generates strings containing a mix of line breaks and multibyte
+/// characters.
+pub fn content() -> impl Strategy<Value = String> {
+    prop::collection::vec(select(PIECES), 0..32).prop_map(|pieces| pieces.concat())
+}
+
+/// Returns a [`Strategy`] that generates [`Text`]s expecting the provided encoding.
+pub fn text(encoding: Encoding) -> impl Strategy<Value = Text> {
+    content().prop_map(move |s| Text::new(s).with_encoding(encoding))
+}
+
+/// Returns every position of the provided [`Text`] that is on a boundary of its expected
+/// encoding.
+fn positions(text: &Text) -> Vec<GridIndex> {
+    text.lines()
+        .enumerate()
+        .flat_map(|(row, line)| {
+            line.char_indices()
+                .map(|(i, _)| i)
+                .chain([line.len()])
+                .map(move |i| (i, text.encoded_len(&line[..i])))
+                .filter(move |&(i, col)| text.col_to_utf8(row, line, col) == Ok(i))
+                .map(move |(_, col)| GridIndex { row, col })
+        })
+        .collect()
+}
+
+/// Returns a [`Strategy`] that generates [`Change`]s that can be applied to the provided
+/// [`Text`].
+///
+/// The positions are in the [`Text`]'s expected encoding, and always exist in the [`Text`]. The
+/// start of a range is never after its end.
+pub fn change_for(text: &Text) -> impl Strategy<Value = Change<'static>> {
+    let positions = positions(text);
+    let range = (select(positions.clone()), select(positions.clone()))
+        .prop_map(|(a, b)| (a.min(b), a.max(b)));
+    prop_oneof![
+        range
+            .clone()
+            .prop_map(|(start, end)| Change::Delete { start, end }),
+        (select(positions), content()).prop_map(|(at, text)| Change::Insert {
+            at,
+            text: text.into()
+        }),
+        (range, content()).prop_map(|((start, end), text)| Change::Replace {
+            start,
+            end,
+            text: text.into()
+        }),
+        content().prop_map(|text| Change::ReplaceFull(text.into())),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::{change_for, text};
+    use crate::{core::history::History, error::Encoding};
+
+    fn text_and_change(
+        encoding: Encoding,
+    ) -> impl Strategy<Value = (crate::core::text::Text, crate::change::Change<'static>)> {
+        text(encoding).prop_flat_map(|t| (Just(t.clone()), change_for(&t)))
+    }
+
+    proptest! {
+        #[test]
+        fn changes_apply((mut t, change) in text_and_change(Encoding::UTF16)) {
+            t.update(change, &mut ()).unwrap();
+            prop_assert!(t.verify_invariants());
+        }
+
+        #[test]
+        fn undo_restores((t, change) in text_and_change(Encoding::UTF8)) {
+            let mut history = History::new(t.clone());
+            history.update(change, &mut ()).unwrap();
+            history.undo(&mut ()).unwrap();
+            prop_assert_eq!(history.text(), &t);
+        }
+    }
+}
@@ -1,6 +1,12 @@
+use std::{borrow::Cow, ops::Range};
+
 use tracing::instrument;
 
-use crate::{change::GridIndex, core::eol_indexes::EolIndexes, error::Result};
+use crate::{
+    change::{Change, GridIndex},
+    core::{eol_indexes::EolIndexes, text::Text},
+    error::Result,
+};
 
 /// Information related to a specific change performed on a [`Text`][`crate::core::text::Text`].
 #[derive(Clone, Debug)]
@@ -38,17 +44,109 @@ pub struct UpdateContext<'a> {
     pub old_str: &'a str,
 }
 
+impl<'a> UpdateContext<'a> {
+    /// Returns the byte range of the old string that is edited, and the string it is replaced
+    /// with.
+    pub(crate) fn edited_bytes(&self) -> (Range<usize>, &'a str) {
+        let old_br = self.old_breaklines;
+        let old_str = self.old_str;
+        // a row past the old rows only exists when a line break was pushed to the end of the
+        // string, in which case it starts at the end of the string
+        let byte = |pos: GridIndex| {
+            old_br
+                .row_start(pos.row)
+                .map_or(old_str.len(), |rs| rs + pos.col)
+                .min(old_str.len())
+        };
+        match self.change {
+            ChangeContext::Insert { position, text, .. } => (byte(position)..byte(position), text),
+            ChangeContext::Delete { start, end } => (byte(start)..byte(end), ""),
+            ChangeContext::Replace {
+                start, end, text, ..
+            } => (byte(start)..byte(end), text),
+            ChangeContext::ReplaceFull { text } => (0..old_str.len(), text),
+        }
+    }
+
+    /// Maps a byte offset of the old string to the same position in the new string.
+    ///
+    /// Offsets before the edited range, including the start of the range, are left as is, and
+    /// offsets at or after the end of the range are shifted by the change in length. An offset
+    /// inside of the edited range snaps to the start of the edit. Every offset is inside of the
+    /// edited range when the full content is replaced.
+    ///
+    /// The old positions are read from [`UpdateContext::old_breaklines`], so this should only be
+    /// used by an [`Updateable`] whose [`Updateable::needs_old_state`] returns `true`.
+    pub fn remap_byte(&self, old_byte: usize) -> usize {
+        let (range, text) = self.edited_bytes();
+        if old_byte <= range.start {
+            old_byte
+        } else if old_byte >= range.end {
+            old_byte - range.len() + text.len()
+        } else {
+            range.start
+        }
+    }
+}
+
 pub trait Updateable {
+    /// Whether [`UpdateContext::old_breaklines`] is read by the [`Updateable`].
+    ///
+    /// When this returns `false`, the previous EOL positions are not copied before an edit, and
+    /// [`UpdateContext::old_breaklines`] may contain stale values. Only return `false` if the
+    /// implementation never reads the old positions.
+    ///
+    /// The default implementation returns `true`.
+    #[inline]
+    fn needs_old_state(&self) -> bool {
+        true
+    }
+
+    /// Update the state with the provided [`UpdateContext`].
+    ///
+    /// This is called before the string of the [`Text`] is modified. Returning an [`Err`] aborts
+    /// the edit, which leaves the [`Text`] unchanged and returns the error from the method that
+    /// performed the edit.
+    ///
+    /// [`Text`]: crate::core::text::Text
     fn update(&mut self, ctx: UpdateContext) -> Result<()>;
+
+    /// Whether the edit described by the [`UpdateContext`] should be applied.
+    ///
+    /// This is called before [`Updateable::update`] and before the string of the [`Text`] is
+    /// modified. Returning `false` cancels the edit without it being an error, in which case
+    /// [`Updateable::update`] is not called, the [`Text`] is left unchanged, and the method that
+    /// performed the edit returns `Ok(())`. This can be used to guard read-only regions, or to only
+    /// allow certain edits.
+    ///
+    /// The default implementation applies every edit.
+    ///
+    /// [`Text`]: crate::core::text::Text
+    #[inline]
+    fn should_apply(&mut self, _ctx: &UpdateContext) -> bool {
+        true
+    }
 }
 
 impl Updateable for () {
+    fn needs_old_state(&self) -> bool {
+        false
+    }
+
     fn update(&mut self, _: UpdateContext) -> Result<()> {
         Ok(())
     }
 }
 
 impl<T: Updateable> Updateable for [T] {
+    fn needs_old_state(&self) -> bool {
+        self.iter().any(|u| u.needs_old_state())
+    }
+
+    fn should_apply(&mut self, ctx: &UpdateContext) -> bool {
+        self.iter_mut().all(|u| u.should_apply(ctx))
+    }
+
     fn update(&mut self, ctx: UpdateContext) -> Result<()> {
         for u in self.iter_mut() {
             u.update(ctx.clone())?;
@@ -58,6 +156,26 @@ impl<T: Updateable> Updateable for [T] {
     }
 }
 
+/// Updates both [`Updateable`]s in order, such as a `tree_sitter::Tree` alongside an
+/// [`AnchorSet`][`crate::core::anchor::AnchorSet`].
+///
+/// The edit is only applied if both of them allow it. If the first one returns an error, the
+/// second one is not updated.
+impl<A: Updateable, B: Updateable> Updateable for (A, B) {
+    fn needs_old_state(&self) -> bool {
+        self.0.needs_old_state() || self.1.needs_old_state()
+    }
+
+    fn should_apply(&mut self, ctx: &UpdateContext) -> bool {
+        self.0.should_apply(ctx) && self.1.should_apply(ctx)
+    }
+
+    fn update(&mut self, ctx: UpdateContext) -> Result<()> {
+        self.0.update(ctx.clone())?;
+        self.1.update(ctx)
+    }
+}
+
 impl<T> Updateable for T
 where
     T: FnMut(UpdateContext) -> Result<()>,
@@ -68,31 +186,260 @@ where
     }
 }
 
+/// An [`Updateable`] that records every change it is provided with.
+///
+/// The recorded changes can be inspected, or replayed on another [`Text`] to reproduce the edits.
+/// The positions of the recorded changes are UTF-8 positions, use [`ChangeRecorder::replay`] to
+/// apply them to a [`Text`] that expects a different encoding.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ChangeRecorder {
+    changes: Vec<Change<'static>>,
+}
+
+impl ChangeRecorder {
+    /// Creates a new [`ChangeRecorder`] with no recorded changes.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the recorded changes in the order they were applied.
+    pub fn changes(&self) -> &[Change<'static>] {
+        &self.changes
+    }
+
+    /// Returns the recorded changes, leaving the recorder empty.
+    pub fn take(&mut self) -> Vec<Change<'static>> {
+        std::mem::take(&mut self.changes)
+    }
+
+    /// Applies the recorded changes to the provided [`Text`] in the order they were recorded.
+    ///
+    /// Before each change is applied, its positions are converted from UTF-8 to the [`Text`]'s
+    /// expected encoding. Replaying on a copy of the [`Text`] the changes were recorded from
+    /// reproduces the edits, regardless of its encoding.
+    pub fn replay<U: Updateable + ?Sized>(
+        &self,
+        text: &mut Text,
+        updateable: &mut U,
+    ) -> Result<()> {
+        // a position on the row after the last row is used as is, as the row does not exist yet
+        let denormalize = |mut pos: GridIndex, text: &Text| -> Result<GridIndex> {
+            if pos.row < text.br_indexes.row_count().get() {
+                pos.denormalize(text)?;
+            }
+            Ok(pos)
+        };
+        for change in &self.changes {
+            let change = match change {
+                Change::Insert { at, text: s } => Change::Insert {
+                    at: denormalize(*at, text)?,
+                    text: Cow::Borrowed(s),
+                },
+                Change::Delete { start, end } => Change::Delete {
+                    start: denormalize(*start, text)?,
+                    end: denormalize(*end, text)?,
+                },
+                Change::Replace {
+                    start,
+                    end,
+                    text: s,
+                } => Change::Replace {
+                    start: denormalize(*start, text)?,
+                    end: denormalize(*end, text)?,
+                    text: Cow::Borrowed(s),
+                },
+                Change::ReplaceFull(s) => Change::ReplaceFull(Cow::Borrowed(s)),
+            };
+            text.update(change, updateable)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Updateable for ChangeRecorder {
+    fn needs_old_state(&self) -> bool {
+        false
+    }
+
+    fn update(&mut self, ctx: UpdateContext) -> Result<()> {
+        let change = match ctx.change {
+            ChangeContext::Insert { position, text, .. } => Change::Insert {
+                at: position,
+                text: text.to_string().into(),
+            },
+            ChangeContext::Delete { start, end } => Change::Delete { start, end },
+            ChangeContext::Replace {
+                start, end, text, ..
+            } => Change::Replace {
+                start,
+                end,
+                text: text.to_string().into(),
+            },
+            ChangeContext::ReplaceFull { text } => Change::ReplaceFull(text.to_string().into()),
+        };
+        self.changes.push(change);
+
+        Ok(())
+    }
+}
+
+/// An [`Updateable`] that keeps track of the number of words in a [`Text`].
+///
+/// A word is a run of non-whitespace characters, the same as what [`str::split_whitespace`]
+/// returns. This means punctuation is counted as a part of the word it is attached to, and a
+/// standalone punctuation character is counted as a word.
+///
+/// Instead of counting the words of the whole content on every change, only the words that are
+/// touching the edited range are counted again.
+///
+/// [`Text`]: crate::core::text::Text
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct WordCounter {
+    count: usize,
+}
+
+impl WordCounter {
+    /// Creates a new [`WordCounter`] with the number of words in the provided string.
+    ///
+    /// The provided string should be the current content of the [`Text`] that will be updated.
+    ///
+    /// [`Text`]: crate::core::text::Text
+    pub fn new(s: &str) -> Self {
+        Self {
+            count: count_words(s.chars()),
+        }
+    }
+
+    /// Returns the current number of words.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+}
+
+impl Updateable for WordCounter {
+    fn update(&mut self, ctx: UpdateContext) -> Result<()> {
+        if let ChangeContext::ReplaceFull { text } = ctx.change {
+            self.count = count_words(text.chars());
+            return Ok(());
+        }
+        let (edited, text) = ctx.edited_bytes();
+        let (start, end) = (edited.start, edited.end);
+
+        // extend the edited range to the surrounding whitespace, so that the words that the edit
+        // splits or joins are counted again
+        let old = ctx.old_str;
+        let prefix = old[..start].trim_end_matches(|c: char| !c.is_whitespace());
+        let prefix = &old[prefix.len()..start];
+        let suffix = old[end..].trim_start_matches(|c: char| !c.is_whitespace());
+        let suffix = &old[end..old.len() - suffix.len()];
+
+        let removed = count_words(
+            prefix
+                .chars()
+                .chain(old[start..end].chars())
+                .chain(suffix.chars()),
+        );
+        let added = count_words(prefix.chars().chain(text.chars()).chain(suffix.chars()));
+        self.count = self.count + added - removed;
+
+        Ok(())
+    }
+}
+
+fn count_words(chars: impl Iterator<Item = char>) -> usize {
+    let mut count = 0;
+    let mut in_word = false;
+    for c in chars {
+        let is_word = !c.is_whitespace();
+        if is_word && !in_word {
+            count += 1;
+        }
+        in_word = is_word;
+    }
+
+    count
+}
+
+/// [`Updateable`] implementations for [`tree_sitter`] types.
 #[cfg_attr(docsrs, doc(cfg(feature = "tree-sitter")))]
 #[cfg(feature = "tree-sitter")]
-mod ts {
+pub mod ts {
     use tracing::info;
     use tree_sitter::{InputEdit, Node, Point, Tree};
 
-    use crate::error::{Error, Result};
+    use crate::{
+        core::eol_indexes::EolIndexes,
+        error::{Error, Result},
+    };
 
     use super::{ChangeContext, UpdateContext, Updateable};
 
     impl Updateable for Tree {
         fn update(&mut self, ctx: UpdateContext) -> Result<()> {
-            self.edit(&edit_from_ctx(ctx)?);
+            self.edit(&input_edit_from_ctx(&ctx)?);
             Ok(())
         }
     }
 
     impl Updateable for Node<'_> {
         fn update(&mut self, ctx: UpdateContext) -> Result<()> {
-            self.edit(&edit_from_ctx(ctx)?);
+            self.edit(&input_edit_from_ctx(&ctx)?);
+            Ok(())
+        }
+    }
+
+    /// Allows updating multiple trees with a single edit by providing a `&mut [&mut Tree]`.
+    ///
+    /// This is useful when a document contains multiple languages, each with their own
+    /// [`Tree`], as every tree will receive the same [`InputEdit`].
+    impl Updateable for &mut Tree {
+        fn update(&mut self, ctx: UpdateContext) -> Result<()> {
+            self.edit(&input_edit_from_ctx(&ctx)?);
+            Ok(())
+        }
+    }
+
+    impl Updateable for &mut Node<'_> {
+        fn update(&mut self, ctx: UpdateContext) -> Result<()> {
+            self.edit(&input_edit_from_ctx(&ctx)?);
             Ok(())
         }
     }
 
-    pub(super) fn edit_from_ctx(ctx: UpdateContext) -> Result<InputEdit> {
+    /// Computes the [`InputEdit`] for the change described by the [`UpdateContext`].
+    ///
+    /// This is the same [`InputEdit`] used to update a [`Tree`] or a [`Node`], and can be used to
+    /// write custom update logic, such as editing multiple nodes of a tree with a single change.
+    ///
+    /// Returns an error if a row of the change does not exist in the [`UpdateContext`]'s EOL
+    /// positions, which cannot happen for a context provided by a
+    /// [`Text`][`crate::core::text::Text`].
+    ///
+    /// ```
+    /// use texter::{
+    ///     change::GridIndex,
+    ///     core::text::Text,
+    ///     updateables::{ts::input_edit_from_ctx, UpdateContext},
+    /// };
+    /// use tree_sitter::Parser;
+    ///
+    /// let mut parser = Parser::new();
+    /// parser.set_language(&tree_sitter_html::LANGUAGE.into()).unwrap();
+    /// let mut text = Text::new("<p>Hello</p>".into());
+    /// let tree = parser.parse(&text.text, None).unwrap();
+    /// // the "</p>" tag
+    /// let mut end_tag = tree.root_node().child(0).unwrap().child(2).unwrap();
+    /// assert_eq!(end_tag.start_byte(), 8);
+    ///
+    /// text.insert("<b>Hi</b>", GridIndex { row: 0, col: 3 }, &mut |ctx: UpdateContext| {
+    ///     end_tag.edit(&input_edit_from_ctx(&ctx)?);
+    ///     Ok(())
+    /// })
+    /// .unwrap();
+    /// assert_eq!(end_tag.start_byte(), 17);
+    /// ```
+    pub fn input_edit_from_ctx(ctx: &UpdateContext) -> Result<InputEdit> {
         let old_br = ctx.old_breaklines;
         let new_br = ctx.breaklines;
         let ie = match ctx.change {
@@ -115,11 +462,7 @@ mod ts {
                     new_end_byte: start_byte,
                 }
             }
-            ChangeContext::Insert {
-                inserted_br_indexes,
-                position,
-                text,
-            } => {
+            ChangeContext::Insert { position, text, .. } => {
                 let start_byte = old_br
                     .row_start(position.row)
                     .ok_or(Error::oob_row(ctx.breaklines.row_count(), position.row))?
@@ -131,21 +474,11 @@ mod ts {
                     new_end_byte,
                     start_position: position.into(),
                     old_end_position: position.into(),
-                    new_end_position: Point {
-                        row: position.row + inserted_br_indexes.len(),
-                        // -1 because bri includes the breakline
-                        column: inserted_br_indexes
-                            .last()
-                            .map(|bri| text.len() - (bri - start_byte) - 1)
-                            .unwrap_or(text.len() + position.col),
-                    },
+                    new_end_position: point_at(new_br, new_end_byte),
                 }
             }
             ChangeContext::Replace {
-                start,
-                end,
-                text,
-                inserted_br_indexes,
+                start, end, text, ..
             } => {
                 let row_count = ctx.breaklines.row_count();
                 let start_byte = old_br
@@ -162,20 +495,7 @@ mod ts {
                     old_end_position: end.into(),
                     old_end_byte,
                     new_end_byte: start_byte + text.len(),
-                    new_end_position: {
-                        if let [.., last] = inserted_br_indexes {
-                            Point {
-                                row: start.row + inserted_br_indexes.len(),
-                                // -1 because last includes the breakline
-                                column: text.len() - (last - start_byte) - 1,
-                            }
-                        } else {
-                            Point {
-                                row: start.row,
-                                column: start.col + text.len(),
-                            }
-                        }
-                    },
+                    new_end_position: point_at(new_br, start_byte + text.len()),
                 }
             }
             ChangeContext::ReplaceFull { text } => InputEdit {
@@ -196,24 +516,113 @@ mod ts {
         info!("{:?}", ie);
         Ok(ie)
     }
+
+    /// Computes the [`Point`] of a byte in the new string.
+    ///
+    /// Tree-sitter expects the column to be the byte offset from the start of the row. Since the
+    /// row is searched for in the new EOL positions, line breaks that were merged by the edit are
+    /// accounted for as well.
+    fn point_at(br_indexes: &EolIndexes, byte: usize) -> Point {
+        let row = br_indexes.0[1..].partition_point(|&eol| eol < byte);
+        let row_start = br_indexes.row_start(row).expect("the row is in bounds");
+        Point {
+            row,
+            column: byte - row_start,
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    mod dyn_updateable {
+        use crate::{
+            change::GridIndex,
+            core::text::Text,
+            updateables::{ChangeRecorder, Updateable},
+        };
+
+        #[test]
+        fn trait_object() {
+            let mut t = Text::new("abc".into());
+            let mut recorder = ChangeRecorder::new();
+            let u: &mut dyn Updateable = &mut recorder;
+            assert!(!u.needs_old_state());
+            t.insert("X", GridIndex { row: 0, col: 1 }, u).unwrap();
+            assert_eq!(t.text, "aXbc");
+            assert_eq!(recorder.changes().len(), 1);
+        }
+    }
+
+    mod remap_byte {
+        use crate::{
+            change::{Change, GridIndex},
+            core::text::Text,
+            updateables::UpdateContext,
+        };
+
+        /// Returns the remapped offsets of the old string for the provided change.
+        fn remapped(s: &str, old: &[usize], change: Change) -> Vec<usize> {
+            let mut t = Text::new(s.into());
+            let mut new = vec![];
+            t.update(change, &mut |ctx: UpdateContext| {
+                new = old.iter().map(|&b| ctx.remap_byte(b)).collect();
+                Ok(())
+            })
+            .unwrap();
+            new
+        }
+
+        #[test]
+        fn insert() {
+            // "ab\ncd" -> "ab\ncXYd"
+            let change = Change::Insert {
+                at: GridIndex { row: 1, col: 1 },
+                text: "XY".into(),
+            };
+            // the insertion position itself is not moved
+            assert_eq!(remapped("ab\ncd", &[1, 4, 5, 6], change), [1, 4, 7, 8]);
+        }
+
+        #[test]
+        fn delete() {
+            // "abc\ndef" -> "adef"
+            let change = Change::Delete {
+                start: GridIndex { row: 0, col: 1 },
+                end: GridIndex { row: 1, col: 0 },
+            };
+            // offsets inside of the deleted range snap to its start
+            assert_eq!(
+                remapped("abc\ndef", &[0, 1, 2, 3, 4, 5, 7], change),
+                [0, 1, 1, 1, 1, 2, 4]
+            );
+        }
+
+        #[test]
+        fn replace() {
+            // "abc\ndef" -> "aXYZ\ndef"
+            let change = Change::Replace {
+                start: GridIndex { row: 0, col: 1 },
+                end: GridIndex { row: 0, col: 3 },
+                text: "XYZ".into(),
+            };
+            assert_eq!(remapped("abc\ndef", &[1, 2, 3, 5], change), [1, 1, 4, 6]);
+        }
+    }
+
     #[cfg(feature = "tree-sitter")]
     mod ts {
         use tree_sitter::{InputEdit, Point};
 
         use crate::{
             change::GridIndex,
-            core::eol_indexes::EolIndexes,
-            updateables::{ts::edit_from_ctx, ChangeContext, UpdateContext},
+            core::{eol_indexes::EolIndexes, text::Text},
+            updateables::{ts::input_edit_from_ctx, ChangeContext, UpdateContext},
         };
 
         #[test]
         fn edit_ctx_delete_across_lines() {
             // old_str: "HelJuice";
-            let edit = edit_from_ctx(UpdateContext {
+            let edit = input_edit_from_ctx(&UpdateContext {
                 breaklines: &EolIndexes(vec![0]),
                 old_breaklines: &EolIndexes(vec![0, 12, 16, 20]),
                 old_str: "Hello World!\n123\nasd\nAppleJuice",
@@ -238,7 +647,7 @@ mod tests {
         #[test]
         fn edit_ctx_delete_in_line_first_row() {
             // let old = "Hello World!\nd\nAppleJuice";
-            let edit = edit_from_ctx(UpdateContext {
+            let edit = input_edit_from_ctx(&UpdateContext {
                 breaklines: &EolIndexes(vec![0, 8, 12, 20]),
                 old_breaklines: &EolIndexes(vec![0, 12, 16, 20]),
                 old_str: "Hello World!\n123\nasd\nAppleJuice",
@@ -263,7 +672,7 @@ mod tests {
         #[test]
         fn edit_ctx_delete_in_line_last_row() {
             // let old = "Hello World!\nd\nAppleJuice";
-            let edit = edit_from_ctx(UpdateContext {
+            let edit = input_edit_from_ctx(&UpdateContext {
                 breaklines: &EolIndexes(vec![0, 12, 16, 20]),
                 old_breaklines: &EolIndexes(vec![0, 12, 16, 20]),
                 old_str: "Hello World!\n123\nasd\nAppleJuice",
@@ -287,7 +696,7 @@ mod tests {
 
         #[test]
         fn edit_ctx_insert() {
-            let edit = edit_from_ctx(UpdateContext {
+            let edit = input_edit_from_ctx(&UpdateContext {
                 breaklines: &EolIndexes(vec![0, 12, 16, 20]),
                 old_breaklines: &EolIndexes(vec![0, 12, 14]),
                 old_str: "Hello World!\nd\nAppleJuice",
@@ -310,10 +719,52 @@ mod tests {
             assert_eq!(edit, Ok(correct_edit));
         }
 
+        /// Collects the [`InputEdit`] of an insert performed on a [`Text`].
+        fn insert_edit(text: &str, s: &str, at: GridIndex) -> InputEdit {
+            let mut t = Text::new(text.into());
+            let mut edit = None;
+            t.insert(s, at, &mut |ctx: UpdateContext| {
+                edit = Some(input_edit_from_ctx(&ctx)?);
+                Ok(())
+            })
+            .unwrap();
+            edit.unwrap()
+        }
+
+        #[test]
+        fn edit_ctx_insert_multibyte() {
+            let edit = insert_edit(
+                "<p>ab</p>\n<p>cd</p>",
+                "ゲ\nート",
+                GridIndex { row: 1, col: 3 },
+            );
+            let correct_edit = InputEdit {
+                start_byte: 13,
+                start_position: Point { row: 1, column: 3 },
+                old_end_byte: 13,
+                old_end_position: Point { row: 1, column: 3 },
+                new_end_byte: 23,
+                // the column is the byte length of "ート"
+                new_end_position: Point { row: 2, column: 6 },
+            };
+            assert_eq!(edit, correct_edit);
+
+            let edit = insert_edit("aゲb", "ート", GridIndex { row: 0, col: 4 });
+            assert_eq!(edit.new_end_position, Point { row: 0, column: 10 });
+        }
+
+        #[test]
+        fn edit_ctx_insert_merged_crlf() {
+            // the inserted "\n" joins the existing "\r" in to a single line break
+            let edit = insert_edit("a\rb", "\nc", GridIndex { row: 1, col: 0 });
+            assert_eq!(edit.new_end_byte, 4);
+            assert_eq!(edit.new_end_position, Point { row: 1, column: 1 });
+        }
+
         #[test]
         fn edit_ctx_replace_shrink() {
             // old = "HelloWelcomedhasgdjh\nAppleJuice";
-            let edit = edit_from_ctx(UpdateContext {
+            let edit = input_edit_from_ctx(&UpdateContext {
                 breaklines: &EolIndexes(vec![0, 20]),
                 old_breaklines: &EolIndexes(vec![0, 12, 31]),
                 old_str: "Hello World!\ndgsadhasgjdhasgdjh\nAppleJuice",
@@ -340,7 +791,7 @@ mod tests {
         #[test]
         fn edit_ctx_replace_grow() {
             //let result = "HelloWelcome\narld!\ndgsadhasgjdhasgdjh\nAppleJuice";
-            let edit = edit_from_ctx(UpdateContext {
+            let edit = input_edit_from_ctx(&UpdateContext {
                 breaklines: &EolIndexes(vec![0, 12, 18, 39]),
                 old_breaklines: &EolIndexes(vec![0, 12, 21]),
                 old_str: "Hello World!\ndgsadhasgjdhasgdjh\nAppleJuice",
@@ -367,7 +818,7 @@ mod tests {
         #[test]
         fn edit_ctx_replace_full() {
             //let result = "HelloWelcome\narld!\ndgsadhasgjdhasgdjh\nAppleJuice";
-            let edit = edit_from_ctx(UpdateContext {
+            let edit = input_edit_from_ctx(&UpdateContext {
                 breaklines: &EolIndexes(vec![0, 10, 19, 20, 21, 39]),
                 old_breaklines: &EolIndexes(vec![0, 12, 31]),
                 old_str: "Hello World!\ndgsadhasgjdhasgdjh\nAppleJuice",
@@ -542,5 +993,237 @@ mod tests {
 
             assert_eq!(prev, modified.text.len());
         }
+
+        fn node_ranges(tree: &Tree) -> Vec<(usize, usize, Point, Point)> {
+            let mut ranges = vec![];
+            let mut cursor = tree.walk();
+            'outer: loop {
+                let node = cursor.node();
+                ranges.push((
+                    node.start_byte(),
+                    node.end_byte(),
+                    node.start_position(),
+                    node.end_position(),
+                ));
+                if cursor.goto_first_child() || cursor.goto_next_sibling() {
+                    continue;
+                }
+                while cursor.goto_parent() {
+                    if cursor.goto_next_sibling() {
+                        continue 'outer;
+                    }
+                }
+                break;
+            }
+
+            ranges
+        }
+
+        #[rstest]
+        fn multiple_trees(mut html_text: Text, html_tree: Tree) {
+            let mut first = html_tree.clone();
+            let mut second = html_tree.clone();
+            let mut expected = html_tree;
+
+            let change = Change::Insert {
+                at: ATTRIBUTE_NAME_POS.into(),
+                text: "some-attr\n".into(),
+            };
+            html_text
+                .clone()
+                .update(change.clone(), &mut expected)
+                .unwrap();
+            html_text
+                .update(change, &mut [&mut first, &mut second][..])
+                .unwrap();
+
+            let expected = node_ranges(&expected);
+            assert_eq!(node_ranges(&first), expected);
+            assert_eq!(node_ranges(&second), expected);
+        }
+    }
+
+    mod change_recorder {
+        use crate::{
+            change::{Change, GridIndex},
+            core::text::Text,
+            updateables::ChangeRecorder,
+        };
+
+        #[test]
+        fn replay() {
+            const S: &str = "fn main() {\r\n    ゲート();\n}";
+            let mut t = Text::new(S.into());
+            let mut recorder = ChangeRecorder::new();
+            t.insert(
+                "let x = 1;\n    ",
+                GridIndex { row: 1, col: 4 },
+                &mut recorder,
+            )
+            .unwrap();
+            t.delete(
+                GridIndex { row: 2, col: 4 },
+                GridIndex { row: 2, col: 7 },
+                &mut recorder,
+            )
+            .unwrap();
+            t.replace(
+                "fn a()",
+                GridIndex { row: 0, col: 0 },
+                GridIndex { row: 0, col: 9 },
+                &mut recorder,
+            )
+            .unwrap();
+            t.insert("\n", GridIndex { row: 4, col: 0 }, &mut recorder)
+                .unwrap();
+
+            assert_eq!(
+                recorder.changes()[1],
+                Change::Delete {
+                    start: GridIndex { row: 2, col: 4 },
+                    end: GridIndex { row: 2, col: 7 },
+                }
+            );
+            assert_eq!(t.text, "fn a() {\r\n    let x = 1;\n    ート();\n}\n\n");
+
+            let mut replayed = Text::new(S.into());
+            for change in recorder.take() {
+                replayed.update(change, &mut ()).unwrap();
+            }
+            assert_eq!(replayed, t);
+            assert!(recorder.changes().is_empty());
+
+            t.replace_full("a".into(), &mut recorder).unwrap();
+            assert_eq!(recorder.changes(), [Change::ReplaceFull("a".into())]);
+        }
+
+        #[test]
+        fn replay_utf16() {
+            const S: &str = "ゲート\r\n😀b";
+            let mut t = Text::new_utf16(S.into());
+            let mut recorder = ChangeRecorder::new();
+            t.insert("x", GridIndex { row: 0, col: 1 }, &mut recorder)
+                .unwrap();
+            t.replace(
+                "y\n",
+                GridIndex { row: 1, col: 2 },
+                GridIndex { row: 1, col: 3 },
+                &mut recorder,
+            )
+            .unwrap();
+            t.delete(
+                GridIndex { row: 0, col: 2 },
+                GridIndex { row: 0, col: 3 },
+                &mut recorder,
+            )
+            .unwrap();
+            t.insert("!", GridIndex { row: 3, col: 0 }, &mut recorder)
+                .unwrap();
+            assert_eq!(t.text, "ゲxト\r\n😀y\n\n!");
+            // the recorded positions are UTF-8 positions
+            assert_eq!(
+                recorder.changes()[0],
+                Change::Insert {
+                    at: GridIndex { row: 0, col: 3 },
+                    text: "x".into(),
+                }
+            );
+
+            let mut replayed = Text::new_utf16(S.into());
+            recorder.replay(&mut replayed, &mut ()).unwrap();
+            assert_eq!(replayed, t);
+        }
+    }
+
+    mod word_counter {
+        use crate::{change::GridIndex, core::text::Text, updateables::WordCounter};
+
+        #[test]
+        fn matches_recount() {
+            const S: &str = "Hello, World!\r\n  ゲート の\tテスト\n\nlast line";
+            let mut t = Text::new(S.into());
+            let mut counter = WordCounter::new(S);
+            assert_eq!(counter.count(), 7);
+
+            let check = |t: &Text, counter: &WordCounter| {
+                assert_eq!(
+                    counter.count(),
+                    t.text.split_whitespace().count(),
+                    "{:?}",
+                    t.text
+                );
+            };
+
+            // split a word
+            t.insert(" ", GridIndex { row: 0, col: 2 }, &mut counter)
+                .unwrap();
+            check(&t, &counter);
+            // insert in to the middle of a word
+            t.insert("abc", GridIndex { row: 0, col: 1 }, &mut counter)
+                .unwrap();
+            check(&t, &counter);
+            // join two words across lines
+            t.delete(
+                GridIndex { row: 0, col: 10 },
+                GridIndex { row: 1, col: 2 },
+                &mut counter,
+            )
+            .unwrap();
+            check(&t, &counter);
+            // replace whitespace with a word
+            t.replace(
+                "x\ny z",
+                GridIndex { row: 1, col: 0 },
+                GridIndex { row: 2, col: 0 },
+                &mut counter,
+            )
+            .unwrap();
+            check(&t, &counter);
+            t.insert("\n", GridIndex { row: 2, col: 3 }, &mut counter)
+                .unwrap();
+            check(&t, &counter);
+            t.delete(
+                GridIndex { row: 0, col: 0 },
+                GridIndex { row: 3, col: 2 },
+                &mut counter,
+            )
+            .unwrap();
+            check(&t, &counter);
+
+            t.replace_full("a b c".into(), &mut counter).unwrap();
+            assert_eq!(counter.count(), 3);
+        }
+
+        #[test]
+        fn past_last_row() {
+            let mut t = Text::new("a b".into());
+            let mut counter = WordCounter::new("a b");
+            t.insert("c d", GridIndex { row: 1, col: 0 }, &mut counter)
+                .unwrap();
+            assert_eq!(t.text, "a b\nc d");
+            assert_eq!(counter.count(), 4);
+            t.replace(
+                "e",
+                GridIndex { row: 2, col: 0 },
+                GridIndex { row: 2, col: 0 },
+                &mut counter,
+            )
+            .unwrap();
+            assert_eq!(t.text, "a b\nc d\ne");
+            assert_eq!(counter.count(), 5);
+        }
+
+        #[test]
+        fn empty() {
+            let mut t = Text::new(String::new());
+            let mut counter = WordCounter::new("");
+            assert_eq!(counter.count(), 0);
+            t.insert("  ", GridIndex { row: 0, col: 0 }, &mut counter)
+                .unwrap();
+            assert_eq!(counter.count(), 0);
+            t.insert("a", GridIndex { row: 0, col: 1 }, &mut counter)
+                .unwrap();
+            assert_eq!(counter.count(), 1);
+        }
     }
 }
@@ -1,40 +1,34 @@
-/// A slightly faster [`str::trim_end_matches`] for trimming EOL bytes.
+/// Returns the index of the first byte of the line break whose last byte is at `eol`.
+///
+/// This is the end of the content of the line that is terminated by the line break.
 #[inline]
-pub(crate) fn trim_eol_from_end(base_line: &str) -> &str {
-    let eol_len = match base_line.as_bytes() {
-        // This pattern should come first as the following pattern could cause an EOL to be
-        // included.
-        [.., b'\r', b'\n'] => 2,
-        [.., b'\n' | b'\r'] => 1,
-        _ => 0,
-    };
-
-    // Since the provided range is based on the length of the str - EOL bytes,
-    // worst we can get is an empty str. We only matched on ascii character bytes,
-    // and any byte of a multibyte UTF8 character cannot match with any ascii byte.
-    let r = &base_line[..base_line.len() - eol_len];
-
-    // Using a debug assert as the line could be long.
-    debug_assert!(!r.contains(['\r', '\n']));
-    r
+pub(crate) fn eol_start(s: &str, eol: usize) -> usize {
+    let bytes = s.as_bytes();
+    match bytes[eol] {
+        b'\n' if eol > 0 && bytes[eol - 1] == b'\r' => eol - 1,
+        b'\n' | b'\r' => eol,
+        // a multibyte line break, such as U+2028
+        _ => (0..=eol)
+            .rev()
+            .find(|&i| s.is_char_boundary(i))
+            .expect("zero is always a char boundary"),
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::trim_eol_from_end;
+    use super::eol_start;
 
     #[test]
     fn non_last_row_trimming() {
-        for normalized in [
-            "Hello, World",
-            "Hello, World\r",
-            "Hello, World\r\n",
-            "Hello, World\n",
-        ]
-        .into_iter()
-        .map(trim_eol_from_end)
-        {
-            assert_eq!("Hello, World", normalized);
+        for (s, eol) in [
+            ("Hello, World\r", 12),
+            ("Hello, World\r\n", 13),
+            ("Hello, World\n", 12),
+            ("Hello, World\u{85}", 13),
+            ("Hello, World\u{2028}", 14),
+        ] {
+            assert_eq!("Hello, World", &s[..eol_start(s, eol)]);
         }
     }
 }